@@ -0,0 +1,794 @@
+use amethyst::assets::*;
+use amethyst::ecs::*;
+use amethyst::renderer::JpgFormat;
+use amethyst::renderer::Material;
+use amethyst::renderer::MaterialDefaults;
+use amethyst::renderer::PngFormat;
+use amethyst::renderer::Texture;
+use amethyst::renderer::TextureMetadata;
+use amethyst::renderer::TextureOffset;
+use image;
+use ron;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::Read as IORead;
+use std::path::Path;
+
+use {ColorMaterialCache, ColorPalette, Heightmap, TextureAtlas};
+
+/// Loads asset from the so-called asset packs
+/// It caches assets which you can manually load or unload on demand.
+///
+/// Example:
+/// If the folder structure looks like this
+/// /assets/base/sprites/player.png
+/// /assets/base/sounds/click.ogg
+/// /assets/base/models/cube.obj
+/// /assets/mod1/sprites/player.png
+/// /assets/mod1/sounds/click.ogg
+/// /assets/mod2/sounds/click.ogg
+///
+/// resolve_path("sprites/player.png") -> /assets/mod1/sprites/player.png
+/// resolve_path("models/cube.obj") -> /assets/base/models/cube.obj
+/// resolve_path("sounds/click.ogg") -> Unknown.
+pub struct AssetLoader {
+    base_path: String,
+    default_pack: String,
+    asset_packs: Vec<String>,
+}
+
+impl AssetLoader {
+    pub fn new(base_path: &str, default_pack: &str) -> Self {
+        let mut al = AssetLoader {
+            base_path: AssetLoader::sanitize_path_trail_only(&base_path),
+            default_pack: AssetLoader::sanitize_path(&default_pack),
+            asset_packs: Vec::new(),
+        };
+        al.get_asset_packs();
+        al
+    }
+
+    fn sanitize_path_trail_only(path: &str) -> String {
+        let mut out = path.to_string();
+        let chars = path.chars();
+        let last = chars.last().unwrap();
+        if last == '/' {
+            let idx = out.len() - 1;
+            out.remove(idx);
+        }
+        out
+    }
+
+    fn sanitize_path(path: &str) -> String {
+        let mut out = path.to_string();
+        let mut chars = path.chars();
+        let first = chars.next().expect("An empty path was specified!");
+        let last = chars.last().unwrap();
+        if first == '/' {
+            out.remove(0);
+        }
+        if last == '/' {
+            let idx = out.len() - 1;
+            out.remove(idx);
+        }
+        out
+    }
+
+    pub fn resolve_path(&self, path: &str) -> Option<String> {
+        // Try to get from default path
+        let mut res = self.resolve_path_for_pack(path, &self.default_pack);
+
+        // Try to find overrides
+        for p in &self.asset_packs {
+            if p != &self.default_pack {
+                if let Some(r) = self.resolve_path_for_pack(path, &p) {
+                    res = Some(r);
+                }
+            }
+        }
+
+        res
+    }
+
+    fn resolve_path_for_pack(&self, path: &str, pack: &str) -> Option<String> {
+        let mut abs = self.base_path.to_owned() + "/" + pack + "/" + &path.to_owned();
+        if cfg!(windows) {
+            abs = abs.replace("/", "\\");
+        }
+
+        let path = Path::new(&abs);
+        if path.exists() {
+            Some(abs.clone())
+        } else {
+            warn!("Failed to find file at path: {}", abs);
+            None
+        }
+    }
+
+    pub fn get_asset_packs(&mut self) -> &Vec<String> {
+        let mut buf: Option<Vec<String>> = None;
+        if self.asset_packs.len() == 0 {
+            if let Ok(elems) = fs::read_dir(&self.base_path) {
+                buf = Some(
+                    elems
+                        .map(|e| {
+                            let path = &e.unwrap().path();
+                            let tmp = &path.to_str().unwrap()[self.base_path.len()..];
+                            AssetLoader::sanitize_path(&tmp)
+                        }).collect(),
+                );
+            } else {
+                error!(
+                    "Failed to find base_path directory for asset loading: {}",
+                    self.base_path
+                );
+            }
+        }
+
+        if let Some(v) = buf {
+            self.asset_packs = v;
+        }
+
+        &self.asset_packs
+    }
+
+    pub fn get_asset_handle<T>(path: &str, ali: &AssetLoaderInternal<T>) -> Option<Handle<T>> {
+        ali.assets.get(path).cloned()
+    }
+
+    pub fn get_asset<'a, T>(
+        path: &str,
+        ali: &AssetLoaderInternal<T>,
+        storage: &'a AssetStorage<T>,
+    ) -> Option<&'a T>
+    where
+        T: Asset,
+    {
+        if let Some(h) = AssetLoader::get_asset_handle::<T>(path, ali) {
+            storage.get(&h)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_asset_or_load<'a, T, F>(
+        &mut self,
+        path: &str,
+        format: F,
+        options: F::Options,
+        ali: &mut AssetLoaderInternal<T>,
+        storage: &'a mut AssetStorage<T>,
+        loader: &Loader,
+    ) -> Option<&'a T>
+    where
+        T: Asset,
+        F: Format<T> + 'static,
+    {
+        if let Some(h) = AssetLoader::get_asset_handle::<T>(path, ali) {
+            return storage.get(&h);
+            //return Some(a);
+        }
+        if let Some(h) = self.load::<T, F>(path, format, options, ali, storage, loader) {
+            return storage.get(&h);
+        }
+        None
+    }
+
+    pub fn load<T, F>(
+        &self,
+        path: &str,
+        format: F,
+        options: F::Options,
+        ali: &mut AssetLoaderInternal<T>,
+        storage: &mut AssetStorage<T>,
+        loader: &Loader,
+    ) -> Option<Handle<T>>
+    where
+        T: Asset,
+        F: Format<T> + 'static,
+    {
+        if let Some(handle) = AssetLoader::get_asset_handle(path, ali) {
+            return Some(handle);
+        }
+        if let Some(p) = self.resolve_path(path) {
+            let handle: Handle<T> = loader.load(p, format, options, (), storage);
+            ali.assets.insert(String::from(path), handle.clone());
+            return Some(handle);
+        }
+        None
+    }
+
+    /// Only removes the internal Handle<T>. To truly unload the asset, you need to drop all handles that you have to it.
+    pub fn unload<T>(path: &str, ali: &mut AssetLoaderInternal<T>) {
+        ali.assets.remove(path);
+    }
+
+    /// Reads and deserializes a ron file resolved through the asset packs.
+    /// Errors are logged and return None.
+    pub fn load_ron<T: DeserializeOwned>(&self, path: &str) -> Option<T> {
+        let resolved = match self.resolve_path(path) {
+            Some(p) => p,
+            None => {
+                error!("Failed to find ron file: {}", path);
+                return None;
+            }
+        };
+        let mut content = String::new();
+        if let Err(e) = File::open(&resolved).and_then(|mut f| f.read_to_string(&mut content)) {
+            error!("Failed to read ron file {}: {:?}", resolved, e);
+            return None;
+        }
+        match ron::de::from_str::<T>(&content) {
+            Ok(o) => Some(o),
+            Err(e) => {
+                error!("Failed to deserialize ron file {}: {:?}", resolved, e);
+                None
+            }
+        }
+    }
+
+    /*pub fn load_from_extension<T>(&mut self,path: &str,ali: &mut AssetLoaderInternal<T>, storage: &mut AssetStorage<T>, loader: Loader) -> Option<Handle<T>> where T: Asset{
+        let ext = AssetLoader::extension_from_path(path);
+        match ext{
+            "obj" => Some(self.load::<Mesh,ObjFormat>(path,ObjFormat,ali,storage,loader)),
+            _ => None,
+        }
+    }
+
+    pub fn auto_load_from_extension(&mut self,path: &str,res: Resources){
+        let ext = AssetLoader::extension_from_path(path);
+        match ext{
+            "obj" => Some(self.load_from_extension::<Mesh>(ext,res.fetch_mut::<AssetLoaderInternal<Mesh>>(),res.fetch_mut::<AssetStorage<Mesh>>(),res.fetch())),
+            _ => None,
+        };
+    }*/
+
+    /*pub fn extension_from_path(path: &str) -> &str{
+        path.split(".").as_slice().last().clone()
+    }*/
+}
+
+impl Component for AssetLoader {
+    type Storage = VecStorage<Self>;
+}
+
+pub struct AssetLoaderInternal<T> {
+    /// Map path to asset handle.
+    pub assets: HashMap<String, Handle<T>>,
+}
+
+impl<T> Default for AssetLoaderInternal<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> AssetLoaderInternal<T> {
+    pub fn new() -> Self {
+        AssetLoaderInternal {
+            assets: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Component for AssetLoaderInternal<T>
+where
+    T: Send + Sync + 'static,
+{
+    type Storage = VecStorage<Self>;
+}
+
+/// The texture slots of a `Material`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TextureSlot {
+    Albedo,
+    Emission,
+    Normal,
+    Metallic,
+    Roughness,
+    AmbientOcclusion,
+    Caveat,
+}
+
+impl TextureSlot {
+    pub fn all() -> [TextureSlot; 7] {
+        [
+            TextureSlot::Albedo,
+            TextureSlot::Emission,
+            TextureSlot::Normal,
+            TextureSlot::Metallic,
+            TextureSlot::Roughness,
+            TextureSlot::AmbientOcclusion,
+            TextureSlot::Caveat,
+        ]
+    }
+
+    fn texture_mut<'a>(&self, material: &'a mut Material) -> &'a mut Handle<Texture> {
+        match *self {
+            TextureSlot::Albedo => &mut material.albedo,
+            TextureSlot::Emission => &mut material.emission,
+            TextureSlot::Normal => &mut material.normal,
+            TextureSlot::Metallic => &mut material.metallic,
+            TextureSlot::Roughness => &mut material.roughness,
+            TextureSlot::AmbientOcclusion => &mut material.ambient_occlusion,
+            TextureSlot::Caveat => &mut material.caveat,
+        }
+    }
+
+    fn offset_mut<'a>(&self, material: &'a mut Material) -> &'a mut TextureOffset {
+        match *self {
+            TextureSlot::Albedo => &mut material.albedo_offset,
+            TextureSlot::Emission => &mut material.emission_offset,
+            TextureSlot::Normal => &mut material.normal_offset,
+            TextureSlot::Metallic => &mut material.metallic_offset,
+            TextureSlot::Roughness => &mut material.roughness_offset,
+            TextureSlot::AmbientOcclusion => &mut material.ambient_occlusion_offset,
+            TextureSlot::Caveat => &mut material.caveat_offset,
+        }
+    }
+}
+
+/// Where the texture of a material slot comes from.
+#[derive(Clone)]
+pub enum TextureSource {
+    /// A 1x1 texture of this color.
+    Color([f32; 4]),
+    /// A png or jpg file, resolved through the asset packs.
+    File(String),
+    /// A texture that is already loaded.
+    Handle(Handle<Texture>),
+}
+
+impl From<[f32; 4]> for TextureSource {
+    fn from(color: [f32; 4]) -> Self {
+        TextureSource::Color(color)
+    }
+}
+
+impl<'a> From<&'a str> for TextureSource {
+    fn from(path: &'a str) -> Self {
+        TextureSource::File(path.to_string())
+    }
+}
+
+impl From<String> for TextureSource {
+    fn from(path: String) -> Self {
+        TextureSource::File(path)
+    }
+}
+
+impl From<Handle<Texture>> for TextureSource {
+    fn from(handle: Handle<Texture>) -> Self {
+        TextureSource::Handle(handle)
+    }
+}
+
+/// Builds a `Material` starting from the `MaterialDefaults`.
+/// Slots that are not set keep their default texture.
+///
+/// Example:
+/// ```rs
+/// let material = MaterialBuilder::new(&material_defaults)
+///     .with_albedo("textures/crate.png")
+///     .with_normal("textures/crate_normal.jpg")
+///     .with_metallic([0.0, 0.0, 0.0, 1.0])
+///     .with_emission(glow_handle)
+///     .build(&asset_loader, &loader, &mut texture_storage, &mut texture_ali);
+/// ```
+pub struct MaterialBuilder {
+    material: Material,
+    sources: Vec<(TextureSlot, TextureSource)>,
+}
+
+impl MaterialBuilder {
+    pub fn new(defaults: &MaterialDefaults) -> Self {
+        MaterialBuilder {
+            material: defaults.0.clone(),
+            sources: vec![],
+        }
+    }
+
+    /// Sets the texture of a slot from a color, a file path or a handle.
+    pub fn with_texture<S: Into<TextureSource>>(mut self, slot: TextureSlot, source: S) -> Self {
+        self.sources.retain(|s| s.0 != slot);
+        self.sources.push((slot, source.into()));
+        self
+    }
+
+    pub fn with_albedo<S: Into<TextureSource>>(self, source: S) -> Self {
+        self.with_texture(TextureSlot::Albedo, source)
+    }
+
+    pub fn with_emission<S: Into<TextureSource>>(self, source: S) -> Self {
+        self.with_texture(TextureSlot::Emission, source)
+    }
+
+    pub fn with_normal<S: Into<TextureSource>>(self, source: S) -> Self {
+        self.with_texture(TextureSlot::Normal, source)
+    }
+
+    pub fn with_metallic<S: Into<TextureSource>>(self, source: S) -> Self {
+        self.with_texture(TextureSlot::Metallic, source)
+    }
+
+    pub fn with_roughness<S: Into<TextureSource>>(self, source: S) -> Self {
+        self.with_texture(TextureSlot::Roughness, source)
+    }
+
+    pub fn with_ambient_occlusion<S: Into<TextureSource>>(self, source: S) -> Self {
+        self.with_texture(TextureSlot::AmbientOcclusion, source)
+    }
+
+    pub fn with_caveat<S: Into<TextureSource>>(self, source: S) -> Self {
+        self.with_texture(TextureSlot::Caveat, source)
+    }
+
+    /// Sets the texture offset of a single slot.
+    pub fn with_offset(mut self, slot: TextureSlot, offset: TextureOffset) -> Self {
+        *slot.offset_mut(&mut self.material) = offset;
+        self
+    }
+
+    /// Sets the same texture offset on every slot.
+    /// Use this to select a region of a texture atlas shared by all the slots.
+    pub fn with_atlas_region(mut self, offset: TextureOffset) -> Self {
+        for slot in TextureSlot::all().iter() {
+            *slot.offset_mut(&mut self.material) = offset.clone();
+        }
+        self
+    }
+
+    /// Loads the textures and creates the material.
+    /// Files that can't be found or have an unknown extension are logged and
+    /// the slot keeps its default texture.
+    pub fn build(
+        self,
+        asset_loader: &AssetLoader,
+        loader: &Loader,
+        storage: &mut AssetStorage<Texture>,
+        ali: &mut AssetLoaderInternal<Texture>,
+    ) -> Material {
+        let mut material = self.material;
+        for (slot, source) in self.sources {
+            let handle = match source {
+                TextureSource::Color(color) => {
+                    Some(loader.load_from_data(color.into(), (), &storage))
+                }
+                TextureSource::Handle(handle) => Some(handle),
+                TextureSource::File(path) => {
+                    load_texture_file(&path, asset_loader, loader, storage, ali)
+                }
+            };
+            if let Some(handle) = handle {
+                *slot.texture_mut(&mut material) = handle;
+            }
+        }
+        material
+    }
+}
+
+/// The texture file formats `load_texture_file` supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextureFileFormat {
+    Png,
+    Jpg,
+}
+
+/// Picks the texture format from the extension of the path, ignoring its case.
+fn texture_file_format(path: &str) -> Option<TextureFileFormat> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_ref().map(|e| e.as_str()) {
+        Some("png") => Some(TextureFileFormat::Png),
+        Some("jpg") | Some("jpeg") => Some(TextureFileFormat::Jpg),
+        _ => None,
+    }
+}
+
+/// Loads a png or jpg texture through the asset packs, picking the format from the extension.
+pub fn load_texture_file(
+    path: &str,
+    asset_loader: &AssetLoader,
+    loader: &Loader,
+    storage: &mut AssetStorage<Texture>,
+    ali: &mut AssetLoaderInternal<Texture>,
+) -> Option<Handle<Texture>> {
+    let handle = match texture_file_format(path) {
+        Some(TextureFileFormat::Png) => asset_loader.load(
+            path,
+            PngFormat,
+            TextureMetadata::default(),
+            ali,
+            storage,
+            loader,
+        ),
+        Some(TextureFileFormat::Jpg) => asset_loader.load(
+            path,
+            JpgFormat,
+            TextureMetadata::default(),
+            ali,
+            storage,
+            loader,
+        ),
+        None => {
+            error!("Unsupported texture format for file: {}", path);
+            return None;
+        }
+    };
+    if handle.is_none() {
+        error!("Failed to find texture file: {}", path);
+    }
+    handle
+}
+
+impl Heightmap {
+    /// Loads a grayscale image resolved through the asset packs.
+    /// Colored images are converted to grayscale first.
+    pub fn from_image(path: &str, asset_loader: &AssetLoader) -> Option<Self> {
+        let resolved = asset_loader.resolve_path(path)?;
+        match image::open(&resolved) {
+            Ok(img) => {
+                let luma = img.to_luma();
+                let (width, depth) = luma.dimensions();
+                let heights = luma
+                    .into_raw()
+                    .into_iter()
+                    .map(|v| v as f32 / 255.0)
+                    .collect();
+                Heightmap::new(width as usize, depth as usize, heights)
+            }
+            Err(e) => {
+                error!("Failed to load heightmap image {}: {:?}", resolved, e);
+                None
+            }
+        }
+    }
+}
+
+impl ColorMaterialCache {
+    /// Loads a palette from a ron file resolved through the asset packs and registers it under `name`.
+    pub fn load_palette(&mut self, name: &str, path: &str, asset_loader: &AssetLoader) -> bool {
+        match asset_loader.load_ron::<ColorPalette>(path) {
+            Some(palette) => {
+                self.palettes.insert(name.to_string(), palette);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl TextureAtlas {
+    /// Loads an atlas description from a ron file resolved through the asset packs.
+    pub fn load(path: &str, asset_loader: &AssetLoader) -> Option<Self> {
+        asset_loader.load_ron::<TextureAtlas>(path)
+    }
+
+    /// Loads the atlas texture.
+    pub fn load_texture(
+        &self,
+        asset_loader: &AssetLoader,
+        loader: &Loader,
+        storage: &mut AssetStorage<Texture>,
+        ali: &mut AssetLoaderInternal<Texture>,
+    ) -> Option<Handle<Texture>> {
+        load_texture_file(&self.texture, asset_loader, loader, storage, ali)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{texture_file_format, TextureFileFormat};
+    use amethyst::core::rayon::ThreadPoolBuilder;
+    use amethyst::renderer::{Material, MaterialDefaults, Texture, TextureOffset};
+    use std::sync::Arc;
+    use *;
+
+    fn load_asset_loader() -> AssetLoader {
+        AssetLoader::new(
+            &format!("{}/test/assets", env!("CARGO_MANIFEST_DIR")),
+            "main",
+        )
+    }
+
+    fn texture_loader() -> (Loader, AssetStorage<Texture>) {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        (
+            Loader::new(env!("CARGO_MANIFEST_DIR"), pool),
+            AssetStorage::new(),
+        )
+    }
+
+    fn default_material(texture: Handle<Texture>) -> MaterialDefaults {
+        MaterialDefaults(Material {
+            alpha_cutoff: 0.01,
+            albedo: texture.clone(),
+            albedo_offset: TextureOffset::default(),
+            emission: texture.clone(),
+            emission_offset: TextureOffset::default(),
+            normal: texture.clone(),
+            normal_offset: TextureOffset::default(),
+            metallic: texture.clone(),
+            metallic_offset: TextureOffset::default(),
+            roughness: texture.clone(),
+            roughness_offset: TextureOffset::default(),
+            ambient_occlusion: texture.clone(),
+            ambient_occlusion_offset: TextureOffset::default(),
+            caveat: texture,
+            caveat_offset: TextureOffset::default(),
+        })
+    }
+
+    #[test]
+    fn path_sanitisation() {
+        AssetLoader::new(
+            &format!("{}/test/assets/", env!("CARGO_MANIFEST_DIR")),
+            "/base/",
+        );
+    }
+
+    #[test]
+    fn asset_loader_resolve_unique_other() {
+        let asset_loader = load_asset_loader();
+        assert_eq!(asset_loader.resolve_path("config/uniqueother"),Some(format!("{}/test/assets/mod1/config/uniqueother",env!("CARGO_MANIFEST_DIR")).to_string()))
+    }
+
+    #[test]
+    fn asset_loader_resolve_path_override_single() {
+        let asset_loader = load_asset_loader();
+        assert_eq!(asset_loader.resolve_path("config/ov1"),Some(format!("{}/test/assets/mod1/config/ov1",env!("CARGO_MANIFEST_DIR")).to_string()))
+    }
+
+    #[test]
+    fn asset_loader_resolve_path_override_all() {
+        let asset_loader = load_asset_loader();
+        assert_eq!(asset_loader.resolve_path("config/ovall"),Some(format!("{}/test/assets/mod2/config/ovall",env!("CARGO_MANIFEST_DIR")).to_string()))
+    }
+
+    #[test]
+    fn color_palette_loading() {
+        let asset_loader = load_asset_loader();
+        let mut cache = ColorMaterialCache::new();
+        assert!(cache.load_palette("debug", "palettes/debug.ron", &asset_loader));
+        assert_eq!(
+            cache.palette_color("debug", "danger"),
+            Some([1.0, 0.0, 0.0, 1.0])
+        );
+        assert_eq!(cache.palette_color("debug", "unknown"), None);
+        assert!(!cache.load_palette("missing", "palettes/missing.ron", &asset_loader));
+    }
+
+    #[test]
+    fn color_cache_counts_each_lookup_once() {
+        let (loader, storage) = texture_loader();
+        let default = loader.load_from_data([0.0, 0.0, 0.0, 1.0].into(), (), &storage);
+        let defaults = default_material(default);
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let mut cache = ColorMaterialCache::new();
+
+        cache.texture(red, &loader, &storage);
+        assert_eq!(cache.stats().misses, 1);
+        // The texture is reused, but the material is new.
+        cache.material(red, &loader, &storage, &defaults);
+        assert_eq!(cache.stats().hits, 0);
+        assert_eq!(cache.stats().misses, 2);
+        assert_eq!(cache.texture_count(), 1);
+
+        cache.material(red, &loader, &storage, &defaults);
+        cache.texture(red, &loader, &storage);
+        assert_eq!(cache.stats().hits, 2);
+        assert_eq!(cache.stats().misses, 2);
+
+        cache.material([0.0, 1.0, 0.0, 1.0], &loader, &storage, &defaults);
+        assert_eq!(cache.stats().misses, 3);
+        assert_eq!(cache.texture_count(), 2);
+    }
+
+    #[test]
+    fn texture_atlas_regions() {
+        let asset_loader = load_asset_loader();
+        let atlas = TextureAtlas::load("sprites/player.ron", &asset_loader)
+            .expect("Failed to load test atlas");
+        assert_eq!(atlas.uv_rect("idle_1"), Some(([0.25, 0.5], [0.5, 1.0])));
+        assert_eq!(atlas.uv_rect("missing"), None);
+        let quad = atlas.quad("idle_1", 1.0, 1.0).unwrap();
+        assert_eq!(quad.vertices[0].tex_coord, [0.25, 0.5]);
+        assert_eq!(quad.vertices[2].tex_coord, [0.5, 1.0]);
+
+        let mut animation = FrameAnimation::from_atlas(&atlas, "idle").unwrap();
+        assert_eq!(animation.frames.len(), 2);
+        assert!(!animation.advance(0.1));
+        assert!(animation.advance(0.2));
+        assert_eq!(animation.current, 1);
+        assert!(animation.advance(0.25));
+        assert_eq!(animation.current, 0);
+    }
+
+    #[test]
+    fn material_builder_fills_slots() {
+        let (loader, mut storage) = texture_loader();
+        let default = loader.load_from_data([0.0, 0.0, 0.0, 1.0].into(), (), &storage);
+        let defaults = default_material(default.clone());
+        let handles = TextureSlot::all()
+            .iter()
+            .map(|_| loader.load_from_data([1.0, 1.0, 1.0, 1.0].into(), (), &storage))
+            .collect::<Vec<_>>();
+        let offset = TextureOffset {
+            u: (0.5, 1.0),
+            v: (0.0, 0.5),
+        };
+
+        let mut builder = MaterialBuilder::new(&defaults).with_albedo(default.clone());
+        for (slot, handle) in TextureSlot::all().iter().zip(handles.iter()) {
+            builder = builder.with_texture(*slot, handle.clone());
+        }
+        let material = builder
+            .with_offset(TextureSlot::Normal, offset.clone())
+            .build(
+                &load_asset_loader(),
+                &loader,
+                &mut storage,
+                &mut AssetLoaderInternal::new(),
+            );
+
+        assert!(material.albedo == handles[0]);
+        assert!(material.emission == handles[1]);
+        assert!(material.normal == handles[2]);
+        assert!(material.metallic == handles[3]);
+        assert!(material.roughness == handles[4]);
+        assert!(material.ambient_occlusion == handles[5]);
+        assert!(material.caveat == handles[6]);
+        assert_eq!(material.normal_offset.u, offset.u);
+        assert_eq!(material.normal_offset.v, offset.v);
+        assert_eq!(material.albedo_offset.u, defaults.0.albedo_offset.u);
+
+        // Files that can't be loaded keep the default texture.
+        let material = MaterialBuilder::new(&defaults)
+            .with_albedo("textures/white.bmp")
+            .with_normal("textures/missing.png")
+            .build(
+                &load_asset_loader(),
+                &loader,
+                &mut storage,
+                &mut AssetLoaderInternal::new(),
+            );
+        assert!(material.albedo == default);
+        assert!(material.normal == default);
+    }
+
+    #[test]
+    fn texture_files_by_extension() {
+        assert_eq!(
+            texture_file_format("textures/white.png"),
+            Some(TextureFileFormat::Png)
+        );
+        assert_eq!(
+            texture_file_format("textures/WHITE.PNG"),
+            Some(TextureFileFormat::Png)
+        );
+        assert_eq!(
+            texture_file_format("textures/photo.jpg"),
+            Some(TextureFileFormat::Jpg)
+        );
+        assert_eq!(
+            texture_file_format("textures/photo.jpeg"),
+            Some(TextureFileFormat::Jpg)
+        );
+        assert_eq!(texture_file_format("textures/white.bmp"), None);
+        assert_eq!(texture_file_format("textures/white"), None);
+
+        let asset_loader = load_asset_loader();
+        let (loader, mut storage) = texture_loader();
+        let mut ali = AssetLoaderInternal::new();
+        let mut load =
+            |path| load_texture_file(path, &asset_loader, &loader, &mut storage, &mut ali);
+        assert!(load("textures/white.png").is_some());
+        // The bmp exists, but its format isn't supported.
+        assert!(load("textures/white.bmp").is_none());
+        assert!(load("textures/missing.png").is_none());
+    }
+}
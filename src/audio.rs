@@ -0,0 +1,1030 @@
+use amethyst::assets::*;
+use amethyst::audio::output::Output;
+use amethyst::audio::{
+    AudioListener, AudioSink, FlacFormat, Mp3Format, OggFormat, Source, SourceHandle, WavFormat,
+};
+use amethyst::core::cgmath::{InnerSpace, Vector3};
+use amethyst::core::timing::Time;
+use amethyst::core::GlobalTransform;
+use amethyst::ecs::*;
+use amethyst::prelude::*;
+use amethyst::shrev::EventChannel;
+use dirty::Dirty;
+use rand::{thread_rng, Rng};
+use rodio;
+use rodio::{Decoder, Device, Sink, Source as RodioSource};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::iter::Cycle;
+use std::path::Path;
+use std::vec::IntoIter;
+
+use {AssetLoader, AssetLoaderInternal, GameSettings, SoundEvent};
+
+/// Music played in a fixed order by the `AudioBundle`, which `GameDataPreset` adds by default.
+/// See `Playlist` for shuffling, skipping and switching tracks.
+pub struct Music {
+    pub music: Cycle<IntoIter<SourceHandle>>,
+}
+
+/// Loads an ogg, wav, flac or mp3 file through the asset packs, picking the format from the extension.
+pub fn load_audio_file(
+    path: &str,
+    asset_loader: &AssetLoader,
+    loader: &Loader,
+    storage: &mut AssetStorage<Source>,
+    ali: &mut AssetLoaderInternal<Source>,
+) -> Option<SourceHandle> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    let handle = match extension.as_ref().map(|e| e.as_str()) {
+        Some("ogg") => asset_loader.load(path, OggFormat, (), ali, storage, loader),
+        Some("wav") => asset_loader.load(path, WavFormat, (), ali, storage, loader),
+        Some("flac") => asset_loader.load(path, FlacFormat, (), ali, storage, loader),
+        Some("mp3") => asset_loader.load(path, Mp3Format, (), ali, storage, loader),
+        _ => {
+            error!("Unsupported audio format for file: {}", path);
+            return None;
+        }
+    };
+    if handle.is_none() {
+        error!("Failed to find audio file: {}", path);
+    }
+    handle
+}
+
+/// How the playlist goes from one track to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Transition {
+    /// Starts the next track right away.
+    Cut,
+    /// Waits this many seconds of silence between tracks.
+    Gap(f32),
+    /// Fades the next track in over this many seconds.
+    /// When skipping or switching playlists, the current track is faded out first.
+    Fade(f32),
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Transition::Cut
+    }
+}
+
+/// The order in which the tracks of a playlist are played.
+/// When shuffling, every track is played once before any is repeated,
+/// and a track is never played twice in a row.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackOrder {
+    order: Vec<usize>,
+    /// Position of the current track in `order`. None before the first track.
+    position: Option<usize>,
+    shuffle: bool,
+}
+
+impl TrackOrder {
+    pub fn new(len: usize, shuffle: bool) -> Self {
+        let mut order = TrackOrder {
+            order: (0..len).collect(),
+            position: None,
+            shuffle,
+        };
+        if shuffle {
+            order.reshuffle(None);
+        }
+        order
+    }
+
+    fn reshuffle(&mut self, last: Option<usize>) {
+        thread_rng().shuffle(&mut self.order);
+        if self.order.len() > 1 && self.order.first() == last.as_ref() {
+            let end = self.order.len() - 1;
+            self.order.swap(0, end);
+        }
+    }
+
+    /// The index of the current track.
+    pub fn current(&self) -> Option<usize> {
+        self.position.and_then(|p| self.order.get(p).cloned())
+    }
+
+    /// Moves to the next track, starting a new cycle after the last one.
+    pub fn next(&mut self) -> Option<usize> {
+        if self.order.is_empty() {
+            return None;
+        }
+        let next = self.position.map(|p| p + 1).unwrap_or(0);
+        if next >= self.order.len() {
+            if self.shuffle {
+                let last = self.current();
+                self.reshuffle(last);
+            }
+            self.position = Some(0);
+        } else {
+            self.position = Some(next);
+        }
+        self.current()
+    }
+
+    /// Moves back to the previous track, wrapping to the end of the cycle.
+    pub fn previous(&mut self) -> Option<usize> {
+        if self.order.is_empty() {
+            return None;
+        }
+        self.position = Some(match self.position {
+            Some(p) if p > 0 => p - 1,
+            _ => self.order.len() - 1,
+        });
+        self.current()
+    }
+}
+
+/// A named playlist, as described in a ron file.
+///
+/// Example file:
+/// ```ron
+/// (
+///     playlists: {
+///         "menu": ["music/menu.ogg"],
+///         "game": ["music/level1.ogg", "music/level2.ogg", "music/level3.ogg"],
+///     },
+///     shuffle: true,
+///     volume: 0.8,
+///     transition: Fade(2.0),
+/// )
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlaylistConfig {
+    /// Track paths by playlist name. Paths are resolved through the asset packs.
+    pub playlists: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub shuffle: bool,
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    #[serde(default)]
+    pub transition: Transition,
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Request {
+    Next,
+    Previous,
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PlaybackState {
+    Stopped,
+    /// Waiting before starting the next track.
+    Waiting(f32),
+    /// Playing. The float is the time since the track started, used to fade in.
+    Playing(f32),
+    /// Fading out before handling the request.
+    FadingOut(f32, Request),
+}
+
+/// Resource controlling the music, played by the `PlaylistSystem`.
+///
+/// Holds named playlists, for example one per game state, and plays one at a time.
+/// Switch between them with `play`, or `switch_playlist` from a `State`.
+///
+/// The `GameDataPreset` plays the `Music` resource unless built `with_playlist(true)`.
+pub struct Playlist {
+    playlists: HashMap<String, Vec<SourceHandle>>,
+    current: Option<String>,
+    order: TrackOrder,
+    shuffle: bool,
+    volume: f32,
+    paused: bool,
+    transition: Transition,
+    state: PlaybackState,
+    /// Set when the sink has to be emptied before playing the next track.
+    restart: bool,
+}
+
+impl Default for Playlist {
+    fn default() -> Self {
+        Playlist {
+            playlists: HashMap::new(),
+            current: None,
+            order: TrackOrder::default(),
+            shuffle: false,
+            volume: 1.0,
+            paused: false,
+            transition: Transition::Cut,
+            state: PlaybackState::Stopped,
+            restart: false,
+        }
+    }
+}
+
+impl Playlist {
+    pub fn new() -> Self {
+        Playlist::default()
+    }
+
+    /// Creates the playlists of a config, loading the tracks through the asset packs.
+    /// Tracks that fail to load are logged and skipped.
+    pub fn from_config(
+        config: &PlaylistConfig,
+        asset_loader: &AssetLoader,
+        loader: &Loader,
+        storage: &mut AssetStorage<Source>,
+        ali: &mut AssetLoaderInternal<Source>,
+    ) -> Self {
+        let mut playlist = Playlist::new();
+        playlist.shuffle = config.shuffle;
+        playlist.set_volume(config.volume);
+        playlist.transition = config.transition;
+        for (name, paths) in &config.playlists {
+            let tracks = paths
+                .iter()
+                .filter_map(|p| load_audio_file(p, asset_loader, loader, storage, ali))
+                .collect();
+            playlist.add_playlist(name, tracks);
+        }
+        playlist
+    }
+
+    /// Loads a `PlaylistConfig` from a ron file resolved through the asset packs.
+    pub fn load(
+        path: &str,
+        asset_loader: &AssetLoader,
+        loader: &Loader,
+        storage: &mut AssetStorage<Source>,
+        ali: &mut AssetLoaderInternal<Source>,
+    ) -> Option<Self> {
+        asset_loader
+            .load_ron::<PlaylistConfig>(path)
+            .map(|config| Playlist::from_config(&config, asset_loader, loader, storage, ali))
+    }
+
+    pub fn add_playlist(&mut self, name: &str, tracks: Vec<SourceHandle>) {
+        self.playlists.insert(name.to_string(), tracks);
+        if self.current.as_ref().map(|c| c == name).unwrap_or(false) {
+            self.order = TrackOrder::new(self.playlists[name].len(), self.shuffle);
+        }
+    }
+
+    /// Loads the tracks through the asset packs and adds them as a playlist.
+    /// Returns the number of tracks loaded.
+    pub fn load_playlist(
+        &mut self,
+        name: &str,
+        paths: &[&str],
+        asset_loader: &AssetLoader,
+        loader: &Loader,
+        storage: &mut AssetStorage<Source>,
+        ali: &mut AssetLoaderInternal<Source>,
+    ) -> usize {
+        let tracks = paths
+            .iter()
+            .filter_map(|p| load_audio_file(p, asset_loader, loader, storage, ali))
+            .collect::<Vec<_>>();
+        let count = tracks.len();
+        self.add_playlist(name, tracks);
+        count
+    }
+
+    /// Starts playing the named playlist from a new track.
+    /// Does nothing if it is already the current playlist.
+    pub fn play(&mut self, name: &str) {
+        if self.current.as_ref().map(|c| c == name).unwrap_or(false) {
+            return;
+        }
+        let len = match self.playlists.get(name) {
+            Some(tracks) => tracks.len(),
+            None => {
+                warn!("Unknown playlist: {}", name);
+                return;
+            }
+        };
+        self.current = Some(name.to_string());
+        self.order = TrackOrder::new(len, self.shuffle);
+        self.request(Request::Next);
+    }
+
+    /// The name of the playlist being played.
+    pub fn current_playlist(&self) -> Option<&str> {
+        self.current.as_ref().map(|c| c.as_str())
+    }
+
+    /// The track being played.
+    pub fn current_track(&self) -> Option<&SourceHandle> {
+        let tracks = self.playlists.get(self.current.as_ref()?)?;
+        self.order.current().and_then(|i| tracks.get(i))
+    }
+
+    pub fn skip(&mut self) {
+        self.request(Request::Next);
+    }
+
+    pub fn previous(&mut self) {
+        self.request(Request::Previous);
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.request(Request::Stop);
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Shuffle the tracks. Applies from the next playlist started.
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Volume between 0 and 1.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.max(0.0).min(1.0);
+    }
+
+    pub fn set_transition(&mut self, transition: Transition) {
+        self.transition = transition;
+    }
+
+    fn request(&mut self, request: Request) {
+        self.state = match (self.state, self.transition) {
+            (PlaybackState::Playing(_), Transition::Fade(duration)) if duration > 0.0 => {
+                PlaybackState::FadingOut(duration, request)
+            }
+            _ => {
+                self.restart = true;
+                self.handle(request)
+            }
+        };
+    }
+
+    fn handle(&mut self, request: Request) -> PlaybackState {
+        let track = match request {
+            Request::Next => self.order.next(),
+            Request::Previous => self.order.previous(),
+            Request::Stop => None,
+        };
+        if track.is_some() {
+            PlaybackState::Waiting(0.0)
+        } else {
+            PlaybackState::Stopped
+        }
+    }
+
+    /// The time to wait once a track is over.
+    fn gap(&self) -> f32 {
+        match self.transition {
+            Transition::Gap(gap) => gap,
+            _ => 0.0,
+        }
+    }
+
+    /// Moves the playback forward by `delta` seconds.
+    /// `track_loaded` tells if the current track can be played and `track_over` if the
+    /// previous one finished. Returns true when the current track has to be started.
+    fn update(&mut self, delta: f32, track_loaded: bool, track_over: bool) -> bool {
+        let mut start = false;
+        let state = self.state;
+        self.state = match state {
+            PlaybackState::Stopped => PlaybackState::Stopped,
+            PlaybackState::Waiting(waited) => {
+                if waited < self.gap() {
+                    PlaybackState::Waiting(waited + delta)
+                } else if track_loaded {
+                    start = true;
+                    PlaybackState::Playing(0.0)
+                } else {
+                    // Still loading.
+                    PlaybackState::Waiting(waited)
+                }
+            }
+            PlaybackState::Playing(t) => {
+                if track_over {
+                    self.handle(Request::Next)
+                } else {
+                    PlaybackState::Playing(t + delta)
+                }
+            }
+            PlaybackState::FadingOut(remaining, request) => {
+                if remaining - delta <= 0.0 {
+                    self.restart = true;
+                    self.handle(request)
+                } else {
+                    PlaybackState::FadingOut(remaining - delta, request)
+                }
+            }
+        };
+        start
+    }
+
+    /// The volume multiplier of the current fade.
+    fn fade(&self) -> f32 {
+        match (self.state, self.transition) {
+            (PlaybackState::Playing(t), Transition::Fade(duration)) if duration > 0.0 => {
+                (t / duration).min(1.0)
+            }
+            (PlaybackState::FadingOut(remaining, _), Transition::Fade(duration))
+                if duration > 0.0 =>
+            {
+                (remaining / duration).max(0.0)
+            }
+            _ => 1.0,
+        }
+    }
+}
+
+/// Changes the playlist being played, if a `Playlist` is present in the world.
+/// Call it from the `on_start` or `on_resume` of your states to get music per state.
+pub fn switch_playlist(name: &str, world: &mut World) {
+    if let Some(mut playlist) = world.res.try_fetch_mut::<Playlist>() {
+        playlist.play(name);
+    }
+}
+
+/// Plays the `Playlist` resource on the `AudioSink`.
+/// Use it instead of the `DjSystem` added by `AudioBundle`, along with a `Processor<Source>`.
+/// The `Music` resource isn't played then.
+pub struct PlaylistSystem;
+
+impl<'a> System<'a> for PlaylistSystem {
+    type SystemData = (
+        Read<'a, Time>,
+        Write<'a, Playlist>,
+        Read<'a, AssetStorage<Source>>,
+        Option<Write<'a, AudioSink>>,
+        Option<Read<'a, Output>>,
+    );
+
+    fn run(&mut self, (time, mut playlist, storage, sink, output): Self::SystemData) {
+        let mut sink = match sink {
+            Some(s) => s,
+            // No audio device.
+            None => return,
+        };
+
+        if playlist.restart {
+            playlist.restart = false;
+            // Dropping the sink stops the track it was playing.
+            if let Some(ref output) = output {
+                *sink = AudioSink::new(output);
+            }
+        }
+
+        if playlist.paused {
+            if !sink.is_paused() {
+                sink.pause();
+            }
+            return;
+        } else if sink.is_paused() {
+            sink.play();
+        }
+
+        let loaded = playlist
+            .current_track()
+            .map(|t| storage.get(t).is_some())
+            .unwrap_or(false);
+        if playlist.update(time.delta_seconds(), loaded, sink.empty()) {
+            if let Some(source) = playlist.current_track().and_then(|t| storage.get(t)) {
+                if let Err(e) = sink.append(source) {
+                    error!("Failed to play music track: {:?}", e);
+                }
+            }
+        }
+        sink.set_volume(playlist.volume * playlist.fade());
+    }
+}
+
+/// How the volume of positional sounds decreases with the distance to the `AudioListener`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Falloff {
+    /// Distance under which the sound plays at full volume.
+    pub reference_distance: f32,
+    /// Distance after which the sound is not played at all.
+    pub max_distance: f32,
+    /// How fast the volume drops after the reference distance.
+    pub rolloff: f32,
+}
+
+impl Default for Falloff {
+    fn default() -> Self {
+        Falloff {
+            reference_distance: 1.0,
+            max_distance: 50.0,
+            rolloff: 1.0,
+        }
+    }
+}
+
+impl Falloff {
+    /// The volume multiplier of a sound at this distance from the listener.
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        if distance >= self.max_distance {
+            0.0
+        } else if distance <= self.reference_distance {
+            1.0
+        } else {
+            self.reference_distance
+                / (self.reference_distance + self.rolloff * (distance - self.reference_distance))
+        }
+    }
+}
+
+/// A sound effect, as described in a ron file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundEffectConfig {
+    /// Path of the sound, resolved through the asset packs.
+    pub path: String,
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    /// The volume is randomly multiplied by a value in [1 - variation, 1 + variation].
+    #[serde(default)]
+    pub volume_variation: f32,
+    /// The pitch is randomly multiplied by a value in [1 - variation, 1 + variation].
+    #[serde(default)]
+    pub pitch_variation: f32,
+    /// How many instances of this sound can play at the same time.
+    /// Additional requests are ignored until one of them is over.
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+}
+
+fn default_max_concurrent() -> usize {
+    4
+}
+
+/// The sound effects of a game, as described in a ron file.
+///
+/// Example file:
+/// ```ron
+/// (
+///     sounds: {
+///         "jump": (path: "sounds/jump.ogg", pitch_variation: 0.1),
+///         "step": (path: "sounds/step.wav", volume: 0.5, volume_variation: 0.2, max_concurrent: 2),
+///     },
+///     falloff: (reference_distance: 2.0, max_distance: 40.0, rolloff: 1.0),
+/// )
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SoundEffectsConfig {
+    pub sounds: HashMap<String, SoundEffectConfig>,
+    #[serde(default)]
+    pub falloff: Falloff,
+}
+
+/// The smallest factor `vary` multiplies by, so a variation of 1 never gives a speed of 0.
+const MIN_VARIATION_FACTOR: f32 = 0.05;
+
+/// Randomly multiplies `value` by a factor in [1 - variation, 1 + variation].
+fn vary(value: f32, variation: f32) -> f32 {
+    let variation = variation.abs().min(1.0);
+    if variation == 0.0 {
+        return value;
+    }
+    value
+        * thread_rng()
+            .gen_range(1.0 - variation, 1.0 + variation)
+            .max(MIN_VARIATION_FACTOR)
+}
+
+struct SoundEffect {
+    config: SoundEffectConfig,
+    source: SourceHandle,
+    /// The instances currently playing.
+    playing: Vec<Sink>,
+}
+
+/// Resource holding the named sound effects of the game.
+/// Play them directly or through `SoundEvent`s handled by the `SoundEffectsSystem`.
+///
+/// The sounds are played on the device of amethyst's `Output` resource, see `set_output`.
+pub struct SoundEffects {
+    sounds: HashMap<String, SoundEffect>,
+    pub falloff: Falloff,
+    volume: f32,
+    device: Option<Device>,
+}
+
+impl Default for SoundEffects {
+    fn default() -> Self {
+        SoundEffects {
+            sounds: HashMap::new(),
+            falloff: Falloff::default(),
+            volume: 1.0,
+            device: None,
+        }
+    }
+}
+
+impl SoundEffects {
+    pub fn new() -> Self {
+        SoundEffects::default()
+    }
+
+    /// Creates the sound effects of a config, loading them through the asset packs.
+    /// Sounds that fail to load are logged and skipped.
+    pub fn from_config(
+        config: SoundEffectsConfig,
+        asset_loader: &AssetLoader,
+        loader: &Loader,
+        storage: &mut AssetStorage<Source>,
+        ali: &mut AssetLoaderInternal<Source>,
+    ) -> Self {
+        let mut effects = SoundEffects::new();
+        effects.falloff = config.falloff;
+        for (name, sound) in config.sounds {
+            if let Some(source) = load_audio_file(&sound.path, asset_loader, loader, storage, ali) {
+                effects.add(&name, sound, source);
+            }
+        }
+        effects
+    }
+
+    /// Loads a `SoundEffectsConfig` from a ron file resolved through the asset packs.
+    pub fn load(
+        path: &str,
+        asset_loader: &AssetLoader,
+        loader: &Loader,
+        storage: &mut AssetStorage<Source>,
+        ali: &mut AssetLoaderInternal<Source>,
+    ) -> Option<Self> {
+        asset_loader
+            .load_ron::<SoundEffectsConfig>(path)
+            .map(|config| SoundEffects::from_config(config, asset_loader, loader, storage, ali))
+    }
+
+    pub fn add(&mut self, name: &str, config: SoundEffectConfig, source: SourceHandle) {
+        self.sounds.insert(
+            name.to_string(),
+            SoundEffect {
+                config,
+                source,
+                playing: vec![],
+            },
+        );
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.sounds.contains_key(name)
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Plays the sounds on the same device as the `Output`.
+    /// The `SoundEffectsSystem` does it when the `Output` resource is present.
+    /// Returns false if the device can't be found, in which case no sound is played.
+    pub fn set_output(&mut self, output: &Output) -> bool {
+        let name = output.name();
+        self.device = rodio::output_devices().find(|d| d.name() == name);
+        if self.device.is_none() {
+            error!("Failed to find the audio output device: {}", name);
+        }
+        self.device.is_some()
+    }
+
+    /// Global volume of the sound effects, between 0 and 1.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.max(0.0).min(1.0);
+    }
+
+    /// The number of instances of a sound currently playing.
+    pub fn playing(&self, name: &str) -> usize {
+        self.sounds
+            .get(name)
+            .map(|s| s.playing.iter().filter(|sink| !sink.empty()).count())
+            .unwrap_or(0)
+    }
+
+    /// Plays a sound without falloff. Returns false if it wasn't played.
+    pub fn play(&mut self, name: &str, storage: &AssetStorage<Source>) -> bool {
+        self.play_with_volume(name, 1.0, storage)
+    }
+
+    /// Plays a sound at `position`, attenuated by its distance to `listener`.
+    pub fn play_at(
+        &mut self,
+        name: &str,
+        position: Vector3<f32>,
+        listener: Vector3<f32>,
+        storage: &AssetStorage<Source>,
+    ) -> bool {
+        let attenuation = self.falloff.attenuation((position - listener).magnitude());
+        if attenuation <= 0.0 {
+            return false;
+        }
+        self.play_with_volume(name, attenuation, storage)
+    }
+
+    fn play_with_volume(
+        &mut self,
+        name: &str,
+        volume: f32,
+        storage: &AssetStorage<Source>,
+    ) -> bool {
+        if self.device.is_none() {
+            return false;
+        }
+        let global_volume = self.volume;
+        let sound = match self.sounds.get_mut(name) {
+            Some(s) => s,
+            None => {
+                warn!("Unknown sound effect: {}", name);
+                return false;
+            }
+        };
+        sound.playing.retain(|sink| !sink.empty());
+        if sound.playing.len() >= sound.config.max_concurrent {
+            return false;
+        }
+        let source = match storage.get(&sound.source) {
+            Some(s) => s,
+            // Still loading.
+            None => return false,
+        };
+        let decoder = match Decoder::new(Cursor::new(source.clone())) {
+            Ok(d) => d,
+            Err(e) => {
+                error!("Failed to decode sound effect {}: {:?}", name, e);
+                return false;
+            }
+        };
+        let sink = Sink::new(self.device.as_ref().unwrap());
+        sink.set_volume(
+            global_volume * vary(sound.config.volume, sound.config.volume_variation) * volume,
+        );
+        sink.append(decoder.speed(vary(1.0, sound.config.pitch_variation)));
+        sound.playing.push(sink);
+        true
+    }
+}
+
+/// Plays the `SoundEvent`s written to the `EventChannel<SoundEvent>`.
+/// Positional sounds are heard from the first entity with an `AudioListener` and a `GlobalTransform`.
+/// Nothing is played without the `Output` resource.
+#[derive(Default)]
+pub struct SoundEffectsSystem {
+    event_reader: Option<ReaderId<SoundEvent>>,
+    /// Was the `Output` given to the `SoundEffects`?
+    output_set: bool,
+}
+
+impl<'a> System<'a> for SoundEffectsSystem {
+    type SystemData = (
+        Read<'a, EventChannel<SoundEvent>>,
+        Write<'a, SoundEffects>,
+        Read<'a, AssetStorage<Source>>,
+        ReadStorage<'a, AudioListener>,
+        ReadStorage<'a, GlobalTransform>,
+        Option<Read<'a, Output>>,
+    );
+
+    fn run(
+        &mut self,
+        (events, mut effects, storage, listeners, transforms, output): Self::SystemData,
+    ) {
+        if !self.output_set {
+            if let Some(ref output) = output {
+                effects.set_output(output);
+                self.output_set = true;
+            }
+        }
+        let listener = (&listeners, &transforms)
+            .join()
+            .next()
+            .map(|(_, t)| Vector3::new(t.0.w.x, t.0.w.y, t.0.w.z));
+        for event in events.read(self.event_reader.as_mut().unwrap()) {
+            match (event.position, listener) {
+                (Some(position), Some(listener)) => {
+                    effects.play_at(&event.name, position, listener, &storage);
+                }
+                _ => {
+                    effects.play(&event.name, &storage);
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.event_reader = Some(
+            res.fetch_mut::<EventChannel<SoundEvent>>()
+                .register_reader(),
+        );
+    }
+}
+
+/// Applies the volumes of the `Dirty<GameSettings>` resource when they change.
+/// The music volume is set on the `Playlist` and on the `AudioSink` used to play the `Music`,
+/// the effects volume on the `SoundEffects`. Both are multiplied by the master volume.
+#[derive(Default)]
+pub struct AudioSettingsSystem {
+    /// The music and effects volumes last applied.
+    applied: Option<(f32, f32)>,
+}
+
+impl<'a> System<'a> for AudioSettingsSystem {
+    type SystemData = (
+        Option<Read<'a, Dirty<GameSettings>>>,
+        Option<Write<'a, Playlist>>,
+        Option<Write<'a, SoundEffects>>,
+        Option<Write<'a, AudioSink>>,
+    );
+
+    fn run(&mut self, (settings, playlist, effects, sink): Self::SystemData) {
+        let settings = match settings {
+            Some(s) => s,
+            None => return,
+        };
+        let volumes = (
+            settings.read().effective_music_volume(),
+            settings.read().effective_effects_volume(),
+        );
+        if self.applied == Some(volumes) {
+            return;
+        }
+        if let Some(mut playlist) = playlist {
+            playlist.set_volume(volumes.0);
+        }
+        if let Some(sink) = sink {
+            sink.set_volume(volumes.0);
+        }
+        if let Some(mut effects) = effects {
+            effects.set_volume(volumes.1);
+        }
+        self.applied = Some(volumes);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{vary, PlaybackState, Request, MIN_VARIATION_FACTOR};
+    use amethyst::audio::{OggFormat, Source};
+    use amethyst::core::rayon::ThreadPoolBuilder;
+    use dirty::Dirty;
+    use ron;
+    use std::sync::Arc;
+    use *;
+
+    /// A "game" playlist whose tracks never finish loading.
+    fn playlist_with_tracks(tracks: usize) -> Playlist {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        let loader = Loader::new(env!("CARGO_MANIFEST_DIR"), pool);
+        let storage = AssetStorage::<Source>::new();
+        let handles = (0..tracks)
+            .map(|i| loader.load(format!("music/{}.ogg", i), OggFormat, (), (), &storage))
+            .collect();
+        let mut playlist = Playlist::new();
+        playlist.add_playlist("game", handles);
+        playlist
+    }
+
+    #[test]
+    fn track_order_in_sequence() {
+        let mut order = TrackOrder::new(3, false);
+        assert_eq!(order.current(), None);
+        assert_eq!(order.next(), Some(0));
+        assert_eq!(order.next(), Some(1));
+        assert_eq!(order.next(), Some(2));
+        assert_eq!(order.next(), Some(0));
+        assert_eq!(order.previous(), Some(2));
+        assert_eq!(TrackOrder::new(0, true).next(), None);
+    }
+
+    #[test]
+    fn playlist_cut_and_gap() {
+        let mut playlist = playlist_with_tracks(2);
+        playlist.play("game");
+        assert_eq!(playlist.state, PlaybackState::Waiting(0.0));
+        // Nothing plays until the track is loaded.
+        assert!(!playlist.update(0.1, false, true));
+        assert!(playlist.update(0.1, true, true));
+        let first = playlist.order.current();
+        assert!(!playlist.update(0.1, true, false));
+        assert_eq!(playlist.state, PlaybackState::Playing(0.1));
+        // The track is over, the next one starts on the next update.
+        assert!(!playlist.update(0.1, true, true));
+        assert!(playlist.update(0.1, true, true));
+        assert_ne!(playlist.order.current(), first);
+
+        playlist.set_transition(Transition::Gap(1.0));
+        assert!(!playlist.update(0.5, true, true));
+        assert!(!playlist.update(0.5, true, true));
+        assert!(!playlist.update(0.5, true, true));
+        assert!(playlist.update(0.5, true, true));
+    }
+
+    #[test]
+    fn playlist_fades() {
+        let mut playlist = playlist_with_tracks(2);
+        playlist.set_transition(Transition::Fade(2.0));
+        playlist.play("game");
+        assert!(playlist.update(0.0, true, true));
+        assert_eq!(playlist.fade(), 0.0);
+        playlist.update(1.0, true, false);
+        assert_eq!(playlist.fade(), 0.5);
+
+        // Cleared by the PlaylistSystem once the sink is emptied.
+        playlist.restart = false;
+        playlist.skip();
+        assert_eq!(playlist.state, PlaybackState::FadingOut(2.0, Request::Next));
+        assert!(!playlist.restart);
+        playlist.update(1.0, true, false);
+        assert_eq!(playlist.fade(), 0.5);
+        assert!(!playlist.update(1.0, true, false));
+        assert!(playlist.restart);
+        assert_eq!(playlist.state, PlaybackState::Waiting(0.0));
+
+        playlist.stop();
+        assert_eq!(playlist.current_playlist(), None);
+        assert_eq!(playlist.state, PlaybackState::Stopped);
+    }
+
+    #[test]
+    fn shuffle_without_repeats() {
+        let mut order = TrackOrder::new(5, true);
+        let mut last = None;
+        for _ in 0..20 {
+            let mut cycle = (0..5).map(|_| order.next().unwrap()).collect::<Vec<_>>();
+            // Never the same track twice in a row, even across cycles.
+            assert_ne!(Some(cycle[0]), last);
+            last = cycle.last().cloned();
+            cycle.sort();
+            assert_eq!(cycle, vec![0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn settings_set_volumes() {
+        let mut world = World::new();
+        let mut settings = GameSettings::default();
+        settings.master_volume = 0.5;
+        settings.music_volume = 0.8;
+        world.add_resource(Dirty::new(settings));
+        world.add_resource(Playlist::default());
+        world.add_resource(SoundEffects::default());
+
+        let mut system = AudioSettingsSystem::default();
+        system.run_now(&world.res);
+        assert_eq!(world.read_resource::<Playlist>().volume(), 0.4);
+        assert_eq!(world.read_resource::<SoundEffects>().volume(), 0.5);
+
+        // Only applied again when the settings change.
+        world.write_resource::<Playlist>().set_volume(1.0);
+        system.run_now(&world.res);
+        assert_eq!(world.read_resource::<Playlist>().volume(), 1.0);
+        world
+            .write_resource::<Dirty<GameSettings>>()
+            .write()
+            .master_volume = 1.0;
+        system.run_now(&world.res);
+        assert_eq!(world.read_resource::<Playlist>().volume(), 0.8);
+    }
+
+    #[test]
+    fn sound_falloff() {
+        let falloff = Falloff {
+            reference_distance: 2.0,
+            max_distance: 10.0,
+            rolloff: 1.0,
+        };
+        assert_eq!(falloff.attenuation(1.0), 1.0);
+        assert_eq!(falloff.attenuation(4.0), 0.5);
+        assert_eq!(falloff.attenuation(10.0), 0.0);
+    }
+
+    #[test]
+    fn sound_effects_config() {
+        let config: SoundEffectsConfig = ron::de::from_str(
+            "(sounds: {\"jump\": (path: \"sounds/jump.ogg\", pitch_variation: 0.1)})",
+        )
+        .unwrap();
+        let jump = &config.sounds["jump"];
+        assert_eq!(jump.volume, 1.0);
+        assert_eq!(jump.max_concurrent, 4);
+        assert_eq!(config.falloff, Falloff::default());
+        for _ in 0..100 {
+            let v = vary(1.0, jump.pitch_variation);
+            assert!(v >= 0.9 && v <= 1.1);
+            assert!(vary(1.0, 1.0) >= MIN_VARIATION_FACTOR);
+        }
+    }
+}
@@ -0,0 +1,557 @@
+use amethyst::core::cgmath::Vector3;
+use amethyst::core::timing::Time;
+use amethyst::core::Transform;
+use amethyst::ecs::{
+    Component, Entities, Entity, Join, LazyUpdate, Read, ReadStorage, System, VecStorage, World,
+    Write, WriteStorage,
+};
+use amethyst::renderer::Hidden;
+use amethyst::shrev::EventChannel;
+use amethyst::ui::UiText;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use {Clock, Timer};
+
+/// Expires the entity once the absolute time of its `ExpireOptions::clock` reaches `time`.
+pub struct DestroyAtTime {
+    pub time: f64,
+}
+
+impl Component for DestroyAtTime {
+    type Storage = VecStorage<Self>;
+}
+
+/// Expires the entity once `seconds` of its `ExpireOptions::clock` have passed.
+pub struct DestroyInTime {
+    timer: Timer,
+}
+
+impl DestroyInTime {
+    pub fn new(seconds: f64) -> Self {
+        DestroyInTime {
+            timer: Timer::once("", seconds),
+        }
+    }
+
+    /// Seconds left before the entity expires.
+    pub fn remaining(&self) -> f64 {
+        self.timer.remaining()
+    }
+
+    /// Stops the countdown until `resume` is called.
+    pub fn pause(&mut self) {
+        self.timer.pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.timer.resume();
+    }
+}
+
+impl Component for DestroyInTime {
+    type Storage = VecStorage<Self>;
+}
+
+/// Written to the `EventChannel<EntityExpired>` when a `DestroyAtTime` or
+/// a `DestroyInTime` expires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityExpired {
+    pub entity: Entity,
+    /// The tag of the `ExpireOptions` of the entity, empty if it has none.
+    pub tag: String,
+}
+
+/// A callback run when an entity expires, for example to spawn an explosion.
+pub type ExpireCallback = Arc<Fn(&mut World, Entity) + Send + Sync>;
+
+/// The callbacks used by `ExpireAction::Callback`, by name.
+#[derive(Default)]
+pub struct ExpireCallbacks {
+    callbacks: HashMap<String, ExpireCallback>,
+}
+
+impl ExpireCallbacks {
+    pub fn add<F>(&mut self, name: &str, callback: F)
+    where
+        F: Fn(&mut World, Entity) + Send + Sync + 'static,
+    {
+        self.callbacks.insert(name.to_string(), Arc::new(callback));
+    }
+
+    pub fn get(&self, name: &str) -> Option<ExpireCallback> {
+        self.callbacks.get(name).cloned()
+    }
+}
+
+/// What happens to an entity when it expires.
+#[derive(Clone)]
+pub enum ExpireAction {
+    Delete,
+    /// Adds the `Hidden` component.
+    Hide,
+    /// Removes a component, see `ExpireAction::remove`.
+    Remove(fn(Entity, &LazyUpdate)),
+    /// Runs the callback registered with this name in the `ExpireCallbacks`.
+    /// It runs before the entity is deleted, so it can still read its components.
+    Callback(String),
+}
+
+impl ExpireAction {
+    /// Removes the component `C` from the expired entity.
+    pub fn remove<C: Component + Send + Sync>() -> Self {
+        ExpireAction::Remove(remove_component::<C>)
+    }
+}
+
+fn remove_component<C: Component + Send + Sync>(entity: Entity, lazy: &LazyUpdate) {
+    lazy.remove::<C>(entity);
+}
+
+/// Changes how the `DestroyAtTime` and `DestroyInTime` of an entity expire.
+/// Without it, entities use the game time and are deleted.
+///
+/// When the entity isn't deleted, its `DestroyAtTime` and `DestroyInTime` are removed.
+#[derive(Clone)]
+pub struct ExpireOptions {
+    /// Sent along with the `EntityExpired` event.
+    pub tag: String,
+    pub actions: Vec<ExpireAction>,
+    pub clock: Clock,
+}
+
+impl ExpireOptions {
+    /// Deletes the entity, using the game time.
+    pub fn new(tag: &str) -> Self {
+        ExpireOptions::with_actions(tag, vec![ExpireAction::Delete])
+    }
+
+    pub fn with_actions(tag: &str, actions: Vec<ExpireAction>) -> Self {
+        ExpireOptions {
+            tag: tag.to_string(),
+            actions,
+            clock: Clock::Game,
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl Component for ExpireOptions {
+    type Storage = VecStorage<Self>;
+}
+
+/// How a `FadeOut` makes the entity disappear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FadeMode {
+    /// Fades the alpha of the `UiText` color, for damage numbers and other floating texts.
+    Alpha,
+    /// Shrinks the `Transform` scale down to zero, for particles and meshes.
+    Scale,
+    AlphaAndScale,
+}
+
+/// Fades the entity out over the last `duration` seconds before its `DestroyAtTime`
+/// or `DestroyInTime` expires.
+/// The alpha or scale goes linearly from the value it had when the fade started to zero.
+///
+/// The alpha of mesh materials isn't faded: a `Material` has no alpha of its own, only
+/// textures that are shared with the other entities using them, so meshes fade out
+/// with `FadeMode::Scale`.
+#[derive(Debug, Clone)]
+pub struct FadeOut {
+    pub duration: f64,
+    pub mode: FadeMode,
+    /// The alpha and scale when the fade started.
+    initial: Option<(f32, Vector3<f32>)>,
+}
+
+impl FadeOut {
+    pub fn new(duration: f64, mode: FadeMode) -> Self {
+        FadeOut {
+            duration,
+            mode,
+            initial: None,
+        }
+    }
+
+    pub fn alpha(duration: f64) -> Self {
+        FadeOut::new(duration, FadeMode::Alpha)
+    }
+
+    pub fn scale(duration: f64) -> Self {
+        FadeOut::new(duration, FadeMode::Scale)
+    }
+
+    fn fades_alpha(&self) -> bool {
+        self.mode != FadeMode::Scale
+    }
+
+    fn fades_scale(&self) -> bool {
+        self.mode != FadeMode::Alpha
+    }
+}
+
+impl Component for FadeOut {
+    type Storage = VecStorage<Self>;
+}
+
+/// Expires the entities with a `DestroyAtTime` or a `DestroyInTime`, and fades out
+/// the ones with a `FadeOut`.
+/// See `ExpireOptions` to do something else than deleting them.
+///
+/// The `DestroyAtTime` and `DestroyInTime` components are removed once they expire,
+/// so an entity only expires once, even if it has both.
+pub struct TimedDestroySystem;
+
+impl<'a> System<'a> for TimedDestroySystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, DestroyAtTime>,
+        WriteStorage<'a, DestroyInTime>,
+        ReadStorage<'a, ExpireOptions>,
+        WriteStorage<'a, FadeOut>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, UiText>,
+        WriteStorage<'a, Hidden>,
+        Read<'a, Time>,
+        Read<'a, LazyUpdate>,
+        Read<'a, ExpireCallbacks>,
+        Write<'a, EventChannel<EntityExpired>>,
+    );
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut dat,
+            mut dit,
+            options,
+            mut fades,
+            mut transforms,
+            mut texts,
+            mut hidden,
+            time,
+            lazy,
+            callbacks,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        let default_options = ExpireOptions::new("");
+        // The time left before each entity expires.
+        let mut remaining = HashMap::new();
+        for (e, d) in (&*entities, &dat).join() {
+            let clock = options.get(e).unwrap_or(&default_options).clock;
+            remaining.insert(e, d.time - clock.absolute_seconds(&time));
+        }
+
+        for (e, mut d) in (&*entities, &mut dit).join() {
+            let clock = options.get(e).unwrap_or(&default_options).clock;
+            d.timer.tick(clock.delta_seconds(&time));
+            let left = remaining.entry(e).or_insert_with(|| d.remaining());
+            *left = left.min(d.remaining());
+        }
+
+        for (e, mut fade) in (&*entities, &mut fades).join() {
+            let left = match remaining.get(&e) {
+                Some(left) if *left < fade.duration => *left,
+                _ => continue,
+            };
+            if fade.initial.is_none() {
+                let alpha = texts.get(e).map(|t| t.color[3]).unwrap_or(1.0);
+                let scale = transforms
+                    .get(e)
+                    .map(|t| t.scale)
+                    .unwrap_or(Vector3::new(1.0, 1.0, 1.0));
+                fade.initial = Some((alpha, scale));
+            }
+            let (alpha, scale) = fade.initial.unwrap();
+            let factor = (left.max(0.0) / fade.duration) as f32;
+            if fade.fades_alpha() {
+                if let Some(text) = texts.get_mut(e) {
+                    text.color[3] = alpha * factor;
+                }
+            }
+            if fade.fades_scale() {
+                if let Some(transform) = transforms.get_mut(e) {
+                    transform.scale = scale * factor;
+                }
+            }
+        }
+
+        let mut expired = remaining
+            .into_iter()
+            .filter(|&(_, left)| left <= 0.0)
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        // Send the events in a stable order.
+        expired.sort_by_key(|e| e.id());
+
+        for e in expired {
+            dat.remove(e);
+            dit.remove(e);
+            let options = options.get(e).unwrap_or(&default_options);
+            events.single_write(EntityExpired {
+                entity: e,
+                tag: options.tag.clone(),
+            });
+
+            let mut delete = false;
+            let mut to_run = vec![];
+            for action in &options.actions {
+                match *action {
+                    ExpireAction::Delete => delete = true,
+                    ExpireAction::Hide => {
+                        if let Err(err) = hidden.insert(e, Hidden) {
+                            error!("Failed to hide expired entity: {:?}", err);
+                        }
+                    }
+                    ExpireAction::Remove(remove) => remove(e, &lazy),
+                    ExpireAction::Callback(ref name) => match callbacks.get(name) {
+                        Some(callback) => to_run.push(callback),
+                        None => warn!("Unknown expire callback: {}", name),
+                    },
+                }
+            }
+
+            if !to_run.is_empty() {
+                lazy.exec_mut(move |world| {
+                    for callback in to_run {
+                        callback(world, e);
+                    }
+                    if delete {
+                        if let Err(err) = world.delete_entity(e) {
+                            error!("Failed to delete expired entity: {:?}", err);
+                        }
+                    }
+                });
+            } else if delete {
+                if let Err(err) = entities.delete(e) {
+                    error!("Failed to delete expired entity: {:?}", err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amethyst::renderer::Hidden;
+    use amethyst::shrev::EventChannel;
+    use *;
+
+    struct Bomb;
+
+    impl Component for Bomb {
+        type Storage = VecStorage<Self>;
+    }
+
+    fn expired(entity: Entity, tag: &str) -> EntityExpired {
+        EntityExpired {
+            entity,
+            tag: tag.to_string(),
+        }
+    }
+
+    #[test]
+    fn expired_entities_send_events() {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        world.register::<Bomb>();
+        let mut system = TimedDestroySystem;
+        System::setup(&mut system, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<EntityExpired>>()
+            .register_reader();
+        world
+            .write_resource::<ExpireCallbacks>()
+            .add("explode", |world, e| {
+                assert!(world.read_storage::<Bomb>().get(e).is_some());
+                world.create_entity().with(Bomb).build();
+            });
+
+        let plain = world.create_entity().with(DestroyInTime::new(1.0)).build();
+        let coin = world
+            .create_entity()
+            .with(DestroyInTime::new(1.0))
+            .with(Bomb)
+            .with(ExpireOptions::with_actions(
+                "coin",
+                vec![ExpireAction::Hide, ExpireAction::remove::<Bomb>()],
+            ))
+            .build();
+        let bomb = world
+            .create_entity()
+            .with(DestroyAtTime { time: 0.5 })
+            .with(Bomb)
+            .with(ExpireOptions::with_actions(
+                "bomb",
+                vec![
+                    ExpireAction::Callback("explode".to_string()),
+                    ExpireAction::Delete,
+                ],
+            ))
+            .build();
+
+        let mut step = |world: &mut World, seconds: f32| {
+            world.write_resource::<Time>().set_delta_seconds(seconds);
+            system.run_now(&world.res);
+            world.maintain();
+        };
+        step(&mut world, 0.6);
+        let events = world
+            .read_resource::<EventChannel<EntityExpired>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec![expired(bomb, "bomb")]);
+        assert!(!world.is_alive(bomb));
+        // The explosion.
+        assert_eq!(world.read_storage::<Bomb>().join().count(), 2);
+
+        step(&mut world, 0.6);
+        step(&mut world, 0.1);
+        let events = world
+            .read_resource::<EventChannel<EntityExpired>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec![expired(plain, ""), expired(coin, "coin")]);
+        assert!(!world.is_alive(plain));
+        assert!(world.is_alive(coin));
+        assert!(world.read_storage::<Hidden>().get(coin).is_some());
+        assert!(world.read_storage::<Bomb>().get(coin).is_none());
+        assert!(world.read_storage::<DestroyInTime>().get(coin).is_none());
+    }
+
+    #[test]
+    fn real_time_ignores_time_scale() {
+        let mut world = World::new();
+        let mut time = Time::default();
+        time.set_time_scale(0.0);
+        world.add_resource(time);
+        let mut system = TimedDestroySystem;
+        System::setup(&mut system, &mut world.res);
+
+        let game = world.create_entity().with(DestroyInTime::new(0.5)).build();
+        let real = world
+            .create_entity()
+            .with(DestroyInTime::new(0.5))
+            .with(ExpireOptions::new("menu").with_clock(Clock::Real))
+            .build();
+        for _ in 0..3 {
+            world.write_resource::<Time>().set_delta_seconds(0.3);
+            system.run_now(&world.res);
+            world.maintain();
+        }
+        assert!(world.is_alive(game));
+        assert!(!world.is_alive(real));
+    }
+
+    #[test]
+    fn paused_countdown_waits() {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        let mut system = TimedDestroySystem;
+        System::setup(&mut system, &mut world.res);
+
+        let mut destroy = DestroyInTime::new(0.5);
+        destroy.pause();
+        let entity = world.create_entity().with(destroy).build();
+        let mut step = |world: &mut World| {
+            world.write_resource::<Time>().set_delta_seconds(0.3);
+            system.run_now(&world.res);
+            world.maintain();
+        };
+        step(&mut world);
+        step(&mut world);
+        assert!(world.is_alive(entity));
+        assert_eq!(
+            world
+                .read_storage::<DestroyInTime>()
+                .get(entity)
+                .unwrap()
+                .remaining(),
+            0.5
+        );
+
+        world
+            .write_storage::<DestroyInTime>()
+            .get_mut(entity)
+            .unwrap()
+            .resume();
+        step(&mut world);
+        step(&mut world);
+        assert!(!world.is_alive(entity));
+    }
+
+    #[test]
+    fn entities_expire_once() {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        let mut system = TimedDestroySystem;
+        System::setup(&mut system, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<EntityExpired>>()
+            .register_reader();
+
+        let both = world
+            .create_entity()
+            .with(DestroyAtTime { time: 0.1 })
+            .with(DestroyInTime::new(0.1))
+            .build();
+        world.write_resource::<Time>().set_delta_seconds(0.2);
+        // Runs twice before the deletion is applied.
+        system.run_now(&world.res);
+        system.run_now(&world.res);
+        world.maintain();
+
+        assert!(!world.is_alive(both));
+        let events = world
+            .read_resource::<EventChannel<EntityExpired>>()
+            .read(&mut reader)
+            .count();
+        assert_eq!(events, 1);
+    }
+
+    #[test]
+    fn fade_out_before_expiring() {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        let mut system = TimedDestroySystem;
+        System::setup(&mut system, &mut world.res);
+
+        let mut transform = Transform::default();
+        transform.scale = Vector3::new(2.0, 2.0, 2.0);
+        let particle = world
+            .create_entity()
+            .with(transform)
+            .with(DestroyInTime::new(2.0))
+            .with(FadeOut::scale(1.0))
+            .build();
+        let scale = |world: &World| {
+            world
+                .read_storage::<Transform>()
+                .get(particle)
+                .unwrap()
+                .scale
+                .x
+        };
+
+        let mut step = |world: &mut World, seconds: f32| {
+            world.write_resource::<Time>().set_delta_seconds(seconds);
+            system.run_now(&world.res);
+            world.maintain();
+        };
+        step(&mut world, 0.5);
+        assert_eq!(scale(&world), 2.0);
+        step(&mut world, 1.0);
+        assert_eq!(scale(&world), 1.0);
+        step(&mut world, 0.25);
+        assert_eq!(scale(&world), 0.5);
+        step(&mut world, 0.25);
+        assert!(!world.is_alive(particle));
+    }
+}
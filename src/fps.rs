@@ -0,0 +1,1095 @@
+use amethyst::controls::CursorHideSystem;
+use amethyst::controls::FlyControlTag;
+use amethyst::controls::HideCursor;
+use amethyst::controls::MouseFocusUpdateSystem;
+use amethyst::controls::WindowFocus;
+use amethyst::core::bundle::{Result as BundleResult, SystemBundle};
+use amethyst::core::cgmath::EuclideanSpace;
+use amethyst::core::cgmath::InnerSpace;
+use amethyst::core::cgmath::One;
+use amethyst::core::cgmath::Vector2;
+use amethyst::core::cgmath::Vector3;
+use amethyst::core::cgmath::{Basis3, Deg, Point3, Quaternion, Rotation3, SquareMatrix};
+use amethyst::core::timing::Time;
+use amethyst::core::*;
+use amethyst::ecs::*;
+use amethyst::input::get_input_axis_simple;
+use amethyst::input::*;
+use amethyst::prelude::*;
+use amethyst::renderer::DeviceEvent;
+use amethyst::renderer::Event;
+use amethyst::shrev::EventChannel;
+use amethyst::Result;
+use amethyst_rhusics::collision::dbvt::query_ray;
+use amethyst_rhusics::collision::primitive::Cuboid;
+use amethyst_rhusics::collision::Ray3;
+use amethyst_rhusics::rhusics_core::physics3d::Velocity3;
+use amethyst_rhusics::rhusics_core::Collider;
+use amethyst_rhusics::rhusics_core::CollisionMode;
+use amethyst_rhusics::rhusics_core::CollisionShape;
+use amethyst_rhusics::rhusics_core::CollisionStrategy;
+use amethyst_rhusics::rhusics_core::ContactEvent;
+use amethyst_rhusics::rhusics_core::ForceAccumulator;
+use amethyst_rhusics::rhusics_core::NextFrame;
+use amethyst_rhusics::rhusics_core::Pose;
+use amethyst_rhusics::rhusics_ecs::collide3d::DynamicBoundingVolumeTree3;
+use amethyst_rhusics::rhusics_ecs::physics3d::BodyPose3;
+use amethyst_rhusics::rhusics_ecs::physics3d::CollisionShape3;
+use amethyst_rhusics::DefaultPhysicsBundle3;
+use dirty::Dirty;
+use partial_function::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use {
+    Cooldown, EntityIds, GameDataPreset, GameSettings, Heightmap, Persistent, SnapshotRegistry,
+    SoundEvent, TerrainSettings,
+};
+
+/// An axis aligned box covering one cell of the terrain collision grid,
+/// from below the lowest point of the terrain up to the highest point of the cell.
+///
+/// `GroundCheckerSystem` raycasts against the bounding volumes of the physics world,
+/// so the terrain is split in many boxes instead of a single shape.
+/// Create one static physical entity per collider, with your ground `ObjectType`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainCollider {
+    pub center: Vector3<f32>,
+    pub size: Vector3<f32>,
+}
+
+/// Splits the terrain into `cells[0] * cells[1]` box colliders matching the mesh
+/// `generate_terrain` produces with the same settings.
+pub fn generate_terrain_colliders(
+    heightmap: &Heightmap,
+    settings: &TerrainSettings,
+    cells: [usize; 2],
+) -> Vec<TerrainCollider> {
+    let (rx, rz) = (settings.resolution[0].max(1), settings.resolution[1].max(1));
+    let (cx, cz) = (cells[0].max(1).min(rx), cells[1].max(1).min(rz));
+    let (sx, sz) = (settings.size[0], settings.size[1]);
+    let heights = settings.vertex_heights(heightmap);
+    // One world unit of thickness under the lowest point, so the boxes are never flat.
+    let bottom = heights.iter().fold(::std::f32::MAX, |m, h| m.min(*h)) - 1.0;
+
+    let mut colliders = Vec::with_capacity(cx * cz);
+    for cj in 0..cz {
+        for ci in 0..cx {
+            let (i0, i1) = (ci * rx / cx, (ci + 1) * rx / cx);
+            let (j0, j1) = (cj * rz / cz, (cj + 1) * rz / cz);
+            let mut top = bottom;
+            for j in j0..j1 + 1 {
+                for i in i0..i1 + 1 {
+                    top = top.max(heights[j * (rx + 1) + i]);
+                }
+            }
+            let x0 = -sx / 2.0 + i0 as f32 * sx / rx as f32;
+            let x1 = -sx / 2.0 + i1 as f32 * sx / rx as f32;
+            let z0 = -sz / 2.0 + j0 as f32 * sz / rz as f32;
+            let z1 = -sz / 2.0 + j1 as f32 * sz / rz as f32;
+            colliders.push(TerrainCollider {
+                center: Vector3::new((x0 + x1) / 2.0, (bottom + top) / 2.0, (z0 + z1) / 2.0),
+                size: Vector3::new(x1 - x0, top - bottom, z1 - z0),
+            });
+        }
+    }
+    colliders
+}
+
+impl TerrainCollider {
+    /// The rhusics collision shape of this collider. It is centered on `pose()`.
+    pub fn collision_shape<Y: Default>(&self) -> CollisionShape3<f32, BodyPose3<f32>, Y> {
+        CollisionShape::new_simple(
+            CollisionStrategy::FullResolution,
+            CollisionMode::Discrete,
+            Cuboid::new(self.size.x, self.size.y, self.size.z).into(),
+        )
+    }
+
+    /// The pose to give to the collider entity.
+    pub fn pose(&self) -> BodyPose3<f32> {
+        BodyPose3::new(Point3::from_vec(self.center), Quaternion::one())
+    }
+}
+
+/// Registers the first person controller systems of this crate.
+/// A and B are the axis and action types of the `InputHandler`,
+/// T is the `ObjectType` of the colliders the player can stand on.
+///
+/// Systems, their names and dependencies:
+/// - `MouseFocusUpdateSystem`: "mouse_focus"
+/// - `CursorHideSystem`: "cursor_hide", after "mouse_focus"
+/// - `FPSRotationRhusicsSystem`: "free_rotation", after "mouse_focus" because it only rotates
+/// while the window is focused.
+/// - `GroundCheckerSystem`: "ground_checker"
+/// - `JumpSystem`: "jump", after "ground_checker" so jumps use this frame's `Grounded` state.
+/// - `BhopMovementSystem`: "bhop_movement", after "ground_checker", "jump" and "free_rotation",
+/// to pick the ground or air acceleration and move along the current view direction.
+/// - `GroundFrictionSystem`: "ground_friction", after "ground_checker" and "bhop_movement",
+/// so friction applies to the velocity of this frame.
+///
+/// Dependencies on disabled systems are skipped. The ground checker is always added.
+/// Add the rhusics physics bundle after this one, and make the `TransformBundle` depend on
+/// "free_rotation".
+#[derive(new)]
+pub struct FpsBundle<A, B, T> {
+    /// The input axis moving the player left and right.
+    right_input_axis: A,
+    /// The input axis moving the player forward and backward.
+    forward_input_axis: A,
+    /// The input action making the player jump.
+    jump_action: B,
+    /// The types of colliders the player can stand and jump on.
+    ground_types: Vec<T>,
+    #[new(value = "0.2")]
+    sensitivity_x: f32,
+    #[new(value = "0.2")]
+    sensitivity_y: f32,
+    #[new(value = "true")]
+    cursor: bool,
+    #[new(value = "true")]
+    rotation: bool,
+    #[new(value = "true")]
+    jump: bool,
+    #[new(value = "true")]
+    bhop: bool,
+    #[new(value = "true")]
+    friction: bool,
+}
+
+impl<A, B, T> FpsBundle<A, B, T> {
+    /// Ignored when there is a `Dirty<GameSettings>` resource, see `FPSRotationRhusicsSystem::new`.
+    pub fn with_sensitivity(mut self, x: f32, y: f32) -> Self {
+        self.sensitivity_x = x;
+        self.sensitivity_y = y;
+        self
+    }
+
+    /// Window focus tracking and cursor hiding.
+    pub fn with_cursor(mut self, enabled: bool) -> Self {
+        self.cursor = enabled;
+        self
+    }
+
+    pub fn with_rotation(mut self, enabled: bool) -> Self {
+        self.rotation = enabled;
+        self
+    }
+
+    pub fn with_jump(mut self, enabled: bool) -> Self {
+        self.jump = enabled;
+        self
+    }
+
+    pub fn with_bhop(mut self, enabled: bool) -> Self {
+        self.bhop = enabled;
+        self
+    }
+
+    pub fn with_friction(mut self, enabled: bool) -> Self {
+        self.friction = enabled;
+        self
+    }
+}
+
+impl<'a, 'b, A, B, T> SystemBundle<'a, 'b> for FpsBundle<A, B, T>
+where
+    A: Send + Sync + Hash + Eq + Clone + 'static,
+    B: Send + Sync + Hash + Eq + Clone + 'static,
+    T: Component + PartialEq + Send + Sync + 'static,
+{
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> BundleResult<()> {
+        let mut rotation_deps = vec![];
+        if self.cursor {
+            builder.add(MouseFocusUpdateSystem::new(), "mouse_focus", &[]);
+            builder.add(CursorHideSystem::new(), "cursor_hide", &["mouse_focus"]);
+            rotation_deps.push("mouse_focus");
+        }
+        if self.rotation {
+            builder.add(
+                FPSRotationRhusicsSystem::<A, B>::new(self.sensitivity_x, self.sensitivity_y),
+                "free_rotation",
+                &rotation_deps,
+            );
+        }
+
+        builder.add(
+            GroundCheckerSystem::new(self.ground_types),
+            "ground_checker",
+            &[],
+        );
+        if self.jump {
+            builder.add(
+                JumpSystem::<A, B>::new(self.jump_action),
+                "jump",
+                &["ground_checker"],
+            );
+        }
+
+        if self.bhop {
+            let mut deps = vec!["ground_checker"];
+            if self.jump {
+                deps.push("jump");
+            }
+            if self.rotation {
+                deps.push("free_rotation");
+            }
+            builder.add(
+                BhopMovementSystem::<A, B>::new(
+                    Some(self.right_input_axis),
+                    Some(self.forward_input_axis),
+                ),
+                "bhop_movement",
+                &deps,
+            );
+        }
+
+        if self.friction {
+            let mut deps = vec!["ground_checker"];
+            if self.bhop {
+                deps.push("bhop_movement");
+            }
+            builder.add(GroundFrictionSystem, "ground_friction", &deps);
+        }
+        Ok(())
+    }
+}
+
+/// The name of the system synchronizing the rhusics body poses into the `Transform`s,
+/// registered by the `DefaultPhysicsBundle3` of amethyst-rhusics.
+const RHUSICS_SYNC_SYSTEM: &str = "sync_system";
+
+impl<A, B> GameDataPreset<A, B>
+where
+    A: Send + Sync + Hash + Eq + Clone + Serialize + DeserializeOwned + 'static,
+    B: Send + Sync + Hash + Eq + Clone + Serialize + DeserializeOwned + 'static,
+{
+    /// Builds the game data with rhusics 3d physics and the first person systems of this crate.
+    /// See `FpsBundle` for the systems added.
+    pub fn build_3d<T>(self, fps: FpsBundle<A, B, T>) -> Result<GameDataBuilder<'static, 'static>>
+    where
+        T: Component + Collider + PartialEq + Default + Send + Sync + 'static,
+    {
+        // The transforms have to be synchronized after the physics and rotation systems wrote them.
+        let mut transform_deps = vec![RHUSICS_SYNC_SYSTEM];
+        if fps.rotation {
+            transform_deps.push("free_rotation");
+        }
+        let builder = GameDataBuilder::default()
+            .with_bundle(fps)?
+            .with_bundle(DefaultPhysicsBundle3::<T>::new().with_spatial())?;
+        self.build_with(builder, &transform_deps)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, new, Component)]
+pub struct FpsMovement {
+    /// The movement speed in units per second.
+    pub speed: f32,
+}
+
+/// The system that manages the fly movement.
+/// Generic parameters are the parameters for the InputHandler.
+#[derive(new)]
+pub struct FpsMovementSystemSimple<A, B> {
+    /// The name of the input axis to locally move in the x coordinates.
+    /// Left and right.
+    right_input_axis: Option<A>,
+    /// The name of the input axis to locally move in the z coordinates.
+    /// Forward and backward. Please note that -z is forward when defining your input configurations.
+    forward_input_axis: Option<A>,
+    _phantomdata: PhantomData<B>,
+}
+
+impl<'a, A, B> System<'a> for FpsMovementSystemSimple<A, B>
+where
+    A: Send + Sync + Hash + Eq + Clone + 'static,
+    B: Send + Sync + Hash + Eq + Clone + 'static,
+{
+    type SystemData = (
+        Read<'a, Time>,
+        WriteStorage<'a, Transform>,
+        Read<'a, InputHandler<A, B>>,
+        ReadStorage<'a, FpsMovement>,
+        WriteStorage<'a, ForceAccumulator<Vector3<f32>, Vector3<f32>>>,
+    );
+
+    fn run(&mut self, (time, transforms, input, tags, mut forces): Self::SystemData) {
+        let x = get_input_axis_simple(&self.right_input_axis, &input);
+        let z = get_input_axis_simple(&self.forward_input_axis, &input);
+
+        let dir = Vector3::new(x, 0.0, z);
+        if dir.magnitude() != 0.0 {
+            for (transform, tag, mut force) in (&transforms, &tags, &mut forces).join() {
+                let mut dir = transform.rotation * dir;
+                dir = dir.normalize();
+                force.add_force(dir * tag.speed * time.delta_seconds());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Component)]
+pub struct RotationControl {
+    pub mouse_accum_x: f32,
+    pub mouse_accum_y: f32,
+}
+
+/// The system that manages the view rotation.
+/// Controlled by the mouse.
+///
+/// When there is a `Dirty<GameSettings>` resource, its mouse sensitivity is used
+/// instead of the one given to the system.
+#[derive(Debug)]
+pub struct FPSRotationRhusicsSystem<A, B> {
+    sensitivity_x: f32,
+    sensitivity_y: f32,
+    _marker1: PhantomData<A>,
+    _marker2: PhantomData<B>,
+    event_reader: Option<ReaderId<Event>>,
+}
+
+impl<A, B> FPSRotationRhusicsSystem<A, B> {
+    /// The sensitivities are in degrees of rotation per pixel of mouse movement.
+    ///
+    /// They are only used while there is no `Dirty<GameSettings>` resource.
+    /// If there is one, for example because an `AutoSaveSystem<GameSettings>` is added,
+    /// `GameSettings::sensitivity` is used instead, even if the settings were never changed.
+    pub fn new(sensitivity_x: f32, sensitivity_y: f32) -> Self {
+        FPSRotationRhusicsSystem {
+            sensitivity_x,
+            sensitivity_y,
+            _marker1: PhantomData,
+            _marker2: PhantomData,
+            event_reader: None,
+        }
+    }
+}
+
+impl<'a, A, B> System<'a> for FPSRotationRhusicsSystem<A, B>
+where
+    A: Send + Sync + Hash + Eq + Clone + 'static,
+    B: Send + Sync + Hash + Eq + Clone + 'static,
+{
+    type SystemData = (
+        Read<'a, EventChannel<Event>>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, BodyPose3<f32>>,
+        WriteStorage<'a, NextFrame<BodyPose3<f32>>>,
+        WriteStorage<'a, RotationControl>,
+        Read<'a, WindowFocus>,
+        Read<'a, HideCursor>,
+        ReadStorage<'a, FlyControlTag>,
+        Option<Read<'a, Dirty<GameSettings>>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            events,
+            mut transforms,
+            mut body_poses,
+            mut next_body_poses,
+            mut rotation_controls,
+            focus,
+            hide,
+            fly_controls,
+            settings,
+        ): Self::SystemData,
+    ) {
+        let (sensitivity_x, sensitivity_y) = settings
+            .map(|s| s.read().sensitivity())
+            .unwrap_or((self.sensitivity_x, self.sensitivity_y));
+        let focused = focus.is_focused;
+        for event in events.read(&mut self.event_reader.as_mut().unwrap()) {
+            if focused && hide.hide {
+                if let Event::DeviceEvent { ref event, .. } = *event {
+                    if let DeviceEvent::MouseMotion { delta: (x, y) } = *event {
+                        for (mut transform, mut rotation_control) in
+                            (&mut transforms, &mut rotation_controls).join()
+                        {
+                            rotation_control.mouse_accum_x -= x as f32 * sensitivity_x;
+                            rotation_control.mouse_accum_y += y as f32 * sensitivity_y;
+                            // Limit maximum vertical angle to prevent locking the quaternion and/or going upside down.
+                            rotation_control.mouse_accum_y =
+                                rotation_control.mouse_accum_y.max(-89.5).min(89.5);
+
+                            transform.rotation =
+                                Quaternion::from_angle_x(Deg(-rotation_control.mouse_accum_y));
+
+                            for (mut body_pose, mut next_body_pose, _) in
+                                (&mut body_poses, &mut next_body_poses, &fly_controls).join()
+                            {
+                                body_pose.set_rotation(Quaternion::from_angle_y(Deg(
+                                    rotation_control.mouse_accum_x,
+                                )));
+                                next_body_pose
+                                    .value
+                                    .set_rotation(Quaternion::from_angle_y(Deg(
+                                        rotation_control.mouse_accum_x
+                                    )));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.event_reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, new, Component)]
+pub struct Grounded {
+    #[new(value = "false")]
+    pub ground: bool,
+    #[new(default)]
+    pub since: f64,
+    pub distance_check: f32,
+    /// Checks if the selected entity collides with the ground.
+    #[serde(skip)]
+    pub watch_entity: Option<Entity>,
+}
+
+/// T: ObjectType for collider checks
+#[derive(new)]
+pub struct GroundCheckerSystem<T> {
+    pub collider_types: Vec<T>,
+    #[new(default)]
+    contact_reader: Option<ReaderId<ContactEvent<Entity, Point3<f32>>>>,
+}
+
+impl<'a, T: Component + PartialEq> System<'a> for GroundCheckerSystem<T> {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Transform>,
+        WriteStorage<'a, Grounded>,
+        ReadStorage<'a, T>,
+        Read<'a, DynamicBoundingVolumeTree3<f32>>,
+        Read<'a, Time>,
+        Read<'a, EventChannel<ContactEvent<Entity, Point3<f32>>>>,
+    );
+
+    fn setup(&mut self, mut res: &mut Resources) {
+        Self::SystemData::setup(&mut res);
+        /*self.contact_reader = Some(
+            res.fetch_mut::<EventChannel<ContactEvent<Entity, Point3<f32>>>>()
+                .register_reader(),
+        );*/
+    }
+
+    fn run(
+        &mut self,
+        (entities, transforms, mut grounded, objecttypes, tree, time, contacts): Self::SystemData,
+    ) {
+        let down = -Vector3::unit_y();
+        for (entity, transform, mut grounded) in (&*entities, &transforms, &mut grounded).join() {
+            let mut ground = false;
+
+            let ray = Ray3::new(Point3::from_vec(transform.translation), down);
+
+            // For all in ray
+            for (v, p) in query_ray(&*tree, ray) {
+                // Not self and close enough
+                if v.value != entity
+                    && (transform.translation - Vector3::new(p.x, p.y, p.z)).magnitude()
+                        <= grounded.distance_check
+                {
+                    // If we can jump off that type of collider
+                    if let Some(obj_type) = objecttypes.get(v.value) {
+                        if self.collider_types.contains(obj_type) {
+                            ground = true;
+                        }
+                    }
+                    //info!("hit bounding volume of {:?} at point {:?}", v.value, p);
+                }
+            }
+
+            /*info!("run {:?}", entity);
+            // Check for secondary collider if any
+            for contact in contacts.read(&mut self.contact_reader.as_mut().unwrap()) {
+                info!("Contact {:?} -> {:?}",contact.bodies.0, contact.bodies.1);
+                // Here because we need to empty the contacts EventChannel
+                if let Some(secondary) = grounded.watch_entity {
+                    info!("Secondary");
+                    if contact.bodies.0 == entity || contact.bodies.1 == entity {
+                        // We hit our player... let's ignore that.
+                        continue;
+                    }
+                    info!("tmp1");
+                    if contact.bodies.0 != secondary && contact.bodies.1 != secondary {
+                        // This has nothing to do with the secondary collider. Skip!
+                        continue;
+                    }
+                    info!("type check");
+                    let type1 = objecttypes.get(contact.bodies.0);
+                    let type2 = objecttypes.get(contact.bodies.1);
+
+                    if type1.is_none() || type2.is_none() {
+                        continue;
+                    }
+                    info!("good to go");
+                    // If we can jump off that type of collider
+                    if self.collider_types.contains(type1.unwrap())
+                        || self.collider_types.contains(type2.unwrap())
+                    {
+                        ground = true;
+                    }
+                }
+            }*/
+
+            if ground && !grounded.ground {
+                // Just grounded
+                grounded.since = time.absolute_time_seconds();
+            }
+            grounded.ground = ground;
+        }
+    }
+}
+
+#[derive(Default, Component, new)]
+pub struct Jump {
+    pub absolute: bool,
+    pub check_ground: bool,
+    pub jump_force: f32,
+    pub auto_jump: bool,
+    /// Minimum time between two jumps.
+    #[new(value = "Cooldown::new(0.3333)")]
+    pub jump_cooldown: Cooldown,
+    /// Minimum time between two jump inputs.
+    #[new(value = "Cooldown::new(0.1)")]
+    pub input_cooldown: Cooldown,
+    /// Multiplier. Time can go in the negatives.
+    #[new(default)]
+    pub jump_timing_boost: Option<PartialFunction<f64, f32>>,
+    #[new(default)]
+    pub last_jump_offset: f64,
+    /// Name of the sound effect played on each jump, through a `SoundEvent`.
+    #[new(default)]
+    pub jump_sound: Option<String>,
+}
+
+/// Makes the entities with a `Jump` component jump when the jump action is pressed.
+/// Generic parameters are the parameters for the InputHandler.
+/// `JumpSystem::default()` uses the "jump" action of an `InputHandler<String, String>`.
+#[derive(new)]
+pub struct JumpSystem<A = String, B = String> {
+    /// The input action making the entities jump.
+    jump_action: B,
+    /// Was the jump key pressed last frame?
+    #[new(default)]
+    input_hold: bool,
+    /// The last time we physically pressed the jump key.
+    #[new(default)]
+    last_physical_press: f64,
+    phantom_data: PhantomData<A>,
+}
+
+impl Default for JumpSystem<String, String> {
+    fn default() -> Self {
+        JumpSystem::new("jump".to_string())
+    }
+}
+
+impl<'a, A, B> System<'a> for JumpSystem<A, B>
+where
+    A: Send + Sync + Hash + Eq + Clone + 'static,
+    B: Send + Sync + Hash + Eq + Clone + 'static,
+{
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Grounded>,
+        WriteStorage<'a, Jump>,
+        Read<'a, Time>,
+        Read<'a, InputHandler<A, B>>,
+        WriteStorage<'a, ForceAccumulator<Vector3<f32>, Vector3<f32>>>,
+        WriteStorage<'a, NextFrame<Velocity3<f32>>>,
+        ReadStorage<'a, GlobalTransform>,
+        Write<'a, EventChannel<SoundEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut grounded,
+            mut jumps,
+            time,
+            input,
+            mut forces,
+            mut velocities,
+            transforms,
+            mut sound_events,
+        ): Self::SystemData,
+    ) {
+        if let Some(true) = input.action_is_down(&self.jump_action) {
+            if !self.input_hold {
+                // We just started pressing the key. Registering time.
+                self.last_physical_press = time.absolute_time_seconds();
+                self.input_hold = true;
+            }
+
+            for (entity, mut jump, mut force, mut velocity) in
+                (&*entities, &mut jumps, &mut forces, &mut velocities).join()
+            {
+                // Holding the jump key on a non-auto jump controller.
+                if self.input_hold && !jump.auto_jump {
+                    continue;
+                }
+
+                // The last time we jumped wasn't long enough ago
+                if !jump.input_cooldown.trigger(&time) {
+                    continue;
+                }
+
+                // If we need to check for it, verify that we are on the ground.
+                let mut grounded_since = time.absolute_time_seconds();
+                if jump.check_ground {
+                    if let Some(ground) = grounded.get(entity) {
+                        if !ground.ground {
+                            continue;
+                        }
+                        grounded_since = ground.since;
+                    } else {
+                        continue;
+                    }
+                }
+
+                if jump.jump_cooldown.trigger(&time) {
+                    // Jump!
+                    // Offset for jump. Positive = time when we jumped AFTER we hit the ground.
+                    jump.last_jump_offset = grounded_since - self.last_physical_press;
+
+                    let multiplier = if let Some(ref curve) = jump.jump_timing_boost {
+                        curve.eval(jump.last_jump_offset).unwrap_or(1.0)
+                    } else {
+                        1.0
+                    };
+
+                    if !jump.absolute {
+                        force.add_force(Vector3::<f32>::unit_y() * jump.jump_force * multiplier);
+                    } else {
+                        let (x, z) = {
+                            let v = velocity.value.linear();
+                            (v.x, v.z)
+                        };
+                        velocity
+                            .value
+                            .set_linear(Vector3::new(x, jump.jump_force, z));
+                    }
+
+                    if let Some(ref sound) = jump.jump_sound {
+                        // The listener position is global too.
+                        sound_events.single_write(match transforms.get(entity) {
+                            Some(t) => {
+                                SoundEvent::at(sound, Vector3::new(t.0.w.x, t.0.w.y, t.0.w.z))
+                            }
+                            None => SoundEvent::new(sound),
+                        });
+                    }
+                }
+                if let Some(ref mut ground) = grounded.get_mut(entity) {
+                    ground.ground = false;
+                }
+            }
+        } else {
+            // The jump key was released.
+            self.input_hold = false;
+        }
+    }
+}
+
+/// The settings controlling how the entity controlled by the `BhopMovementSystem` will behave.
+/// This is a component that you should add on the entity.
+#[derive(Serialize, Deserialize, Debug, Clone, Component, new)]
+pub struct BhopMovement3D {
+    /// False = Forces, True = Velocity
+    pub absolute: bool,
+    /// Use world coordinates XYZ.
+    #[new(default)]
+    pub absolute_axis: bool,
+    /// Negates the velocity when pressing the key opposite to the current velocity.
+    /// Effectively a way to instantly stop, even at high velocities.
+    #[new(default)]
+    pub counter_impulse: bool,
+    /// Acceleration in unit/s² while on the ground.
+    pub accelerate_ground: f32,
+    /// Acceleration in unit/s² while in the air.
+    pub accelerate_air: f32,
+    /// The maximum ground velocity.
+    pub max_velocity_ground: f32,
+    /// The maximum air velocity.
+    pub max_velocity_air: f32,
+    /// Enables accelerating over maximumVelocity by airstrafing. Bunnyhop in a nutshell.
+    pub allow_projection_acceleration: bool,
+}
+
+/// The system that manages the first person movements (with added projection acceleration capabilities).
+/// Generic parameters are the parameters for the InputHandler.
+#[derive(new)]
+pub struct BhopMovementSystem<A, B> {
+    /// The name of the input axis to locally move in the x coordinates.
+    right_input_axis: Option<A>,
+    /// The name of the input axis to locally move in the z coordinates.
+    forward_input_axis: Option<A>,
+    phantom_data: PhantomData<B>,
+}
+
+impl<'a, A, B> System<'a> for BhopMovementSystem<A, B>
+where
+    A: Send + Sync + Hash + Eq + Clone + 'static,
+    B: Send + Sync + Hash + Eq + Clone + 'static,
+{
+    type SystemData = (
+        Read<'a, Time>,
+        Read<'a, InputHandler<A, B>>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, BhopMovement3D>,
+        ReadStorage<'a, Grounded>,
+        WriteStorage<'a, NextFrame<Velocity3<f32>>>,
+    );
+
+    fn run(
+        &mut self,
+        (time, input, transforms, movements, groundeds, mut velocities): Self::SystemData,
+    ) {
+        let x = get_input_axis_simple(&self.right_input_axis, &input);
+        let z = get_input_axis_simple(&self.forward_input_axis, &input);
+        let input = Vector2::new(x, z);
+
+        if input.magnitude() != 0.0 {
+            for (transform, movement, grounded, mut velocity) in
+                (&transforms, &movements, &groundeds, &mut velocities).join()
+            {
+                let (acceleration, max_velocity) = if grounded.ground {
+                    (movement.accelerate_ground, movement.max_velocity_ground)
+                } else {
+                    (movement.accelerate_air, movement.max_velocity_air)
+                };
+
+                // Global to local coords.
+                let mut relative = SquareMatrix::invert(Basis3::from(transform.rotation).as_ref())
+                    .unwrap()
+                    * velocity.value.linear();
+
+                let new_vel_rel = if movement.absolute {
+                    // Absolute = We immediately set the maximum velocity without checking the max speed.
+                    Vector3::new(input.x * acceleration, relative.y, input.y * acceleration)
+                } else {
+                    let mut wish_vel = relative;
+
+                    if movement.counter_impulse {
+                        wish_vel = counter_impulse(input, wish_vel);
+                    }
+
+                    wish_vel = accelerate_vector(
+                        time.delta_seconds(),
+                        input,
+                        wish_vel,
+                        acceleration,
+                        max_velocity,
+                    );
+                    if !movement.allow_projection_acceleration {
+                        wish_vel = limit_velocity(wish_vel, max_velocity);
+                    }
+
+                    wish_vel
+                };
+
+                // Global to local coords;
+                let new_vel = transform.rotation * new_vel_rel;
+
+                // Assign the new velocity to the player
+                velocity.value.set_linear(new_vel);
+            }
+        }
+    }
+}
+
+/// The way friction is applied.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub enum FrictionMode {
+    /// The velocity is reduced by a fixed amount each second (deceleration).
+    Linear,
+    /// The velocity is reduced by a fraction of the current velocity.
+    /// A value of 0.2 means that approximatively 20% of the speed will be lost each second.
+    /// Since it is not calculated as an integration but as discrete values, the actual slowdown will vary slightly from case to case.
+    Percent,
+}
+
+/// Component you add to your entities to apply a ground friction.
+/// What the friction field does is dependent on the choosen `FrictionMode`.
+#[derive(Serialize, Deserialize, Clone, Debug, Component, new)]
+pub struct GroundFriction3D {
+    /// The amount of friction speed loss by second.
+    pub friction: f32,
+    /// The way friction is applied.
+    pub friction_mode: FrictionMode,
+    /// The time to wait after touching the ground before applying the friction.
+    pub ground_time_before_apply: f64,
+}
+
+/// Applies friction (slows the velocity down) according to the `GroundFriction3D` component of your entity.
+/// Your entity also needs to have a `Grounded` component (and the `GroundCheckerSystem` added to your dispatcher) to detect the ground.
+/// It also needs to have a NextFrame<Velocity3<f32>> component. This is added automatically by rhusics when creating a dynamic physical entity.
+pub struct GroundFrictionSystem;
+
+impl<'a> System<'a> for GroundFrictionSystem {
+    type SystemData = (
+        Read<'a, Time>,
+        ReadStorage<'a, Grounded>,
+        ReadStorage<'a, GroundFriction3D>,
+        WriteStorage<'a, NextFrame<Velocity3<f32>>>,
+    );
+
+    fn run(&mut self, (time, groundeds, frictions, mut velocities): Self::SystemData) {
+        fn apply_friction_single(v: f32, friction: f32) -> f32 {
+            if v.abs() <= friction {
+                return 0.0;
+            }
+            v - friction
+        }
+        for (grounded, friction, mut velocity) in (&groundeds, &frictions, &mut velocities).join() {
+            if grounded.ground
+                && time.absolute_time_seconds() - grounded.since
+                    >= friction.ground_time_before_apply
+            {
+                let (x, y, z) = {
+                    let v = velocity.value.linear();
+                    (v.x, v.y, v.z)
+                };
+                match friction.friction_mode {
+                    FrictionMode::Linear => {
+                        let slowdown = friction.friction * time.delta_seconds();
+                        velocity.value.set_linear(Vector3::new(
+                            apply_friction_single(x, slowdown),
+                            y,
+                            apply_friction_single(z, slowdown),
+                        ));
+                    }
+                    FrictionMode::Percent => {
+                        let coef = friction.friction * time.delta_seconds();
+                        velocity.value.set_linear(Vector3::new(
+                            apply_friction_single(x, x * coef),
+                            y,
+                            apply_friction_single(z, z * coef),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Accelerates the given `relative` vector by the given `acceleration` and `input`.
+/// The `maximum_velocity` is only taken into account for the projection of the acceleration vector on the `relative` vector.
+/// This allows going over the speed limit by performing what is called a "strafe".
+/// If your velocity is forward and have an input accelerating you to the right, the projection of
+/// the acceleration vector over your current velocity will be 0. This means that the acceleration vector will be applied fully,
+/// even if this makes the resulting vector's magnitude go over `max_velocity`.
+pub fn accelerate_vector(
+    delta_time: f32,
+    input: Vector2<f32>,
+    rel: Vector3<f32>,
+    acceleration: f32,
+    max_velocity: f32,
+) -> Vector3<f32> {
+    let mut o = rel;
+    let input3 = Vector3::new(input.x, 0.0, input.y);
+    let rel_flat = Vector3::new(rel.x, 0.0, rel.z);
+    if input3.magnitude() > 0.0 {
+        let proj = rel_flat.dot(input3.normalize());
+        let mut accel_velocity = acceleration * delta_time as f32;
+        if proj + accel_velocity > max_velocity {
+            accel_velocity = max_velocity - proj;
+        }
+        if accel_velocity > 0.0 {
+            let add_speed = input3 * accel_velocity;
+            o += add_speed;
+        }
+    }
+    o
+}
+
+/// Completely negates the velocity of a specific axis if an input is performed in the opposite direction.
+pub fn counter_impulse(input: Vector2<f32>, relative_velocity: Vector3<f32>) -> Vector3<f32> {
+    let mut o = relative_velocity;
+    if (input.x < 0.0 && relative_velocity.x > 0.001)
+        || (input.x > 0.0 && relative_velocity.x < -0.001)
+    {
+        o = Vector3::new(0.0, relative_velocity.y, relative_velocity.z);
+    }
+    if (input.y < 0.0 && relative_velocity.z < -0.001)
+        || (input.y > 0.0 && relative_velocity.z > 0.001)
+    {
+        o = Vector3::new(relative_velocity.x, relative_velocity.y, 0.0);
+    }
+    o
+}
+
+/// Limits the total velocity so that its magnitude doesn't exceed `maximum_velocity`.
+/// If you are using the `accelerate_vector` function, calling this will ensure that air strafing
+/// doesn't allow you to go over the maximum velocity, while still keeping fluid controls.
+pub fn limit_velocity(vec: Vector3<f32>, maximum_velocity: f32) -> Vector3<f32> {
+    let v_flat = Vector2::new(vec.x, vec.z).magnitude();
+    if v_flat > maximum_velocity && maximum_velocity != 0.0 {
+        let ratio = maximum_velocity / v_flat;
+        return Vector3::new(vec.x * ratio, vec.y, vec.z * ratio);
+    }
+    vec
+}
+
+/// The persisted part of `Grounded`. The ground state is computed again after loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroundedData {
+    pub distance_check: f32,
+    pub watch_entity: Option<u32>,
+}
+
+impl Persistent for Grounded {
+    type Data = GroundedData;
+
+    fn name() -> &'static str {
+        "grounded"
+    }
+
+    fn save(&self, ids: &EntityIds) -> GroundedData {
+        GroundedData {
+            distance_check: self.distance_check,
+            watch_entity: self.watch_entity.and_then(|e| ids.id(e)),
+        }
+    }
+
+    fn load(data: GroundedData, ids: &EntityIds) -> Self {
+        Grounded::new(
+            data.distance_check,
+            data.watch_entity.and_then(|id| ids.entity(id)),
+        )
+    }
+}
+
+/// The settings of `Jump`. `jump_timing_boost` can't be saved and has to be set again after loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JumpData {
+    pub absolute: bool,
+    pub check_ground: bool,
+    pub jump_force: f32,
+    pub auto_jump: bool,
+    pub jump_cooldown: f64,
+    pub input_cooldown: f64,
+    pub jump_sound: Option<String>,
+}
+
+impl Persistent for Jump {
+    type Data = JumpData;
+
+    fn name() -> &'static str {
+        "jump"
+    }
+
+    fn save(&self, _ids: &EntityIds) -> JumpData {
+        JumpData {
+            absolute: self.absolute,
+            check_ground: self.check_ground,
+            jump_force: self.jump_force,
+            auto_jump: self.auto_jump,
+            jump_cooldown: self.jump_cooldown.duration,
+            input_cooldown: self.input_cooldown.duration,
+            jump_sound: self.jump_sound.clone(),
+        }
+    }
+
+    fn load(data: JumpData, _ids: &EntityIds) -> Self {
+        let mut jump = Jump::new(
+            data.absolute,
+            data.check_ground,
+            data.jump_force,
+            data.auto_jump,
+        );
+        jump.jump_cooldown.duration = data.jump_cooldown;
+        jump.input_cooldown.duration = data.input_cooldown;
+        jump.jump_sound = data.jump_sound;
+        jump
+    }
+}
+
+impl SnapshotRegistry {
+    /// Saves the `Grounded`, `Jump`, `BhopMovement3D`, `GroundFriction3D`
+    /// and `RotationControl` components.
+    pub fn with_fps_components(self) -> Self {
+        self.with_persistent::<Grounded>()
+            .with_persistent::<Jump>()
+            .with::<BhopMovement3D>("bhop_movement")
+            .with::<GroundFriction3D>("ground_friction")
+            .with::<RotationControl>("rotation_control")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amethyst::core::cgmath::Vector2;
+    use *;
+
+    #[test]
+    fn terrain_colliders() {
+        let heightmap = Heightmap::from_fn(4, 4, |_, _| 0.5);
+        let settings = TerrainSettings::new([10., 20.], 4., [5, 10]);
+        let colliders = generate_terrain_colliders(&heightmap, &settings, [2, 2]);
+        assert_eq!(colliders.len(), 4);
+        assert!(colliders.iter().all(|c| c.center.y + c.size.y / 2.0 == 2.0));
+    }
+
+    #[test]
+    fn default_jump_system_uses_jump_action() {
+        let system = JumpSystem::default();
+        assert_eq!(system.jump_action, "jump");
+    }
+
+    #[test]
+    fn velocity_limits() {
+        let limited = limit_velocity(Vector3::new(3.0, 5.0, 4.0), 2.5);
+        assert_eq!(limited, Vector3::new(1.5, 5.0, 2.0));
+        let stopped = counter_impulse(Vector2::new(-1.0, 0.0), Vector3::new(2.0, 1.0, 3.0));
+        assert_eq!(stopped, Vector3::new(0.0, 1.0, 3.0));
+    }
+
+    #[test]
+    fn snapshot_remaps_watched_entity() {
+        let mut world = World::new();
+        world.register::<Persist>();
+        world.register::<Grounded>();
+        world.register::<Jump>();
+        let feet = world.create_entity().with(Persist).build();
+        world
+            .create_entity()
+            .with(Persist)
+            .with(Grounded::new(0.1, Some(feet)))
+            .with(Jump::new(false, true, 5.0, true))
+            .build();
+        let registry = SnapshotRegistry::new().with_fps_components();
+        let snapshot = registry.snapshot(&world);
+
+        let mut loaded = World::new();
+        // Offsets the entity ids of the new world.
+        loaded.create_entity().build();
+        let entities = registry.restore(&mut loaded, &snapshot);
+        assert_eq!(entities.len(), 2);
+        let grounded = loaded.read_storage::<Grounded>();
+        let restored = grounded.get(entities[1]).unwrap();
+        assert_eq!(restored.watch_entity, Some(entities[0]));
+        assert_ne!(restored.watch_entity, Some(feet));
+        assert_eq!(
+            loaded
+                .read_storage::<Jump>()
+                .get(entities[1])
+                .unwrap()
+                .jump_force,
+            5.0
+        );
+    }
+}
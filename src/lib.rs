@@ -2,483 +2,348 @@ extern crate amethyst;
 #[macro_use]
 extern crate serde;
 extern crate ron;
+#[cfg(feature = "cbor")]
+extern crate serde_cbor;
+#[cfg(feature = "json")]
+extern crate serde_json;
 #[macro_use]
 extern crate log;
-extern crate crossterm;
+#[cfg(feature = "dirs")]
+extern crate dirs;
 extern crate dirty;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[macro_use]
+extern crate derive_new;
+#[macro_use]
+extern crate specs_derive;
+#[cfg(feature = "console")]
+extern crate crossterm;
+#[cfg(feature = "console")]
 extern crate fern;
+#[cfg(any(feature = "loot", feature = "physics-fps"))]
 extern crate partial_function;
+#[cfg(any(feature = "audio", feature = "loot"))]
 extern crate rand;
+#[cfg(feature = "console")]
 #[macro_use]
 extern crate lazy_static;
-#[macro_use]
-extern crate derive_new;
-#[macro_use]
-extern crate specs_derive;
+#[cfg(feature = "physics-fps")]
 extern crate amethyst_rhusics;
+#[cfg(feature = "discord")]
 extern crate discord_rpc_client;
+#[cfg(feature = "assets")]
+extern crate image;
+#[cfg(feature = "audio")]
+extern crate rodio;
+
+#[cfg(feature = "assets")]
+mod assets;
+#[cfg(feature = "audio")]
+mod audio;
+#[cfg(feature = "console")]
+mod console;
+mod destroy;
+#[cfg(feature = "discord")]
+mod discord;
+#[cfg(feature = "physics-fps")]
+mod fps;
+#[cfg(feature = "loot")]
+mod loot;
+mod save;
+mod settings;
+#[cfg(feature = "physics-fps")]
+mod speedrun;
+mod timer;
+
+#[cfg(feature = "assets")]
+pub use assets::*;
+#[cfg(feature = "audio")]
+pub use audio::*;
+pub use destroy::*;
+#[cfg(feature = "discord")]
+pub use discord::*;
+#[cfg(feature = "physics-fps")]
+pub use fps::*;
+#[cfg(feature = "loot")]
+pub use loot::*;
+pub use save::*;
+pub use settings::*;
+#[cfg(feature = "physics-fps")]
+pub use speedrun::*;
+pub use timer::*;
 
-use amethyst::controls::FlyControlTag;
-use amethyst::controls::HideCursor;
-use amethyst::controls::WindowFocus;
-use amethyst::core::cgmath::EuclideanSpace;
 use amethyst::core::cgmath::InnerSpace;
-use amethyst::core::cgmath::Vector2;
 use amethyst::core::cgmath::Vector3;
-use amethyst::core::cgmath::{Basis3, Deg, Point3, Quaternion, Rotation3};
 use amethyst::renderer::Camera;
-use amethyst::renderer::DeviceEvent;
 use amethyst::renderer::DrawFlat;
-use amethyst::renderer::Event;
+use amethyst::renderer::DrawPbm;
+use amethyst::renderer::DrawShaded;
 use amethyst::renderer::Material;
 use amethyst::renderer::MaterialDefaults;
 use amethyst::renderer::Mesh;
+use amethyst::renderer::MeshData;
 use amethyst::renderer::PngFormat;
+use amethyst::renderer::PosNormTangTex;
+use amethyst::renderer::PosNormTex;
 use amethyst::renderer::PosTex;
 use amethyst::renderer::ScreenDimensions;
 use amethyst::renderer::Texture;
+use amethyst::renderer::TextureData;
 use amethyst::renderer::TextureMetadata;
-use amethyst::shrev::EventChannel;
-use amethyst_rhusics::collision::dbvt::query_ray;
-use amethyst_rhusics::collision::Ray3;
-use amethyst_rhusics::rhusics_core::physics3d::Velocity3;
-use amethyst_rhusics::rhusics_core::ContactEvent;
-use amethyst_rhusics::rhusics_core::ForceAccumulator;
-use amethyst_rhusics::rhusics_core::NextFrame;
-use amethyst_rhusics::rhusics_core::Pose;
-use amethyst_rhusics::rhusics_ecs::collide3d::DynamicBoundingVolumeTree3;
-use amethyst_rhusics::rhusics_ecs::physics3d::BodyPose3;
-use rand::{thread_rng, Rng};
+use amethyst::renderer::TextureOffset;
 
 use amethyst::animation::AnimationBundle;
 use amethyst::assets::*;
-use amethyst::audio::{AudioBundle, SourceHandle};
+#[cfg(feature = "audio")]
+use amethyst::audio::{AudioBundle, Source};
+use amethyst::core::bundle::{Result as BundleResult, SystemBundle};
 use amethyst::core::cgmath::{SquareMatrix, Vector4};
 use amethyst::core::timing::Time;
 use amethyst::core::*;
 use amethyst::ecs::*;
-use amethyst::input::get_input_axis_simple;
 use amethyst::input::*;
 use amethyst::prelude::*;
 use amethyst::ui::{UiBundle, UiText};
 use amethyst::Result;
-use discord_rpc_client::Client as DiscordClient;
-use dirty::Dirty;
-use partial_function::*;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::collections::HashMap;
-use std::fs;
-use std::fs::File;
 use std::hash::Hash;
-use std::io::Read as IORead;
-use std::io::Write as IOWrite;
-use std::iter::Cycle;
 use std::marker::PhantomData;
 use std::ops::{Add, Sub};
-use std::path::Path;
-use std::sync::{Arc, Mutex};
-use std::thread::{sleep, spawn};
-use std::time::Duration;
-use std::vec::IntoIter;
-
-use crossterm::cursor::TerminalCursor;
-//use crossterm::screen::RawScreen;
-use crossterm::terminal::{ClearType, Terminal};
-use crossterm::{Crossterm, Screen};
-
-lazy_static! {
-    static ref CROSSTERM: Crossterm = {
-        let mut screen = Screen::new(true);
-        screen.disable_drop();
-        Crossterm::new(&screen)
-    };
-}
-
-/// Loads asset from the so-called asset packs
-/// It caches assets which you can manually load or unload on demand.
-///
-/// Example:
-/// If the folder structure looks like this
-/// /assets/base/sprites/player.png
-/// /assets/base/sounds/click.ogg
-/// /assets/base/models/cube.obj
-/// /assets/mod1/sprites/player.png
-/// /assets/mod1/sounds/click.ogg
-/// /assets/mod2/sounds/click.ogg
-///
-/// resolve_path("sprites/player.png") -> /assets/mod1/sprites/player.png
-/// resolve_path("models/cube.obj") -> /assets/base/models/cube.obj
-/// resolve_path("sounds/click.ogg") -> Unknown.
-pub struct AssetLoader {
-    base_path: String,
-    default_pack: String,
-    asset_packs: Vec<String>,
-}
-
-impl AssetLoader {
-    pub fn new(base_path: &str, default_pack: &str) -> Self {
-        let mut al = AssetLoader {
-            base_path: AssetLoader::sanitize_path_trail_only(&base_path),
-            default_pack: AssetLoader::sanitize_path(&default_pack),
-            asset_packs: Vec::new(),
-        };
-        al.get_asset_packs();
-        al
-    }
-
-    fn sanitize_path_trail_only(path: &str) -> String {
-        let mut out = path.to_string();
-        let chars = path.chars();
-        let last = chars.last().unwrap();
-        if last == '/' {
-            let idx = out.len() - 1;
-            out.remove(idx);
-        }
-        out
-    }
-
-    fn sanitize_path(path: &str) -> String {
-        let mut out = path.to_string();
-        let mut chars = path.chars();
-        let first = chars.next().expect("An empty path was specified!");
-        let last = chars.last().unwrap();
-        if first == '/' {
-            out.remove(0);
-        }
-        if last == '/' {
-            let idx = out.len() - 1;
-            out.remove(idx);
-        }
-        out
-    }
-
-    pub fn resolve_path(&self, path: &str) -> Option<String> {
-        // Try to get from default path
-        let mut res = self.resolve_path_for_pack(path, &self.default_pack);
 
-        // Try to find overrides
-        for p in &self.asset_packs {
-            if p != &self.default_pack {
-                if let Some(r) = self.resolve_path_for_pack(path, &p) {
-                    res = Some(r);
-                }
-            }
-        }
+/*pub trait AssetToFormat<T> where T: Sized{
+    fn get_format() -> Format<T>;
+}
 
-        res
+impl AssetToFormat<Mesh> for Mesh{
+    fn get_format() -> Format<Mesh>{
+        ObjFormat
     }
+}*/
 
-    fn resolve_path_for_pack(&self, path: &str, pack: &str) -> Option<String> {
-        let mut abs = self.base_path.to_owned() + "/" + pack + "/" + &path.to_owned();
-        if cfg!(windows) {
-            abs = abs.replace("/", "\\");
-        }
-
-        let path = Path::new(&abs);
-        if path.exists() {
-            Some(abs.clone())
-        } else {
-            warn!("Failed to find file at path: {}", abs);
-            None
-        }
-    }
-
-    pub fn get_asset_packs(&mut self) -> &Vec<String> {
-        let mut buf: Option<Vec<String>> = None;
-        if self.asset_packs.len() == 0 {
-            if let Ok(elems) = fs::read_dir(&self.base_path) {
-                buf = Some(
-                    elems
-                        .map(|e| {
-                            let path = &e.unwrap().path();
-                            let tmp = &path.to_str().unwrap()[self.base_path.len()..];
-                            AssetLoader::sanitize_path(&tmp)
-                        }).collect(),
-                );
-            } else {
-                error!(
-                    "Failed to find base_path directory for asset loading: {}",
-                    self.base_path
-                );
-            }
-        }
+/// Vertex types that can be compared bit for bit, so that identical vertices can be merged
+/// when building an `IndexedMesh`.
+pub trait VertexKey {
+    type Key: Hash + Eq;
+    fn vertex_key(&self) -> Self::Key;
+}
 
-        if let Some(v) = buf {
-            self.asset_packs = v;
-        }
+/// Bit pattern of a float, with -0.0 folded into 0.0 so both merge together.
+fn float_key(f: f32) -> u32 {
+    (f + 0.0).to_bits()
+}
 
-        &self.asset_packs
+impl VertexKey for PosTex {
+    type Key = [u32; 5];
+    fn vertex_key(&self) -> Self::Key {
+        [
+            float_key(self.position[0]),
+            float_key(self.position[1]),
+            float_key(self.position[2]),
+            float_key(self.tex_coord[0]),
+            float_key(self.tex_coord[1]),
+        ]
     }
+}
 
-    pub fn get_asset_handle<T>(path: &str, ali: &AssetLoaderInternal<T>) -> Option<Handle<T>> {
-        ali.assets.get(path).cloned()
+impl VertexKey for PosNormTex {
+    type Key = [u32; 8];
+    fn vertex_key(&self) -> Self::Key {
+        [
+            float_key(self.position[0]),
+            float_key(self.position[1]),
+            float_key(self.position[2]),
+            float_key(self.normal[0]),
+            float_key(self.normal[1]),
+            float_key(self.normal[2]),
+            float_key(self.tex_coord[0]),
+            float_key(self.tex_coord[1]),
+        ]
     }
+}
 
-    pub fn get_asset<'a, T>(
-        path: &str,
-        ali: &AssetLoaderInternal<T>,
-        storage: &'a AssetStorage<T>,
-    ) -> Option<&'a T>
-    where
-        T: Asset,
-    {
-        if let Some(h) = AssetLoader::get_asset_handle::<T>(path, ali) {
-            storage.get(&h)
-        } else {
-            None
-        }
+impl VertexKey for PosNormTangTex {
+    type Key = [u32; 11];
+    fn vertex_key(&self) -> Self::Key {
+        [
+            float_key(self.position[0]),
+            float_key(self.position[1]),
+            float_key(self.position[2]),
+            float_key(self.normal[0]),
+            float_key(self.normal[1]),
+            float_key(self.normal[2]),
+            float_key(self.tangent[0]),
+            float_key(self.tangent[1]),
+            float_key(self.tangent[2]),
+            float_key(self.tex_coord[0]),
+            float_key(self.tex_coord[1]),
+        ]
     }
+}
 
-    pub fn get_asset_or_load<'a, T, F>(
-        &mut self,
-        path: &str,
-        format: F,
-        options: F::Options,
-        ali: &mut AssetLoaderInternal<T>,
-        storage: &'a mut AssetStorage<T>,
-        loader: &Loader,
-    ) -> Option<&'a T>
-    where
-        T: Asset,
-        F: Format<T> + 'static,
-    {
-        if let Some(h) = AssetLoader::get_asset_handle::<T>(path, ali) {
-            return storage.get(&h);
-            //return Some(a);
-        }
-        if let Some(h) = self.load::<T, F>(path, format, options, ali, storage, loader) {
-            return storage.get(&h);
-        }
-        None
+/// A mesh stored as a buffer of unique vertices and a buffer of indices into it.
+/// Every three indices form a triangle.
+///
+/// The renderer currently only uploads triangle lists, so the mesh is expanded
+/// when it is converted into `MeshData`. Keeping the indexed form around is what saves
+/// memory when you generate and cache a lot of shapes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedMesh<V> {
+    pub vertices: Vec<V>,
+    pub indices: Vec<u32>,
+}
+
+impl<V: Clone> IndexedMesh<V> {
+    pub fn new(vertices: Vec<V>, indices: Vec<u32>) -> Self {
+        IndexedMesh { vertices, indices }
     }
 
-    pub fn load<T, F>(
-        &self,
-        path: &str,
-        format: F,
-        options: F::Options,
-        ali: &mut AssetLoaderInternal<T>,
-        storage: &mut AssetStorage<T>,
-        loader: &Loader,
-    ) -> Option<Handle<T>>
-    where
-        T: Asset,
-        F: Format<T> + 'static,
-    {
-        if let Some(handle) = AssetLoader::get_asset_handle(path, ali) {
-            return Some(handle);
-        }
-        if let Some(p) = self.resolve_path(path) {
-            let handle: Handle<T> = loader.load(p, format, options, (), storage);
-            ali.assets.insert(String::from(path), handle.clone());
-            return Some(handle);
-        }
-        None
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
     }
 
-    /// Only removes the internal Handle<T>. To truly unload the asset, you need to drop all handles that you have to it.
-    pub fn unload<T>(path: &str, ali: &mut AssetLoaderInternal<T>) {
-        ali.assets.remove(path);
+    /// Expands the indices into a flat triangle list.
+    pub fn to_triangle_list(&self) -> Vec<V> {
+        self.indices
+            .iter()
+            .map(|i| self.vertices[*i as usize].clone())
+            .collect()
     }
+}
 
-    /*pub fn load_from_extension<T>(&mut self,path: &str,ali: &mut AssetLoaderInternal<T>, storage: &mut AssetStorage<T>, loader: Loader) -> Option<Handle<T>> where T: Asset{
-        let ext = AssetLoader::extension_from_path(path);
-        match ext{
-            "obj" => Some(self.load::<Mesh,ObjFormat>(path,ObjFormat,ali,storage,loader)),
-            _ => None,
+impl<V: Clone + VertexKey> IndexedMesh<V> {
+    /// Builds an indexed mesh from a flat triangle list, merging vertices that are exactly equal.
+    pub fn from_triangle_list(triangles: &[V]) -> Self {
+        let mut known = HashMap::new();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::with_capacity(triangles.len());
+        for v in triangles {
+            let idx = *known.entry(v.vertex_key()).or_insert_with(|| {
+                vertices.push(v.clone());
+                (vertices.len() - 1) as u32
+            });
+            indices.push(idx);
         }
+        IndexedMesh { vertices, indices }
     }
-
-    pub fn auto_load_from_extension(&mut self,path: &str,res: Resources){
-        let ext = AssetLoader::extension_from_path(path);
-        match ext{
-            "obj" => Some(self.load_from_extension::<Mesh>(ext,res.fetch_mut::<AssetLoaderInternal<Mesh>>(),res.fetch_mut::<AssetStorage<Mesh>>(),res.fetch())),
-            _ => None,
-        };
-    }*/
-
-    /*pub fn extension_from_path(path: &str) -> &str{
-        path.split(".").as_slice().last().clone()
-    }*/
-}
-
-impl Component for AssetLoader {
-    type Storage = VecStorage<Self>;
 }
 
-pub struct AssetLoaderInternal<T> {
-    /// Map path to asset handle.
-    pub assets: HashMap<String, Handle<T>>,
+impl From<IndexedMesh<PosTex>> for MeshData {
+    fn from(mesh: IndexedMesh<PosTex>) -> Self {
+        mesh.to_triangle_list().into()
+    }
 }
 
-impl<T> Default for AssetLoaderInternal<T> {
-    fn default() -> Self {
-        Self::new()
+impl From<IndexedMesh<PosNormTex>> for MeshData {
+    fn from(mesh: IndexedMesh<PosNormTex>) -> Self {
+        mesh.to_triangle_list().into()
     }
 }
 
-impl<T> AssetLoaderInternal<T> {
-    pub fn new() -> Self {
-        AssetLoaderInternal {
-            assets: HashMap::new(),
-        }
+impl From<IndexedMesh<PosNormTangTex>> for MeshData {
+    fn from(mesh: IndexedMesh<PosNormTangTex>) -> Self {
+        mesh.to_triangle_list().into()
     }
 }
 
-impl<T> Component for AssetLoaderInternal<T>
+/// Loads an indexed mesh into the mesh storage.
+pub fn load_indexed_mesh<V>(
+    mesh: IndexedMesh<V>,
+    loader: &Loader,
+    storage: &AssetStorage<Mesh>,
+) -> Handle<Mesh>
 where
-    T: Send + Sync + 'static,
+    IndexedMesh<V>: Into<MeshData>,
 {
-    type Storage = VecStorage<Self>;
+    loader.load_from_data(mesh.into(), (), &storage)
 }
 
-#[cfg(test)]
-mod test {
-    use *;
-
-    fn load_asset_loader() -> AssetLoader {
-        AssetLoader::new(
-            &format!("{}/test/assets", env!("CARGO_MANIFEST_DIR")),
-            "main",
-        )
-    }
+/// The way texture coordinates are assigned to the vertices of generated shapes.
+/// Texture coordinates go from (0,0) at the bottom left to (1,1) at the top right.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UvMapping {
+    /// Stretches the whole texture over the bounding box of the shape.
+    PlanarFit,
+    /// Repeats the texture `repeat` times per world unit, starting from the bottom left corner
+    /// of the bounding box. The texture needs a `WrapMode::Tile` sampler for this to look right.
+    Tiled { repeat: f32 },
+    /// Stretches a sub-rectangle of the texture over the bounding box of the shape.
+    /// `min` and `max` are texture coordinates in [0,1]. Useful with sprite sheets.
+    Atlas { min: [f32; 2], max: [f32; 2] },
+}
 
-    #[test]
-    fn path_sanitisation() {
-        AssetLoader::new(
-            &format!("{}/test/assets/", env!("CARGO_MANIFEST_DIR")),
-            "/base/",
-        );
+impl Default for UvMapping {
+    fn default() -> Self {
+        UvMapping::PlanarFit
     }
+}
 
-    #[test]
-    fn asset_loader_resolve_unique_other() {
-        let asset_loader = load_asset_loader();
-        assert_eq!(asset_loader.resolve_path("config/uniqueother"),Some(format!("{}/test/assets/mod1/config/uniqueother",env!("CARGO_MANIFEST_DIR")).to_string()))
+impl UvMapping {
+    /// Computes the texture coordinates of `point`, given the bounding box of the shape.
+    pub fn map(&self, point: [f32; 2], min: [f32; 2], max: [f32; 2]) -> [f32; 2] {
+        let fit = |axis: usize| {
+            let size = max[axis] - min[axis];
+            if size == 0.0 {
+                0.0
+            } else {
+                (point[axis] - min[axis]) / size
+            }
+        };
+        match *self {
+            UvMapping::PlanarFit => [fit(0), fit(1)],
+            UvMapping::Tiled { repeat } => {
+                [(point[0] - min[0]) * repeat, (point[1] - min[1]) * repeat]
+            }
+            UvMapping::Atlas {
+                min: uv_min,
+                max: uv_max,
+            } => [
+                uv_min[0] + (uv_max[0] - uv_min[0]) * fit(0),
+                uv_min[1] + (uv_max[1] - uv_min[1]) * fit(1),
+            ],
+        }
     }
+}
 
-    #[test]
-    fn asset_loader_resolve_path_override_single() {
-        let asset_loader = load_asset_loader();
-        assert_eq!(asset_loader.resolve_path("config/ov1"),Some(format!("{}/test/assets/mod1/config/ov1",env!("CARGO_MANIFEST_DIR")).to_string()))
+/// Overwrites the texture coordinates of the vertices using their x and y positions.
+/// The bounding box is computed from the vertices.
+pub fn apply_uv_mapping(vertices: &mut [PosTex], mapping: UvMapping) {
+    if vertices.is_empty() {
+        return;
     }
-
-    #[test]
-    fn asset_loader_resolve_path_override_all() {
-        let asset_loader = load_asset_loader();
-        assert_eq!(asset_loader.resolve_path("config/ovall"),Some(format!("{}/test/assets/mod2/config/ovall",env!("CARGO_MANIFEST_DIR")).to_string()))
+    let mut min = [vertices[0].position[0], vertices[0].position[1]];
+    let mut max = min;
+    for v in vertices.iter() {
+        for axis in 0..2 {
+            min[axis] = min[axis].min(v.position[axis]);
+            max[axis] = max[axis].max(v.position[axis]);
+        }
     }
-
-    #[test]
-    pub fn crossterm() {
-        let terminal = CROSSTERM.terminal();
-        let cursor = CROSSTERM.cursor();
-        //cursor.hide();
-
-        let mut input = CROSSTERM.input().read_async().bytes();
-
-        let input_buf = Arc::new(Mutex::new(String::new()));
-        let key_buf = [0 as u8; 32];
-
-        start_logger(input_buf.clone());
-
-        spawn(|| loop {
-            info!("More random stuff");
-            sleep(Duration::from_millis(52));
-        });
-
-        loop {
-            let (_, _) = terminal.terminal_size();
-            info!("random stuff");
-            while let Some(Ok(b)) = input.next() {
-                info!("{:?} <- Char entered!", b);
-                if b == 3 {
-                    // Ctrl+C = exit
-                    terminal.exit();
-                    return;
-                } else if b == b'\n' || b == 13 {
-                    //info!(">{}", input_buf.lock().unwrap());
-                    let mut buffer = input_buf.lock().unwrap();
-                    buffer.clear();
-                    refresh_input_line(&terminal, &cursor, &buffer);
-                //let input = CROSSTERM.input().read_async().bytes();
-                } else if b == 127 || b == 8 {
-                    // Delete || Backspace
-                    let mut buffer = input_buf.lock().unwrap();
-                    buffer.pop();
-                    refresh_input_line(&terminal, &cursor, &buffer);
-                } else {
-                    let mut buffer = input_buf.lock().unwrap();
-                    buffer.push(b as char);
-                    refresh_input_line(&terminal, &cursor, &buffer);
-                }
-            }
-            sleep(Duration::from_millis(100));
-        }
-    }
-
-    pub fn swap_write(terminal: &Terminal, cursor: &TerminalCursor, msg: &str, input_buf: &String) {
-        let (_, term_height) = terminal.terminal_size();
-        cursor.goto(0, term_height);
-        terminal.clear(ClearType::CurrentLine);
-        terminal.write(format!("{}\r\n>{}", msg, input_buf));
-        //terminal.write(format!(">{}", input_buf));
-    }
-
-    pub fn refresh_input_line(terminal: &Terminal, cursor: &TerminalCursor, input_buf: &String) {
-        let (_, term_height) = terminal.terminal_size();
-        cursor.goto(0, term_height);
-        terminal.clear(ClearType::CurrentLine);
-        terminal.write(format!(">{}", input_buf));
-    }
-
-    pub fn start_logger(input_buf: Arc<Mutex<String>>) {
-        let color_config = fern::colors::ColoredLevelConfig::new();
-        let terminal = CROSSTERM.terminal();
-        let cursor = CROSSTERM.cursor();
-
-        fern::Dispatch::new()
-            .format(move |out, message, record| {
-                out.finish(format_args!(
-                    "{color}[{level}][{target}] {message}{color_reset}",
-                    color = format!(
-                        "\x1B[{}m",
-                        color_config.get_color(&record.level()).to_fg_str()
-                    ),
-                    level = record.level(),
-                    target = record.target(),
-                    message = message,
-                    color_reset = "\x1B[0m",
-                ))
-            }).level(log::LevelFilter::Debug)
-            .chain(fern::Output::call(move |record| {
-                //let color = color_config.get_color(&record.level()).to_fg_str();
-                //println!("\x1B[{}m[{}][{}] {}\x1B[0m",color,record.level(),record.target(),record.args());
-                //println!("{}",record.args());
-                //RawScreen::into_raw_mode().unwrap();
-                swap_write(
-                    &terminal,
-                    &cursor,
-                    &format!("{}", record.args()),
-                    &input_buf.lock().unwrap(),
-                );
-            })).apply()
-            .unwrap_or_else(|_| {
-                error!("Global logger already set, amethyst-extra logger not used!")
-            });
+    apply_uv_mapping_in(vertices, mapping, min, max);
+}
+
+/// Overwrites the texture coordinates of the vertices using their x and y positions,
+/// relative to the given bounding box.
+pub fn apply_uv_mapping_in(
+    vertices: &mut [PosTex],
+    mapping: UvMapping,
+    min: [f32; 2],
+    max: [f32; 2],
+) {
+    for v in vertices.iter_mut() {
+        v.tex_coord = mapping.map([v.position[0], v.position[1]], min, max);
     }
 }
 
-/*pub trait AssetToFormat<T> where T: Sized{
-    fn get_format() -> Format<T>;
-}
-
-impl AssetToFormat<Mesh> for Mesh{
-    fn get_format() -> Format<Mesh>{
-        ObjFormat
+impl IndexedMesh<PosTex> {
+    /// Replaces the texture coordinates of the mesh using the given mapping.
+    pub fn with_uv_mapping(mut self, mapping: UvMapping) -> Self {
+        apply_uv_mapping(&mut self.vertices, mapping);
+        self
     }
-}*/
+}
 
 /// Generates a rectangle 2d mesh.
 pub fn gen_rectangle_mesh(
@@ -487,302 +352,2043 @@ pub fn gen_rectangle_mesh(
     loader: &Loader,
     storage: &AssetStorage<Mesh>,
 ) -> Handle<Mesh> {
-    let verts = gen_rectangle_vertices(w, h);
+    gen_rectangle_mesh_with_uv(w, h, UvMapping::PlanarFit, loader, storage)
+}
+
+/// Generates a rectangle 2d mesh using the given uv mapping.
+pub fn gen_rectangle_mesh_with_uv(
+    w: f32,
+    h: f32,
+    uv: UvMapping,
+    loader: &Loader,
+    storage: &AssetStorage<Mesh>,
+) -> Handle<Mesh> {
+    let verts = gen_rectangle_vertices_with_uv(w, h, uv);
     loader.load_from_data(verts.into(), (), &storage)
 }
 
 /// Generate the vertices of a rectangle.
 pub fn gen_rectangle_vertices(w: f32, h: f32) -> Vec<PosTex> {
-    let data: Vec<PosTex> = vec![
-        PosTex {
-            position: [-w / 2., -h / 2., 0.],
-            tex_coord: [0., 0.],
-        },
-        PosTex {
-            position: [w / 2., -h / 2., 0.],
-            tex_coord: [1., 0.],
-        },
-        PosTex {
-            position: [w / 2., h / 2., 0.],
-            tex_coord: [1., 1.],
-        },
-        PosTex {
-            position: [w / 2., h / 2., 0.],
-            tex_coord: [1., 1.],
-        },
-        PosTex {
-            position: [-w / 2., h / 2., 0.],
-            tex_coord: [0., 1.],
-        },
-        PosTex {
-            position: [-w / 2., -h / 2., 0.],
-            tex_coord: [0., 0.],
-        },
-    ];
-    data
+    gen_rectangle_vertices_with_uv(w, h, UvMapping::PlanarFit)
+}
+
+/// Generate the vertices of a rectangle using the given uv mapping.
+pub fn gen_rectangle_vertices_with_uv(w: f32, h: f32, uv: UvMapping) -> Vec<PosTex> {
+    gen_rectangle_indexed(w, h, uv).to_triangle_list()
+}
+
+/// Generate a rectangle as 4 vertices and 6 indices.
+pub fn gen_rectangle_indexed(w: f32, h: f32, uv: UvMapping) -> IndexedMesh<PosTex> {
+    let mut vertices: Vec<PosTex> = [
+        [-w / 2., -h / 2.],
+        [w / 2., -h / 2.],
+        [w / 2., h / 2.],
+        [-w / 2., h / 2.],
+    ]
+    .iter()
+    .map(|p| PosTex {
+        position: [p[0], p[1], 0.],
+        tex_coord: [0., 0.],
+    })
+    .collect();
+    apply_uv_mapping_in(&mut vertices, uv, [-w / 2., -h / 2.], [w / 2., h / 2.]);
+    IndexedMesh::new(vertices, vec![0, 1, 2, 2, 3, 0])
 }
 
 /// Generates vertices for a circle. The circle will be made of `resolution`
 /// triangles.
 pub fn generate_circle_vertices(radius: f32, resolution: usize) -> Vec<PosTex> {
+    generate_circle_vertices_with_uv(radius, resolution, UvMapping::PlanarFit)
+}
+
+/// Generates vertices for a circle using the given uv mapping.
+pub fn generate_circle_vertices_with_uv(
+    radius: f32,
+    resolution: usize,
+    uv: UvMapping,
+) -> Vec<PosTex> {
+    generate_circle_indexed(radius, resolution, uv).to_triangle_list()
+}
+
+/// Generates a circle made of `resolution` triangles sharing a single center vertex.
+/// The result has `resolution + 1` vertices.
+/// The uv mapping uses the square enclosing the circle as bounding box.
+pub fn generate_circle_indexed(
+    radius: f32,
+    resolution: usize,
+    uv: UvMapping,
+) -> IndexedMesh<PosTex> {
     use std::f32::consts::PI;
 
-    let mut vertices = Vec::with_capacity(resolution * 3);
+    let mut vertices = Vec::with_capacity(resolution + 1);
+    let mut indices = Vec::with_capacity(resolution * 3);
     let angle_offset = 2.0 * PI / resolution as f32;
-    // Helper function to generate the vertex at the specified angle.
-    let generate_vertex = |angle: f32| {
-        let x = angle.cos();
-        let y = angle.sin();
-        PosTex {
-            position: [x * radius, y * radius, 0.0],
-            tex_coord: [x, y],
-        }
-    };
+
+    vertices.push(PosTex {
+        position: [0.0, 0.0, 0.0],
+        tex_coord: [0.0, 0.0],
+    });
 
     for index in 0..resolution {
+        let angle = angle_offset * index as f32;
         vertices.push(PosTex {
-            position: [0.0, 0.0, 0.0],
+            position: [angle.cos() * radius, angle.sin() * radius, 0.0],
             tex_coord: [0.0, 0.0],
         });
 
-        vertices.push(generate_vertex(angle_offset * index as f32));
-        vertices.push(generate_vertex(angle_offset * (index + 1) as f32));
+        indices.push(0);
+        indices.push(index as u32 + 1);
+        indices.push(((index + 1) % resolution) as u32 + 1);
     }
 
-    vertices
+    apply_uv_mapping_in(&mut vertices, uv, [-radius, -radius], [radius, radius]);
+    IndexedMesh::new(vertices, indices)
 }
 
-pub fn material_from_color(
-    color: [f32; 4],
-    loader: &Loader,
-    storage: &AssetStorage<Texture>,
-    material_defaults: &MaterialDefaults,
-) -> Material {
-    let albedo = loader.load_from_data(color.into(), (), &storage);
-    material_from_texture(albedo, material_defaults)
-}
+/// Tolerance used when comparing generated 2d geometry.
+const SHAPE_EPSILON: f32 = 1e-5;
 
-pub fn material_from_texture(texture: Handle<Texture>, defaults: &MaterialDefaults) -> Material {
-    Material {
-        albedo: texture,
-        ..defaults.0.clone()
-    }
+/// Cross product of the vectors `o -> a` and `o -> b`.
+/// Positive when `o`, `a`, `b` turn counter-clockwise.
+fn cross2(o: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
 }
 
-pub fn value_near<B: Add<Output = B> + Sub<Output = B> + PartialOrd + Copy>(
-    number: B,
-    target: B,
-    margin: B,
-) -> bool {
-    number >= target - margin && number <= target + margin
+/// Signed area of a contour. Positive when counter-clockwise.
+pub fn contour_area(contour: &[[f32; 2]]) -> f32 {
+    let n = contour.len();
+    let mut sum = 0.0;
+    for i in 0..n {
+        let a = contour[i];
+        let b = contour[(i + 1) % n];
+        sum += a[0] * b[1] - b[0] * a[1];
+    }
+    sum / 2.0
 }
 
-pub fn material_from_png(
-    path: &str,
-    loader: &Loader,
-    storage: &AssetStorage<Texture>,
-    material_defaults: &MaterialDefaults,
-) -> Material {
-    material_from_texture(
-        loader.load(path, PngFormat, TextureMetadata::default(), (), &storage),
-        material_defaults,
-    )
+/// Removes repeated and collinear points, then makes the contour counter-clockwise
+/// (`ccw == true`) or clockwise.
+fn oriented_contour(contour: Vec<[f32; 2]>, ccw: bool) -> Vec<[f32; 2]> {
+    let mut out: Vec<[f32; 2]> = Vec::with_capacity(contour.len());
+    for p in contour {
+        if out.last().map_or(true, |l| !points_near(*l, p)) {
+            out.push(p);
+        }
+    }
+    while out.len() > 1 && points_near(out[0], out[out.len() - 1]) {
+        out.pop();
+    }
+    let mut i = 0;
+    while out.len() >= 3 && i < out.len() {
+        let n = out.len();
+        let prev = out[(i + n - 1) % n];
+        let next = out[(i + 1) % n];
+        if cross2(prev, out[i], next).abs() <= SHAPE_EPSILON * SHAPE_EPSILON {
+            out.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+    if (contour_area(&out) > 0.0) != ccw {
+        out.reverse();
+    }
+    out
 }
 
-/// Doesn't work if you run `cargo run` while you are not in the root directory
-pub fn get_working_dir() -> String {
-    let mut base_path = String::from(
-        std::env::current_exe()
-            .expect("Failed to find executable path.")
-            .parent()
-            .expect("Failed to get parent directory of the executable.")
-            .to_str()
-            .unwrap(),
-    );
-    if base_path.contains("target/") || base_path.contains("target\\") {
-        base_path = String::from(".");
-    }
-    base_path
+fn points_near(a: [f32; 2], b: [f32; 2]) -> bool {
+    (a[0] - b[0]).abs() <= SHAPE_EPSILON && (a[1] - b[1]).abs() <= SHAPE_EPSILON
 }
 
-pub struct Music {
-    pub music: Cycle<IntoIter<SourceHandle>>,
+/// Inclusive point in triangle test working for both windings.
+fn point_in_triangle(p: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    let d1 = cross2(a, b, p);
+    let d2 = cross2(b, c, p);
+    let d3 = cross2(c, a, p);
+    let neg = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let pos = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(neg && pos)
 }
 
-// TODO: Broken af dependency of TransformBundle pls fix asap lmao
-pub fn amethyst_gamedata_base_2d(base: &str) -> Result<GameDataBuilder<'static, 'static>> {
-    amethyst::start_logger(Default::default());
+/// Even-odd point in polygon test over a set of contours.
+fn point_in_contours(p: [f32; 2], contours: &[Vec<[f32; 2]>]) -> bool {
+    let mut inside = false;
+    for contour in contours {
+        let n = contour.len();
+        for i in 0..n {
+            let a = contour[i];
+            let b = contour[(i + 1) % n];
+            if (a[1] > p[1]) != (b[1] > p[1])
+                && p[0] < a[0] + (p[1] - a[1]) * (b[0] - a[0]) / (b[1] - a[1])
+            {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// If `p` lies on an edge of the contours, returns the direction of that edge.
+fn boundary_direction(p: [f32; 2], contours: &[Vec<[f32; 2]>]) -> Option<[f32; 2]> {
+    for contour in contours {
+        let n = contour.len();
+        for i in 0..n {
+            let a = contour[i];
+            let b = contour[(i + 1) % n];
+            let d = [b[0] - a[0], b[1] - a[1]];
+            let len2 = d[0] * d[0] + d[1] * d[1];
+            if len2 == 0.0 {
+                continue;
+            }
+            let t = (((p[0] - a[0]) * d[0] + (p[1] - a[1]) * d[1]) / len2)
+                .max(0.0)
+                .min(1.0);
+            let proj = [a[0] + d[0] * t, a[1] + d[1] * t];
+            if points_near(proj, p) {
+                return Some(d);
+            }
+        }
+    }
+    None
+}
+
+/// A 2d polygon made of an outer contour and optional holes.
+/// The outer contour is stored counter-clockwise and the holes clockwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shape2D {
+    pub outer: Vec<[f32; 2]>,
+    pub holes: Vec<Vec<[f32; 2]>>,
+}
+
+impl Shape2D {
+    /// Creates a shape, fixing the winding of the contours and removing repeated or collinear points.
+    /// Holes must be inside the outer contour and must not overlap each other.
+    pub fn new(outer: Vec<[f32; 2]>, holes: Vec<Vec<[f32; 2]>>) -> Self {
+        Shape2D {
+            outer: oriented_contour(outer, true),
+            holes: holes
+                .into_iter()
+                .map(|h| oriented_contour(h, false))
+                .filter(|h| h.len() >= 3)
+                .collect(),
+        }
+    }
 
-    let display_config_path = format!("{}/assets/base/config/display.ron", base);
+    /// The area covered by the shape, holes excluded.
+    pub fn area(&self) -> f32 {
+        contour_area(&self.outer) + self.holes.iter().map(|h| contour_area(h)).sum::<f32>()
+    }
 
-    let key_bindings_path = format!("{}/assets/base/config/input.ron", base);
+    /// All the points of the shape: the outer contour followed by each hole.
+    /// This is what the indices returned by `triangulate` refer to.
+    pub fn points(&self) -> Vec<[f32; 2]> {
+        let mut points = self.outer.clone();
+        for hole in &self.holes {
+            points.extend_from_slice(hole);
+        }
+        points
+    }
 
-    GameDataBuilder::default()
-        //.with(PrefabLoaderSystem::<MyPrefabData>::default(), "", &[])
-        .with_bundle(TransformBundle::new())?
-        .with_bundle(
-            InputBundle::<String, String>::new().with_bindings_from_file(&key_bindings_path)?,
-        )?.with_bundle(UiBundle::<String, String>::new())?
-        .with_bundle(AnimationBundle::<u32, Material>::new(
-            "animation_control_system",
-            "sampler_interpolation_system",
-        ))?.with_bundle(AudioBundle::new(|music: &mut Music| music.music.next()))?
-        .with(TimedDestroySystem, "timed_destroy", &[])
-        .with_basic_renderer(display_config_path, DrawFlat::<PosTex>::new(), false)
-}
+    /// Triangulates the shape by ear clipping.
+    /// Holes are first connected to the outer contour by bridges, which turns the shape
+    /// into a single contour.
+    /// Returns counter-clockwise triangles indexing into `points()`.
+    pub fn triangulate(&self) -> Vec<u32> {
+        let mut points = self.outer.clone();
+        let mut ring: Vec<usize> = (0..points.len()).collect();
+
+        let mut holes: Vec<(usize, &Vec<[f32; 2]>)> = Vec::with_capacity(self.holes.len());
+        for hole in &self.holes {
+            holes.push((points.len(), hole));
+            points.extend_from_slice(hole);
+        }
+        // Bridge the rightmost holes first so that later bridges can't cross them.
+        let max_x = |c: &Vec<[f32; 2]>| c.iter().fold(::std::f32::MIN, |m, p| m.max(p[0]));
+        holes.sort_by(|a, b| {
+            max_x(b.1)
+                .partial_cmp(&max_x(a.1))
+                .unwrap_or(::std::cmp::Ordering::Equal)
+        });
 
-/*pub fn build_amethyst(game_data_builder: GameDataBuilder<'static,'static>, init_state: State<GameData<'static,'static>>) -> Result<Application<GameData<'static,'static>>>{
-    let resources_directory = format!("{}/assets/base", env!("CARGO_MANIFEST_DIR"));
-    let game_data = game_data_builder.build()?;
-    Application::build(resources_directory, init_state)?.build(game_data)
-}*/
+        for (offset, hole) in holes {
+            let m = (0..hole.len())
+                .max_by(|a, b| {
+                    hole[*a][0]
+                        .partial_cmp(&hole[*b][0])
+                        .unwrap_or(::std::cmp::Ordering::Equal)
+                }).unwrap();
+            if let Some(pos) = find_bridge(&points, &ring, hole[m]) {
+                let mut bridged = ring[..pos + 1].to_vec();
+                for k in 0..hole.len() + 1 {
+                    bridged.push(offset + (m + k) % hole.len());
+                }
+                bridged.extend_from_slice(&ring[pos..]);
+                ring = bridged;
+            } else {
+                warn!("Failed to connect a hole to the outer contour of a shape, ignoring it.");
+            }
+        }
 
-/// If the tracked resource changes, this will be checked to make sure it is a proper time to save.
-pub trait ShouldSave {
-    fn save_ready(&self) -> bool;
-    fn set_save_ready(&mut self, ready: bool);
+        ear_clip(&points, ring)
+    }
 }
 
-/// System used to automatically save a Resource T to a file.
-/// On load, it will attempt to load it from the file and if it fails, it will use T::default().
-pub struct AutoSaveSystem<T> {
-    /// Absolute path.
-    save_path: String,
-    _phantom_data: PhantomData<T>,
-}
+/// Finds the vertex of the ring that is visible from the hole vertex `m`, looking towards +x.
+/// Returns its position in the ring.
+fn find_bridge(points: &[[f32; 2]], ring: &[usize], m: [f32; 2]) -> Option<usize> {
+    let n = ring.len();
+    let mut best_x = ::std::f32::MAX;
+    let mut best = None;
+    for i in 0..n {
+        let a = points[ring[i]];
+        let b = points[ring[(i + 1) % n]];
+        if (a[1] > m[1]) == (b[1] > m[1]) {
+            continue;
+        }
+        let x = a[0] + (m[1] - a[1]) * (b[0] - a[0]) / (b[1] - a[1]);
+        if x >= m[0] && x < best_x {
+            best_x = x;
+            best = Some(if a[1] == m[1] {
+                i
+            } else if b[1] == m[1] || b[0] > a[0] {
+                (i + 1) % n
+            } else {
+                i
+            });
+        }
+    }
+    let mut best = best?;
+    let hit = [best_x, m[1]];
+    let p = points[ring[best]];
+    if points_near(p, hit) {
+        return Some(best);
+    }
 
-impl<T> AutoSaveSystem<T> {
-    /// Save path is an absolute path.
-    pub fn new(save_path: String) -> Self {
-        AutoSaveSystem {
-            save_path,
-            _phantom_data: PhantomData,
+    // Reflex vertices inside the triangle (m, hit, p) could hide p.
+    // Pick the one with the smallest angle to the ray instead.
+    let mut best_tan = ((p[1] - m[1]) / (p[0] - m[0])).abs();
+    for j in 0..n {
+        let v = points[ring[j]];
+        let prev = points[ring[(j + n - 1) % n]];
+        let next = points[ring[(j + 1) % n]];
+        if v[0] <= m[0] || cross2(prev, v, next) > 0.0 || !point_in_triangle(v, m, hit, p) {
+            continue;
+        }
+        let tan = ((v[1] - m[1]) / (v[0] - m[0])).abs();
+        if tan < best_tan || (tan == best_tan && v[0] < points[ring[best]][0]) {
+            best_tan = tan;
+            best = j;
+        }
+    }
+    Some(best)
+}
+
+/// Triangulates a counter-clockwise contour given as indices into `points`.
+fn ear_clip(points: &[[f32; 2]], mut ring: Vec<usize>) -> Vec<u32> {
+    let mut triangles = Vec::with_capacity(ring.len().saturating_sub(2) * 3);
+    let mut i = 0;
+    let mut misses = 0;
+    while ring.len() > 3 {
+        let n = ring.len();
+        i %= n;
+        let (a, b, c) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+        if is_ear(points, &ring, a, b, c) {
+            triangles.extend_from_slice(&[a as u32, b as u32, c as u32]);
+            ring.remove(i);
+            misses = 0;
+        } else {
+            i += 1;
+            misses += 1;
+            if misses >= n {
+                // Degenerate input (self intersection, overlapping holes...).
+                // Clip the flattest vertex to keep going instead of looping forever.
+                warn!("Failed to find an ear while triangulating a shape. It might be self-intersecting.");
+                let flatness = |j: usize| {
+                    cross2(
+                        points[ring[(j + n - 1) % n]],
+                        points[ring[j]],
+                        points[ring[(j + 1) % n]],
+                    ).abs()
+                };
+                let flattest = (0..n)
+                    .min_by(|x, y| {
+                        flatness(*x)
+                            .partial_cmp(&flatness(*y))
+                            .unwrap_or(::std::cmp::Ordering::Equal)
+                    }).unwrap();
+                ring.remove(flattest);
+                misses = 0;
+            }
         }
     }
+    if ring.len() == 3 && cross2(points[ring[0]], points[ring[1]], points[ring[2]]) > 0.0 {
+        triangles.extend_from_slice(&[ring[0] as u32, ring[1] as u32, ring[2] as u32]);
+    }
+    triangles
 }
 
-impl<'a, T> System<'a> for AutoSaveSystem<T>
-where
-    T: Serialize + DeserializeOwned + Default + ShouldSave + Send + Sync + 'static,
-{
-    type SystemData = (Write<'a, Dirty<T>>,);
-    fn setup(&mut self, res: &mut amethyst::ecs::Resources) {
-        // attempt loading
-        if let Ok(mut f) = File::open(&self.save_path) {
-            let mut c = String::new();
-            if let Ok(_) = f.read_to_string(&mut c) {
-                if let Ok(o) = ron::de::from_str::<T>(&c) {
-                    res.insert(Dirty::new(o));
-                } else {
-                    error!(
-                        "Failed to deserialize save file: {}.\nThe file might be corrupted.",
-                        self.save_path
-                    );
+fn is_ear(points: &[[f32; 2]], ring: &[usize], a: usize, b: usize, c: usize) -> bool {
+    let (pa, pb, pc) = (points[a], points[b], points[c]);
+    if cross2(pa, pb, pc) <= 0.0 {
+        return false;
+    }
+    !ring.iter().any(|&v| {
+        let p = points[v];
+        !points_near(p, pa)
+            && !points_near(p, pb)
+            && !points_near(p, pc)
+            && point_in_triangle(p, pa, pb, pc)
+    })
+}
+
+/// A directed edge between two points.
+type Segment = ([f32; 2], [f32; 2]);
+
+#[derive(Clone, Copy, PartialEq)]
+enum BooleanOp {
+    Union,
+    Difference,
+}
+
+/// Splits the edges of `a` and `b` wherever they cross or touch each other.
+/// Intersection points are shared by both sides so the resulting segments can be chained exactly.
+fn split_contours(a: &[Vec<[f32; 2]>], b: &[Vec<[f32; 2]>]) -> (Vec<Segment>, Vec<Segment>) {
+    fn edges(contours: &[Vec<[f32; 2]>]) -> Vec<Segment> {
+        contours
+            .iter()
+            .flat_map(|c| (0..c.len()).map(move |i| (c[i], c[(i + 1) % c.len()])))
+            .collect()
+    }
+    fn param(p: [f32; 2], from: [f32; 2], to: [f32; 2]) -> f32 {
+        let d = [to[0] - from[0], to[1] - from[1]];
+        ((p[0] - from[0]) * d[0] + (p[1] - from[1]) * d[1]) / (d[0] * d[0] + d[1] * d[1])
+    }
+    let edges_a = edges(a);
+    let edges_b = edges(b);
+    let mut cuts_a: Vec<Vec<(f32, [f32; 2])>> = vec![Vec::new(); edges_a.len()];
+    let mut cuts_b: Vec<Vec<(f32, [f32; 2])>> = vec![Vec::new(); edges_b.len()];
+
+    for (ia, &(p, q)) in edges_a.iter().enumerate() {
+        for (ib, &(r, s)) in edges_b.iter().enumerate() {
+            let d1 = [q[0] - p[0], q[1] - p[1]];
+            let d2 = [s[0] - r[0], s[1] - r[1]];
+            let denom = d1[0] * d2[1] - d1[1] * d2[0];
+            if denom.abs() <= SHAPE_EPSILON * SHAPE_EPSILON {
+                // Parallel. If collinear, each edge is cut where the other one's endpoints lie on it.
+                for &end in &[r, s] {
+                    if boundary_direction(end, &[vec![p, q]]).is_some() {
+                        cuts_a[ia].push((param(end, p, q), end));
+                    }
                 }
-            } else {
-                error!("Failed to read content of save file: {}", self.save_path);
+                for &end in &[p, q] {
+                    if boundary_direction(end, &[vec![r, s]]).is_some() {
+                        cuts_b[ib].push((param(end, r, s), end));
+                    }
+                }
+                continue;
             }
-        } else {
-            warn!(
-                "Failed to load save file: {}. It will be created during the next save.",
-                self.save_path
-            );
+            let t = ((r[0] - p[0]) * d2[1] - (r[1] - p[1]) * d2[0]) / denom;
+            let u = ((r[0] - p[0]) * d1[1] - (r[1] - p[1]) * d1[0]) / denom;
+            let in_range = |v: f32| v >= -SHAPE_EPSILON && v <= 1.0 + SHAPE_EPSILON;
+            if !in_range(t) || !in_range(u) {
+                continue;
+            }
+            // Reuse existing vertices when the crossing is on one of them.
+            let point = [p, q, r, s]
+                .iter()
+                .cloned()
+                .find(|v| points_near(*v, [p[0] + d1[0] * t, p[1] + d1[1] * t]))
+                .unwrap_or([p[0] + d1[0] * t, p[1] + d1[1] * t]);
+            cuts_a[ia].push((param(point, p, q), point));
+            cuts_b[ib].push((param(point, r, s), point));
         }
-        Self::SystemData::setup(res);
-    }
-    fn run(&mut self, (mut d,): Self::SystemData) {
-        if d.dirty() {
-            d.clear();
-            let v = d.read();
-            let s = ron::ser::to_string(&v).expect(&format!(
-                "Unable to serialize the save struct for: {}",
-                self.save_path
-            ));
-            let mut f = File::create(&self.save_path);
-            if f.is_ok() {
-                let file = f.as_mut().ok().unwrap();
-                let res = file.write_all(s.as_bytes());
-                if res.is_err() {
-                    error!(
-                        "Failed to write serialized save data to the file. Error: {:?}",
-                        res.err().unwrap()
-                    );
+    }
+
+    fn segments(edges: &[Segment], cuts: Vec<Vec<(f32, [f32; 2])>>) -> Vec<Segment> {
+        let mut out = Vec::new();
+        for (&(from, to), mut cuts) in edges.iter().zip(cuts.into_iter()) {
+            cuts.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(::std::cmp::Ordering::Equal));
+            let mut last = from;
+            for (_, point) in cuts.into_iter().chain(::std::iter::once((1.0, to))) {
+                if !points_near(last, point) {
+                    out.push((last, point));
+                    last = point;
                 }
-            } else {
-                error!(
-                    "Failed to create or load the save file \"{}\". Error: {:?}",
-                    &self.save_path,
-                    &f.err().unwrap()
-                );
             }
         }
+        out
     }
-}
 
-pub struct DestroyAtTime {
-    pub time: f64,
+    (segments(&edges_a, cuts_a), segments(&edges_b, cuts_b))
 }
 
-impl Component for DestroyAtTime {
-    type Storage = VecStorage<Self>;
-}
-
-pub struct DestroyInTime {
-    pub timer: f64,
-}
+/// Applies a boolean operation between a set of non overlapping shapes and a simple contour.
+/// Edges of both sides are cut at their intersections, kept or dropped depending on which side
+/// of the other region they lie, then chained back into contours.
+fn shape_boolean(shapes: &[Shape2D], contour: Vec<[f32; 2]>, op: BooleanOp) -> Vec<Shape2D> {
+    let a: Vec<Vec<[f32; 2]>> = shapes
+        .iter()
+        .flat_map(|s| ::std::iter::once(s.outer.clone()).chain(s.holes.iter().cloned()))
+        .collect();
+    let b = vec![oriented_contour(contour, true)];
+    if b[0].len() < 3 {
+        return shapes.to_vec();
+    }
+    let (split_a, split_b) = split_contours(&a, &b);
+    let mid = |from: [f32; 2], to: [f32; 2]| [(from[0] + to[0]) / 2.0, (from[1] + to[1]) / 2.0];
+
+    let mut kept = Vec::new();
+    for (from, to) in split_a {
+        let m = mid(from, to);
+        let keep = match boundary_direction(m, &b) {
+            Some(dir) => {
+                // Shared edge. It stays once if the regions are on the same side for a union,
+                // or on opposite sides for a difference.
+                let same = dir[0] * (to[0] - from[0]) + dir[1] * (to[1] - from[1]) > 0.0;
+                same == (op == BooleanOp::Union)
+            }
+            None => !point_in_contours(m, &b),
+        };
+        if keep {
+            kept.push((from, to));
+        }
+    }
+    for (from, to) in split_b {
+        let m = mid(from, to);
+        if boundary_direction(m, &a).is_some() {
+            continue;
+        }
+        let inside = point_in_contours(m, &a);
+        match op {
+            BooleanOp::Union if !inside => kept.push((from, to)),
+            BooleanOp::Difference if inside => kept.push((to, from)),
+            _ => {}
+        }
+    }
+
+    shapes_from_contours(chain_segments(kept))
+}
+
+/// Joins directed segments into closed contours.
+/// When several segments leave the same point, the leftmost turn is taken so touching
+/// contours stay separate.
+fn chain_segments(segments: Vec<Segment>) -> Vec<Vec<[f32; 2]>> {
+    let key = |p: [f32; 2]| {
+        (
+            (p[0] / (SHAPE_EPSILON * 10.0)).round() as i64,
+            (p[1] / (SHAPE_EPSILON * 10.0)).round() as i64,
+        )
+    };
+    let mut outgoing: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, s) in segments.iter().enumerate() {
+        outgoing.entry(key(s.0)).or_insert_with(Vec::new).push(i);
+    }
+    let mut used = vec![false; segments.len()];
+    let mut contours = Vec::new();
+
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        let mut contour = Vec::new();
+        let mut current = start;
+        let closed = loop {
+            used[current] = true;
+            let (from, to) = segments[current];
+            contour.push(from);
+            if key(to) == key(segments[start].0) {
+                break true;
+            }
+            let dir_in = [to[0] - from[0], to[1] - from[1]];
+            let next = outgoing.get(&key(to)).and_then(|candidates| {
+                candidates
+                    .iter()
+                    .cloned()
+                    .filter(|c| !used[*c])
+                    .max_by(|x, y| {
+                        let turn = |c: usize| {
+                            let (f, t) = segments[c];
+                            let d = [t[0] - f[0], t[1] - f[1]];
+                            (dir_in[0] * d[1] - dir_in[1] * d[0])
+                                .atan2(dir_in[0] * d[0] + dir_in[1] * d[1])
+                        };
+                        turn(*x)
+                            .partial_cmp(&turn(*y))
+                            .unwrap_or(::std::cmp::Ordering::Equal)
+                    })
+            });
+            match next {
+                Some(n) => current = n,
+                None => break false,
+            }
+        };
+        if closed {
+            contours.push(contour);
+        } else {
+            warn!("Dropped an open contour while combining shapes.");
+        }
+    }
+    contours
+}
+
+/// Sorts contours into outer contours (counter-clockwise) and holes (clockwise), and assigns
+/// each hole to the smallest outer contour containing it.
+fn shapes_from_contours(contours: Vec<Vec<[f32; 2]>>) -> Vec<Shape2D> {
+    let mut outers = Vec::new();
+    let mut holes = Vec::new();
+    for c in contours {
+        let area = contour_area(&c);
+        if c.len() < 3 || area.abs() <= SHAPE_EPSILON {
+            continue;
+        }
+        if area > 0.0 {
+            outers.push(Shape2D::new(c, vec![]));
+        } else {
+            holes.push(c);
+        }
+    }
+    for hole in holes {
+        // A point just inside the hole, next to its first edge.
+        let (a, b) = (hole[0], hole[1]);
+        let d = [b[0] - a[0], b[1] - a[1]];
+        let len = (d[0] * d[0] + d[1] * d[1]).sqrt();
+        let probe = [
+            (a[0] + b[0]) / 2.0 + d[1] / len * SHAPE_EPSILON * 10.0,
+            (a[1] + b[1]) / 2.0 - d[0] / len * SHAPE_EPSILON * 10.0,
+        ];
+        let owner = outers
+            .iter_mut()
+            .filter(|s| point_in_contours(probe, &[s.outer.clone()]))
+            .min_by(|x, y| {
+                contour_area(&x.outer)
+                    .partial_cmp(&contour_area(&y.outer))
+                    .unwrap_or(::std::cmp::Ordering::Equal)
+            });
+        match owner {
+            Some(shape) => shape.holes.push(oriented_contour(hole, false)),
+            None => warn!("Dropped a hole that isn't inside any shape."),
+        }
+    }
+    outers
+}
+
+/// A contour for a rectangle centered on `center`.
+pub fn rectangle_contour(center: [f32; 2], w: f32, h: f32) -> Vec<[f32; 2]> {
+    vec![
+        [center[0] - w / 2., center[1] - h / 2.],
+        [center[0] + w / 2., center[1] - h / 2.],
+        [center[0] + w / 2., center[1] + h / 2.],
+        [center[0] - w / 2., center[1] + h / 2.],
+    ]
+}
+
+/// A contour approximating a circle with `resolution` points.
+pub fn circle_contour(center: [f32; 2], radius: f32, resolution: usize) -> Vec<[f32; 2]> {
+    use std::f32::consts::PI;
+    (0..resolution)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f32 / resolution as f32;
+            [
+                center[0] + angle.cos() * radius,
+                center[1] + angle.sin() * radius,
+            ]
+        }).collect()
+}
+
+/// Builds 2d shapes out of arbitrary polygons and turns them into meshes.
+///
+/// Polygons can be concave and have holes. Overlapping shapes should be combined using
+/// `union` and `difference` rather than `with_polygon`, which assumes the shapes don't overlap.
+///
+/// Example:
+/// ```rs
+/// let panel = ShapeBuilder::new()
+///     .union(rectangle_contour([0.0, 0.0], 4.0, 2.0))
+///     .union(circle_contour([2.0, 0.0], 1.0, 32))
+///     .difference(rectangle_contour([0.0, 0.0], 1.0, 1.0))
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ShapeBuilder {
+    shapes: Vec<Shape2D>,
+    uv: UvMapping,
+}
+
+impl ShapeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a polygon with holes. It must not overlap the shapes already present.
+    pub fn with_polygon(mut self, outer: Vec<[f32; 2]>, holes: Vec<Vec<[f32; 2]>>) -> Self {
+        let shape = Shape2D::new(outer, holes);
+        if shape.outer.len() >= 3 {
+            self.shapes.push(shape);
+        }
+        self
+    }
+
+    /// Sets how texture coordinates are generated. The bounding box of all the shapes is used.
+    pub fn with_uv_mapping(mut self, uv: UvMapping) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    /// Merges a simple polygon into the current shapes.
+    pub fn union(mut self, contour: Vec<[f32; 2]>) -> Self {
+        self.shapes = shape_boolean(&self.shapes, contour, BooleanOp::Union);
+        self
+    }
+
+    /// Cuts a simple polygon out of the current shapes.
+    pub fn difference(mut self, contour: Vec<[f32; 2]>) -> Self {
+        self.shapes = shape_boolean(&self.shapes, contour, BooleanOp::Difference);
+        self
+    }
+
+    pub fn shapes(&self) -> &[Shape2D] {
+        &self.shapes
+    }
+
+    fn bounds(&self) -> ([f32; 2], [f32; 2]) {
+        let mut min = [::std::f32::MAX; 2];
+        let mut max = [::std::f32::MIN; 2];
+        for p in self.shapes.iter().flat_map(|s| s.outer.iter()) {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        (min, max)
+    }
+
+    /// Triangulates the shapes into a flat mesh on the z = 0 plane.
+    pub fn build(&self) -> IndexedMesh<PosTex> {
+        let (min, max) = self.bounds();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for shape in &self.shapes {
+            let offset = vertices.len() as u32;
+            indices.extend(shape.triangulate().into_iter().map(|i| i + offset));
+            vertices.extend(shape.points().into_iter().map(|p| PosTex {
+                position: [p[0], p[1], 0.0],
+                tex_coord: self.uv.map(p, min, max),
+            }));
+        }
+        IndexedMesh::new(vertices, indices)
+    }
+
+    /// Extrudes the shapes along z, from z = 0 to z = `depth`.
+    /// The front cap faces +z, the back cap faces -z and the sides face outwards.
+    /// Caps use the uv mapping, each side quad maps the full texture.
+    pub fn extrude(&self, depth: f32) -> IndexedMesh<PosNormTex> {
+        let (min, max) = self.bounds();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for shape in &self.shapes {
+            let triangles = shape.triangulate();
+            let points = shape.points();
+
+            let front = vertices.len() as u32;
+            for p in &points {
+                vertices.push(PosNormTex {
+                    position: [p[0], p[1], depth],
+                    normal: [0.0, 0.0, 1.0],
+                    tex_coord: self.uv.map(*p, min, max),
+                });
+            }
+            indices.extend(triangles.iter().map(|i| i + front));
+
+            let back = vertices.len() as u32;
+            for p in &points {
+                vertices.push(PosNormTex {
+                    position: [p[0], p[1], 0.0],
+                    normal: [0.0, 0.0, -1.0],
+                    tex_coord: self.uv.map(*p, min, max),
+                });
+            }
+            for t in triangles.chunks(3) {
+                indices.extend_from_slice(&[back + t[0], back + t[2], back + t[1]]);
+            }
+
+            for contour in ::std::iter::once(&shape.outer).chain(shape.holes.iter()) {
+                let n = contour.len();
+                for i in 0..n {
+                    let a = contour[i];
+                    let b = contour[(i + 1) % n];
+                    let d = [b[0] - a[0], b[1] - a[1]];
+                    let len = (d[0] * d[0] + d[1] * d[1]).sqrt();
+                    // The filled side is on the left of each edge, so the right is outside.
+                    let normal = [d[1] / len, -d[0] / len, 0.0];
+                    let base = vertices.len() as u32;
+                    for &(p, z, uv) in &[
+                        (a, 0.0, [0.0, 0.0]),
+                        (b, 0.0, [1.0, 0.0]),
+                        (b, depth, [1.0, 1.0]),
+                        (a, depth, [0.0, 1.0]),
+                    ] {
+                        vertices.push(PosNormTex {
+                            position: [p[0], p[1], z],
+                            normal,
+                            tex_coord: uv,
+                        });
+                    }
+                    indices.extend_from_slice(&[
+                        base,
+                        base + 1,
+                        base + 2,
+                        base + 2,
+                        base + 3,
+                        base,
+                    ]);
+                }
+            }
+        }
+        IndexedMesh::new(vertices, indices)
+    }
+}
+
+/// Deterministic pseudo random value in [0, 1] for a point of the integer lattice.
+fn lattice_hash(x: i32, y: i32, seed: u64) -> f32 {
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    h ^= h >> 33;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Smoothstep used to interpolate between lattice points.
+fn fade(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Value noise. Returns a value in [0, 1].
+pub fn value_noise_2d(x: f32, y: f32, seed: u64) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (ix, iy) = (x0 as i32, y0 as i32);
+    let (tx, ty) = (fade(x - x0), fade(y - y0));
+    lerp(
+        lerp(
+            lattice_hash(ix, iy, seed),
+            lattice_hash(ix + 1, iy, seed),
+            tx,
+        ),
+        lerp(
+            lattice_hash(ix, iy + 1, seed),
+            lattice_hash(ix + 1, iy + 1, seed),
+            tx,
+        ),
+        ty,
+    )
+}
+
+/// Perlin (gradient) noise. Returns a value in [0, 1].
+pub fn perlin_noise_2d(x: f32, y: f32, seed: u64) -> f32 {
+    use std::f32::consts::PI;
+
+    let (x0, y0) = (x.floor(), y.floor());
+    let (ix, iy) = (x0 as i32, y0 as i32);
+    let (fx, fy) = (x - x0, y - y0);
+    let gradient = |gx: i32, gy: i32, dx: f32, dy: f32| {
+        let angle = lattice_hash(gx, gy, seed) * 2.0 * PI;
+        angle.cos() * dx + angle.sin() * dy
+    };
+    let (tx, ty) = (fade(fx), fade(fy));
+    let v = lerp(
+        lerp(
+            gradient(ix, iy, fx, fy),
+            gradient(ix + 1, iy, fx - 1.0, fy),
+            tx,
+        ),
+        lerp(
+            gradient(ix, iy + 1, fx, fy - 1.0),
+            gradient(ix + 1, iy + 1, fx - 1.0, fy - 1.0),
+            tx,
+        ),
+        ty,
+    );
+    // Unit gradients keep 2d perlin noise within [-sqrt(0.5), sqrt(0.5)].
+    (v * ::std::f32::consts::FRAC_1_SQRT_2 + 0.5)
+        .max(0.0)
+        .min(1.0)
+}
+
+/// The base noise function used by `NoiseSettings`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum NoiseType {
+    Value,
+    Perlin,
+}
+
+/// Fractal noise made by summing octaves of a base noise.
+/// The same settings and seed always give the same result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, new)]
+pub struct NoiseSettings {
+    pub noise_type: NoiseType,
+    pub seed: u64,
+    /// Number of noise cells over a [0, 1] range.
+    pub frequency: f32,
+    /// Number of layers of noise, each with twice the frequency of the previous one.
+    #[new(value = "1")]
+    pub octaves: u32,
+    /// Amplitude multiplier applied to each successive octave.
+    #[new(value = "0.5")]
+    pub persistence: f32,
+}
+
+impl NoiseSettings {
+    /// Samples the noise at normalized coordinates. Returns a value in [0, 1].
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut max = 0.0;
+        for octave in 0..self.octaves.max(1) {
+            // Offset the seed so octaves don't line up.
+            let seed = self.seed.wrapping_add(octave as u64);
+            let n = match self.noise_type {
+                NoiseType::Value => value_noise_2d(x * frequency, y * frequency, seed),
+                NoiseType::Perlin => perlin_noise_2d(x * frequency, y * frequency, seed),
+            };
+            total += n * amplitude;
+            max += amplitude;
+            amplitude *= self.persistence;
+            frequency *= 2.0;
+        }
+        total / max
+    }
+}
+
+/// A grid of height samples in [0, 1], stored row by row.
+/// Row 0 is the far edge of the terrain (-z), column 0 its left edge (-x).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    pub width: usize,
+    pub depth: usize,
+    pub heights: Vec<f32>,
+}
+
+impl Heightmap {
+    /// Returns None if the size doesn't match the number of samples.
+    pub fn new(width: usize, depth: usize, heights: Vec<f32>) -> Option<Self> {
+        if width * depth != heights.len() {
+            error!(
+                "The heightmap size {}x{} doesn't match the number of samples: {}",
+                width,
+                depth,
+                heights.len()
+            );
+            return None;
+        }
+        Some(Heightmap {
+            width,
+            depth,
+            heights,
+        })
+    }
+
+    /// Fills the heightmap by calling `f` with normalized coordinates in [0, 1].
+    pub fn from_fn<F: Fn(f32, f32) -> f32>(width: usize, depth: usize, f: F) -> Self {
+        let mut heights = Vec::with_capacity(width * depth);
+        for z in 0..depth {
+            for x in 0..width {
+                heights.push(f(
+                    x as f32 / (width.max(2) - 1) as f32,
+                    z as f32 / (depth.max(2) - 1) as f32,
+                ));
+            }
+        }
+        Heightmap {
+            width,
+            depth,
+            heights,
+        }
+    }
+
+    /// Generates a heightmap from seeded noise.
+    pub fn from_noise(width: usize, depth: usize, noise: &NoiseSettings) -> Self {
+        Heightmap::from_fn(width, depth, |x, z| noise.sample(x, z))
+    }
+
+    /// The sample at the given coordinates, clamped to the edges of the map.
+    /// Returns None if the map is empty.
+    pub fn get(&self, x: usize, z: usize) -> Option<f32> {
+        if self.width == 0 || self.depth == 0 {
+            return None;
+        }
+        self.heights
+            .get(z.min(self.depth - 1) * self.width + x.min(self.width - 1))
+            .cloned()
+    }
+
+    /// Bilinear sample at normalized coordinates in [0, 1].
+    /// An empty map is flat at height 0.
+    pub fn sample(&self, u: f32, v: f32) -> f32 {
+        if self.width == 0 || self.depth == 0 {
+            return 0.0;
+        }
+        let get = |x, z| self.get(x, z).unwrap_or(0.0);
+        let fx = u.max(0.0).min(1.0) * (self.width - 1) as f32;
+        let fz = v.max(0.0).min(1.0) * (self.depth - 1) as f32;
+        let (x0, z0) = (fx.floor() as usize, fz.floor() as usize);
+        let (tx, tz) = (fx - x0 as f32, fz - z0 as f32);
+        lerp(
+            lerp(get(x0, z0), get(x0 + 1, z0), tx),
+            lerp(get(x0, z0 + 1), get(x0 + 1, z0 + 1), tx),
+            tz,
+        )
+    }
+}
+
+/// How a heightmap is turned into a terrain.
+/// The terrain is centered on the origin along x and z, and goes up from y = 0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, new)]
+pub struct TerrainSettings {
+    /// Size of the terrain along x and z, in world units.
+    pub size: [f32; 2],
+    /// The height of a heightmap sample of 1, in world units.
+    pub height: f32,
+    /// Number of cells of the mesh along x and z.
+    pub resolution: [usize; 2],
+    /// How texture coordinates are mapped on the terrain, seen from above.
+    #[new(default)]
+    pub uv: UvMapping,
+}
+
+impl TerrainSettings {
+    fn vertex_heights(&self, heightmap: &Heightmap) -> Vec<f32> {
+        let (rx, rz) = (self.resolution[0].max(1), self.resolution[1].max(1));
+        let mut heights = Vec::with_capacity((rx + 1) * (rz + 1));
+        for j in 0..rz + 1 {
+            for i in 0..rx + 1 {
+                heights.push(
+                    heightmap.sample(i as f32 / rx as f32, j as f32 / rz as f32) * self.height,
+                );
+            }
+        }
+        heights
+    }
+}
+
+/// Generates a grid mesh following the heightmap, with normals and tangents
+/// so it can be used with normal maps.
+pub fn generate_terrain(
+    heightmap: &Heightmap,
+    settings: &TerrainSettings,
+) -> IndexedMesh<PosNormTangTex> {
+    let (rx, rz) = (settings.resolution[0].max(1), settings.resolution[1].max(1));
+    let (sx, sz) = (settings.size[0], settings.size[1]);
+    let (dx, dz) = (sx / rx as f32, sz / rz as f32);
+    let heights = settings.vertex_heights(heightmap);
+    let height_at = |i: isize, j: isize| {
+        let i = i.max(0).min(rx as isize) as usize;
+        let j = j.max(0).min(rz as isize) as usize;
+        heights[j * (rx + 1) + i]
+    };
+
+    let mut vertices = Vec::with_capacity(heights.len());
+    for j in 0..rz + 1 {
+        for i in 0..rx + 1 {
+            let (ii, jj) = (i as isize, j as isize);
+            let x = -sx / 2.0 + i as f32 * dx;
+            let z = -sz / 2.0 + j as f32 * dz;
+            // Central differences, one sided on the borders.
+            let slope_x = (height_at(ii + 1, jj) - height_at(ii - 1, jj))
+                / (((i + 1).min(rx) - i.saturating_sub(1)) as f32 * dx);
+            let slope_z = (height_at(ii, jj + 1) - height_at(ii, jj - 1))
+                / (((j + 1).min(rz) - j.saturating_sub(1)) as f32 * dz);
+            let normal = Vector3::new(-slope_x, 1.0, -slope_z).normalize();
+            let tangent = Vector3::new(1.0, slope_x, 0.0).normalize();
+            vertices.push(PosNormTangTex {
+                position: [x, height_at(ii, jj), z],
+                normal: normal.into(),
+                tangent: tangent.into(),
+                tex_coord: settings
+                    .uv
+                    .map([x, -z], [-sx / 2.0, -sz / 2.0], [sx / 2.0, sz / 2.0]),
+            });
+        }
+    }
+
+    let mut indices = Vec::with_capacity(rx * rz * 6);
+    for j in 0..rz {
+        for i in 0..rx {
+            let a = (j * (rx + 1) + i) as u32;
+            let b = a + 1;
+            let d = a + rx as u32 + 1;
+            let c = d + 1;
+            // Counter-clockwise seen from above.
+            indices.extend_from_slice(&[a, d, c, a, c, b]);
+        }
+    }
+
+    IndexedMesh::new(vertices, indices)
+}
+
+/// Creates a material with a 1x1 texture of the given color as albedo.
+/// A new texture is uploaded on every call, see `ColorMaterialCache` to share them.
+pub fn material_from_color(
+    color: [f32; 4],
+    loader: &Loader,
+    storage: &AssetStorage<Texture>,
+    material_defaults: &MaterialDefaults,
+) -> Material {
+    let albedo = loader.load_from_data(color.into(), (), &storage);
+    material_from_texture(albedo, material_defaults)
+}
+
+pub fn material_from_texture(texture: Handle<Texture>, defaults: &MaterialDefaults) -> Material {
+    Material {
+        albedo: texture,
+        ..defaults.0.clone()
+    }
+}
+
+pub fn value_near<B: Add<Output = B> + Sub<Output = B> + PartialOrd + Copy>(
+    number: B,
+    target: B,
+    margin: B,
+) -> bool {
+    number >= target - margin && number <= target + margin
+}
+
+pub fn material_from_png(
+    path: &str,
+    loader: &Loader,
+    storage: &AssetStorage<Texture>,
+    material_defaults: &MaterialDefaults,
+) -> Material {
+    material_from_texture(
+        loader.load(path, PngFormat, TextureMetadata::default(), (), &storage),
+        material_defaults,
+    )
+}
+
+/// An RGBA image generated on the cpu, 4 bytes per pixel, row by row starting from the top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl PixelBuffer {
+    /// Creates an image by calling `f` with the coordinates of each pixel.
+    pub fn from_fn<F: Fn(u32, u32) -> [f32; 4]>(width: u32, height: u32, f: F) -> Self {
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.extend_from_slice(&ColorMaterialCache::quantize(f(x, y)));
+            }
+        }
+        PixelBuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    /// Converts the image into texture data using the given metadata. The size is filled in for you.
+    pub fn into_texture_data(self, metadata: TextureMetadata) -> TextureData {
+        let metadata = metadata.with_size(self.width as u16, self.height as u16);
+        TextureData::U8(self.pixels, metadata)
+    }
+}
+
+impl From<PixelBuffer> for TextureData {
+    fn from(buffer: PixelBuffer) -> Self {
+        buffer.into_texture_data(TextureMetadata::default())
+    }
+}
+
+fn lerp_color(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [
+        lerp(a[0], b[0], t),
+        lerp(a[1], b[1], t),
+        lerp(a[2], b[2], t),
+        lerp(a[3], b[3], t),
+    ]
+}
+
+/// Position of the center of a pixel, normalized to [0, 1].
+fn normalized_pixel(x: u32, y: u32, width: u32, height: u32) -> [f32; 2] {
+    [
+        (x as f32 + 0.5) / width as f32,
+        (y as f32 + 0.5) / height as f32,
+    ]
+}
+
+/// A checkerboard of `cell` x `cell` pixel squares. The top left square uses `a`.
+pub fn checkerboard_texture(size: [u32; 2], cell: u32, a: [f32; 4], b: [f32; 4]) -> PixelBuffer {
+    let cell = cell.max(1);
+    PixelBuffer::from_fn(size[0], size[1], |x, y| {
+        if (x / cell + y / cell) % 2 == 0 {
+            a
+        } else {
+            b
+        }
+    })
+}
+
+/// A gradient going from `from` to `to` along `direction`, given in texture space (+y is down).
+/// The gradient spans the whole texture, from corner to corner for diagonal directions.
+pub fn linear_gradient_texture(
+    size: [u32; 2],
+    direction: [f32; 2],
+    from: [f32; 4],
+    to: [f32; 4],
+) -> PixelBuffer {
+    let len = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+    let dir = if len == 0.0 {
+        [1.0, 0.0]
+    } else {
+        [direction[0] / len, direction[1] / len]
+    };
+    // Half of the projected size of the texture on the direction.
+    let extent = (dir[0].abs() + dir[1].abs()) / 2.0;
+    PixelBuffer::from_fn(size[0], size[1], |x, y| {
+        let p = normalized_pixel(x, y, size[0], size[1]);
+        let t = ((p[0] - 0.5) * dir[0] + (p[1] - 0.5) * dir[1]) / extent / 2.0 + 0.5;
+        lerp_color(from, to, t.max(0.0).min(1.0))
+    })
+}
+
+/// A gradient going from `inner` at `center` to `outer` at `radius` and beyond.
+/// `center` and `radius` are normalized to the texture size.
+pub fn radial_gradient_texture(
+    size: [u32; 2],
+    center: [f32; 2],
+    radius: f32,
+    inner: [f32; 4],
+    outer: [f32; 4],
+) -> PixelBuffer {
+    PixelBuffer::from_fn(size[0], size[1], |x, y| {
+        let p = normalized_pixel(x, y, size[0], size[1]);
+        let d = ((p[0] - center[0]).powi(2) + (p[1] - center[1]).powi(2)).sqrt();
+        let t = if radius > 0.0 { d / radius } else { 1.0 };
+        lerp_color(inner, outer, t.max(0.0).min(1.0))
+    })
+}
+
+/// Noise blending between `low` and `high`. The same settings always give the same image.
+pub fn noise_texture(
+    size: [u32; 2],
+    noise: &NoiseSettings,
+    low: [f32; 4],
+    high: [f32; 4],
+) -> PixelBuffer {
+    PixelBuffer::from_fn(size[0], size[1], |x, y| {
+        let p = normalized_pixel(x, y, size[0], size[1]);
+        lerp_color(low, high, noise.sample(p[0], p[1]))
+    })
+}
+
+/// Grid lines of `line_width` pixels every `cell` pixels, over a `background` color.
+/// Lines are drawn on the top and left edges of each cell, so the texture tiles seamlessly
+/// when its size is a multiple of `cell`.
+pub fn grid_texture(
+    size: [u32; 2],
+    cell: u32,
+    line_width: u32,
+    background: [f32; 4],
+    line: [f32; 4],
+) -> PixelBuffer {
+    let cell = cell.max(1);
+    PixelBuffer::from_fn(size[0], size[1], |x, y| {
+        if x % cell < line_width || y % cell < line_width {
+            line
+        } else {
+            background
+        }
+    })
+}
+
+/// A named set of colors, loaded from a ron file.
+///
+/// Example file:
+/// ```ron
+/// (
+///     colors: {
+///         "wall": (0.5, 0.5, 0.5, 1.0),
+///         "danger": (1.0, 0.0, 0.0, 1.0),
+///     },
+/// )
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColorPalette {
+    pub colors: HashMap<String, [f32; 4]>,
+}
+
+/// Hit and miss counts of a `ColorMaterialCache`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// The fraction of lookups that were served from the cache, between 0 and 1.
+    pub fn hit_ratio(&self) -> f32 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f32 / total as f32
+        }
+    }
+}
+
+/// Resource handing out shared solid color textures and materials.
+/// Colors are quantized to 8 bits per channel, so colors that would look the same
+/// share a single 1x1 texture instead of uploading a new one every time.
+#[derive(Default)]
+pub struct ColorMaterialCache {
+    textures: HashMap<[u8; 4], Handle<Texture>>,
+    materials: HashMap<[u8; 4], Material>,
+    palettes: HashMap<String, ColorPalette>,
+    stats: CacheStats,
+}
+
+impl ColorMaterialCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cache key of a color.
+    pub fn quantize(color: [f32; 4]) -> [u8; 4] {
+        let q = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+        [q(color[0]), q(color[1]), q(color[2]), q(color[3])]
+    }
+
+    /// Gets the texture of this color, creating it if it isn't cached yet.
+    pub fn texture(
+        &mut self,
+        color: [f32; 4],
+        loader: &Loader,
+        storage: &AssetStorage<Texture>,
+    ) -> Handle<Texture> {
+        let key = ColorMaterialCache::quantize(color);
+        if self.textures.contains_key(&key) {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+        self.texture_for_key(key, loader, storage)
+    }
+
+    /// Gets or creates the texture of a quantized color without counting the lookup.
+    fn texture_for_key(
+        &mut self,
+        key: [u8; 4],
+        loader: &Loader,
+        storage: &AssetStorage<Texture>,
+    ) -> Handle<Texture> {
+        if let Some(handle) = self.textures.get(&key) {
+            return handle.clone();
+        }
+        let quantized = [
+            key[0] as f32 / 255.0,
+            key[1] as f32 / 255.0,
+            key[2] as f32 / 255.0,
+            key[3] as f32 / 255.0,
+        ];
+        let handle: Handle<Texture> = loader.load_from_data(quantized.into(), (), &storage);
+        self.textures.insert(key, handle.clone());
+        handle
+    }
+
+    /// Gets a material using the texture of this color as albedo, like `material_from_color`,
+    /// creating it if it isn't cached yet.
+    pub fn material(
+        &mut self,
+        color: [f32; 4],
+        loader: &Loader,
+        storage: &AssetStorage<Texture>,
+        material_defaults: &MaterialDefaults,
+    ) -> Material {
+        let key = ColorMaterialCache::quantize(color);
+        if let Some(material) = self.materials.get(&key) {
+            self.stats.hits += 1;
+            return material.clone();
+        }
+        self.stats.misses += 1;
+        let texture = self.texture_for_key(key, loader, storage);
+        let material = material_from_texture(texture, material_defaults);
+        self.materials.insert(key, material.clone());
+        material
+    }
+
+    pub fn add_palette(&mut self, name: &str, palette: ColorPalette) {
+        self.palettes.insert(name.to_string(), palette);
+    }
+
+    pub fn palette(&self, name: &str) -> Option<&ColorPalette> {
+        self.palettes.get(name)
+    }
+
+    /// Looks up a color by palette and color name.
+    pub fn palette_color(&self, palette: &str, color: &str) -> Option<[f32; 4]> {
+        self.palettes
+            .get(palette)
+            .and_then(|p| p.colors.get(color))
+            .cloned()
+    }
+
+    /// Gets the material of a palette color.
+    pub fn palette_material(
+        &mut self,
+        palette: &str,
+        color: &str,
+        loader: &Loader,
+        storage: &AssetStorage<Texture>,
+        material_defaults: &MaterialDefaults,
+    ) -> Option<Material> {
+        let c = self.palette_color(palette, color)?;
+        Some(self.material(c, loader, storage, material_defaults))
+    }
+
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+
+    /// Number of distinct textures currently cached.
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
+    }
+
+    /// Drops all the cached handles. Palettes are kept.
+    pub fn clear(&mut self) {
+        self.textures.clear();
+        self.materials.clear();
+    }
+}
+
+/// A rectangle of a texture atlas, in pixels from the top left corner of the texture.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A named sequence of atlas regions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtlasAnimation {
+    pub frames: Vec<String>,
+    /// Time each frame is shown, in seconds.
+    pub frame_time: f64,
+    #[serde(default)]
+    pub looping: bool,
+}
+
+/// Describes the named regions of a sprite sheet. Usually loaded from a ron file.
+///
+/// Example file:
+/// ```ron
+/// (
+///     texture: "sprites/player.png",
+///     width: 128,
+///     height: 64,
+///     regions: {
+///         "idle_0": (x: 0, y: 0, width: 32, height: 32),
+///         "idle_1": (x: 32, y: 0, width: 32, height: 32),
+///     },
+///     animations: {
+///         "idle": (frames: ["idle_0", "idle_1"], frame_time: 0.25, looping: true),
+///     },
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextureAtlas {
+    /// Path of the texture, resolved through the asset packs.
+    pub texture: String,
+    /// Size of the texture in pixels.
+    pub width: u32,
+    pub height: u32,
+    pub regions: HashMap<String, AtlasRegion>,
+    #[serde(default)]
+    pub animations: HashMap<String, AtlasAnimation>,
+}
+
+impl TextureAtlas {
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        let region = self.regions.get(name);
+        if region.is_none() {
+            warn!("Unknown atlas region: {}", name);
+        }
+        region
+    }
+
+    /// The texture coordinates of a region as (min, max).
+    /// Texture coordinates start at the bottom left, while regions start at the top left.
+    pub fn uv_rect(&self, name: &str) -> Option<([f32; 2], [f32; 2])> {
+        self.region(name).map(|r| {
+            let (w, h) = (self.width as f32, self.height as f32);
+            (
+                [r.x as f32 / w, 1.0 - (r.y + r.height) as f32 / h],
+                [(r.x + r.width) as f32 / w, 1.0 - r.y as f32 / h],
+            )
+        })
+    }
+
+    pub fn uv_mapping(&self, name: &str) -> Option<UvMapping> {
+        self.uv_rect(name)
+            .map(|(min, max)| UvMapping::Atlas { min, max })
+    }
+
+    /// The texture offset selecting a region, for use in a `Material`.
+    pub fn texture_offset(&self, name: &str) -> Option<TextureOffset> {
+        self.uv_rect(name).map(|(min, max)| TextureOffset {
+            u: (min[0], max[0]),
+            v: (min[1], max[1]),
+        })
+    }
+
+    /// A `w` by `h` quad showing the region. The region is baked in the texture coordinates,
+    /// so it can use the whole atlas texture as albedo.
+    pub fn quad(&self, name: &str, w: f32, h: f32) -> Option<IndexedMesh<PosTex>> {
+        self.uv_mapping(name)
+            .map(|uv| gen_rectangle_indexed(w, h, uv))
+    }
+
+    /// A material showing a region of the atlas texture on a mesh with [0, 1] texture coordinates,
+    /// like the one `gen_rectangle_mesh` creates.
+    pub fn material(
+        &self,
+        name: &str,
+        texture: Handle<Texture>,
+        defaults: &MaterialDefaults,
+    ) -> Option<Material> {
+        self.texture_offset(name).map(|offset| Material {
+            albedo_offset: offset,
+            ..material_from_texture(texture, defaults)
+        })
+    }
+}
+
+/// Cycles the albedo texture offset of an entity's `Material` through atlas regions.
+/// Use it with a mesh that has [0, 1] texture coordinates and the atlas texture as albedo.
+#[derive(Debug, Clone, Component, new)]
+pub struct FrameAnimation {
+    pub frames: Vec<TextureOffset>,
+    /// Time each frame is shown, in seconds.
+    pub frame_time: f64,
+    pub looping: bool,
+    #[new(default)]
+    pub current: usize,
+    #[new(default)]
+    pub elapsed: f64,
+    #[new(value = "true")]
+    pub playing: bool,
+}
+
+impl FrameAnimation {
+    /// Creates the animation from one of the atlas' animations.
+    pub fn from_atlas(atlas: &TextureAtlas, animation: &str) -> Option<Self> {
+        let anim = match atlas.animations.get(animation) {
+            Some(a) => a,
+            None => {
+                warn!("Unknown atlas animation: {}", animation);
+                return None;
+            }
+        };
+        let frames = anim
+            .frames
+            .iter()
+            .map(|f| atlas.texture_offset(f))
+            .collect::<Option<Vec<_>>>()?;
+        Some(FrameAnimation::new(frames, anim.frame_time, anim.looping))
+    }
+
+    /// Moves the animation forward. Returns true if the frame changed.
+    pub fn advance(&mut self, delta_seconds: f64) -> bool {
+        if !self.playing || self.frames.is_empty() || self.frame_time <= 0.0 {
+            return false;
+        }
+        let previous = self.current;
+        self.elapsed += delta_seconds;
+        while self.elapsed >= self.frame_time {
+            self.elapsed -= self.frame_time;
+            if self.current + 1 < self.frames.len() {
+                self.current += 1;
+            } else if self.looping {
+                self.current = 0;
+            } else {
+                self.playing = false;
+                self.elapsed = 0.0;
+                break;
+            }
+        }
+        self.current != previous
+    }
+
+    /// Goes back to the first frame and plays.
+    pub fn restart(&mut self) {
+        self.current = 0;
+        self.elapsed = 0.0;
+        self.playing = true;
+    }
+
+    pub fn current_frame(&self) -> Option<&TextureOffset> {
+        self.frames.get(self.current)
+    }
+}
+
+/// Advances the `FrameAnimation`s and applies the current frame to the entities' `Material`.
+pub struct FrameAnimationSystem;
+
+impl<'a> System<'a> for FrameAnimationSystem {
+    type SystemData = (
+        Read<'a, Time>,
+        WriteStorage<'a, FrameAnimation>,
+        WriteStorage<'a, Material>,
+    );
+
+    fn run(&mut self, (time, mut animations, mut materials): Self::SystemData) {
+        for (animation, material) in (&mut animations, &mut materials).join() {
+            animation.advance(time.delta_seconds() as f64);
+            if let Some(frame) = animation.current_frame() {
+                material.albedo_offset = frame.clone();
+            }
+        }
+    }
+}
+
+/// Doesn't work if you run `cargo run` while you are not in the root directory
+pub fn get_working_dir() -> String {
+    let mut base_path = String::from(
+        std::env::current_exe()
+            .expect("Failed to find executable path.")
+            .parent()
+            .expect("Failed to get parent directory of the executable.")
+            .to_str()
+            .unwrap(),
+    );
+    if base_path.contains("target/") || base_path.contains("target\\") {
+        base_path = String::from(".");
+    }
+    base_path
+}
+
+/// Asks for a sound effect to be played. Gameplay systems write these to an
+/// `EventChannel<SoundEvent>`, and the `SoundEffectsSystem` plays them when the `audio`
+/// feature is enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundEvent {
+    /// The name of the sound in the `SoundEffects` resource.
+    pub name: String,
+    /// Where the sound comes from in the world. None plays it without falloff.
+    pub position: Option<Vector3<f32>>,
+}
+
+impl SoundEvent {
+    pub fn new(name: &str) -> Self {
+        SoundEvent {
+            name: name.to_string(),
+            position: None,
+        }
+    }
+
+    /// A sound heard relative to the `AudioListener`.
+    pub fn at(name: &str, position: Vector3<f32>) -> Self {
+        SoundEvent {
+            name: name.to_string(),
+            position: Some(position),
+        }
+    }
+}
+
+/// Registers the general purpose systems of this crate.
+///
+/// Systems, their names and dependencies:
+/// - `TimedDestroySystem`: "timed_destroy"
+/// - `TimerSystem`: "timers"
+/// - `UiTimerSystem`: "ui_timer", after "timers". Both write the `TimerFired` events,
+/// so the events of a frame always come in the same order.
+/// - `FrameAnimationSystem`: "frame_animation"
+/// - `GameSettingsSystem`: "game_settings"
+/// - `UiAutoTextSystem<T>`: the names given to `with_auto_text`, not added by default.
+///
+/// Systems only read the events written earlier in the same frame if they run after the
+/// writer. Make the systems reading `TimerFired` depend on "timers" and "ui_timer",
+/// and the ones reading `EntityExpired` on "timed_destroy", or they get the events
+/// one frame late.
+///
+/// Dependencies on disabled systems are skipped. All are enabled by default.
+#[derive(Debug, Clone, new)]
+pub struct ExtraBundle {
+    #[new(value = "true")]
+    timed_destroy: bool,
+    #[new(value = "true")]
+    timers: bool,
+    #[new(value = "true")]
+    ui_timer: bool,
+    #[new(value = "true")]
+    frame_animation: bool,
+    #[new(value = "true")]
+    settings: bool,
+    #[new(default)]
+    auto_texts: Vec<(String, AddAutoText)>,
+}
+
+/// Adds a `UiAutoTextSystem<T>` with this name to the dispatcher.
+#[derive(Clone, Copy)]
+struct AddAutoText(fn(&mut DispatcherBuilder, &str));
+
+impl ::std::fmt::Debug for AddAutoText {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.write_str("AddAutoText")
+    }
+}
+
+fn add_auto_text<T>(builder: &mut DispatcherBuilder, name: &str)
+where
+    T: Component + UiAutoText,
+{
+    builder.add(UiAutoTextSystem::<T>::default(), name, &[]);
+}
+
+impl ExtraBundle {
+    pub fn with_timed_destroy(mut self, enabled: bool) -> Self {
+        self.timed_destroy = enabled;
+        self
+    }
+
+    pub fn with_timers(mut self, enabled: bool) -> Self {
+        self.timers = enabled;
+        self
+    }
+
+    pub fn with_ui_timer(mut self, enabled: bool) -> Self {
+        self.ui_timer = enabled;
+        self
+    }
+
+    pub fn with_frame_animation(mut self, enabled: bool) -> Self {
+        self.frame_animation = enabled;
+        self
+    }
+
+    /// The `GameSettingsSystem`, applying the key bindings and field of view of the
+    /// `Dirty<GameSettings>` resource.
+    pub fn with_settings(mut self, enabled: bool) -> Self {
+        self.settings = enabled;
+        self
+    }
+
+    /// Adds a `UiAutoTextSystem<T>` with this name, showing the `T`s in the `UiText` of
+    /// their entity.
+    pub fn with_auto_text<T>(mut self, name: &str) -> Self
+    where
+        T: Component + UiAutoText,
+    {
+        self.auto_texts
+            .push((name.to_string(), AddAutoText(add_auto_text::<T>)));
+        self
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for ExtraBundle {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> BundleResult<()> {
+        if self.timed_destroy {
+            builder.add(TimedDestroySystem, "timed_destroy", &[]);
+        }
+        if self.timers {
+            builder.add(TimerSystem, "timers", &[]);
+        }
+        if self.ui_timer {
+            let deps: &[&str] = if self.timers { &["timers"] } else { &[] };
+            builder.add(UiTimerSystem, "ui_timer", deps);
+        }
+        if self.frame_animation {
+            builder.add(FrameAnimationSystem, "frame_animation", &[]);
+        }
+        if self.settings {
+            builder.add(GameSettingsSystem::default(), "game_settings", &[]);
+        }
+        for (name, add) in self.auto_texts {
+            (add.0)(builder, &name);
+        }
+        Ok(())
+    }
+}
+
+/// The render pass used by a `GameDataPreset`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RenderPreset {
+    /// `DrawFlat<PosTex>`, for 2d games and unlit meshes.
+    Flat,
+    /// `DrawShaded<PosNormTex>`, lit meshes.
+    Shaded,
+    /// `DrawPbm<PosNormTangTex>`, physically based rendering.
+    Pbr,
+}
+
+/// Builds a `GameDataBuilder` with the bundles most games need.
+/// A and B are the axis and action types of the `InputHandler`.
+///
+/// The configuration files are read from `{base}/assets/{pack}/`.
+///
+/// Usage:
+/// ```rs
+/// let game_data = GameDataPreset::<String, String>::base_2d(env!("CARGO_MANIFEST_DIR"))
+///     .with_render(RenderPreset::Shaded)
+///     .with_animation(false)
+///     .build()?;
+/// ```
+pub struct GameDataPreset<A, B> {
+    base: String,
+    pack: String,
+    display_config: String,
+    input_config: String,
+    render: RenderPreset,
+    #[cfg(feature = "audio")]
+    audio: bool,
+    #[cfg(feature = "audio")]
+    playlist: bool,
+    #[cfg(feature = "audio")]
+    sound_effects: bool,
+    ui: bool,
+    draw_ui: bool,
+    animation: bool,
+    logger: bool,
+    extra: ExtraBundle,
+    _phantom: PhantomData<(A, B)>,
+}
+
+impl<A, B> GameDataPreset<A, B>
+where
+    A: Send + Sync + Hash + Eq + Clone + Serialize + DeserializeOwned + 'static,
+    B: Send + Sync + Hash + Eq + Clone + Serialize + DeserializeOwned + 'static,
+{
+    /// Flat rendering with input, ui, audio and animations.
+    pub fn base_2d(base: &str) -> Self {
+        GameDataPreset {
+            base: base.to_string(),
+            pack: "base".to_string(),
+            display_config: "config/display.ron".to_string(),
+            input_config: "config/input.ron".to_string(),
+            render: RenderPreset::Flat,
+            #[cfg(feature = "audio")]
+            audio: true,
+            #[cfg(feature = "audio")]
+            playlist: false,
+            #[cfg(feature = "audio")]
+            sound_effects: true,
+            ui: true,
+            draw_ui: true,
+            animation: true,
+            logger: true,
+            extra: ExtraBundle::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Shaded rendering with input, ui and audio.
+    /// Use `build_3d` (`physics-fps` feature) to also add the physics and first person systems.
+    pub fn base_3d(base: &str) -> Self {
+        GameDataPreset {
+            render: RenderPreset::Shaded,
+            animation: false,
+            ..GameDataPreset::base_2d(base)
+        }
+    }
+
+    /// The asset pack the configuration files are read from. Defaults to "base".
+    pub fn with_pack(mut self, pack: &str) -> Self {
+        self.pack = pack.to_string();
+        self
+    }
+
+    /// Path of the display configuration, relative to the asset pack.
+    pub fn with_display_config(mut self, path: &str) -> Self {
+        self.display_config = path.to_string();
+        self
+    }
+
+    /// Path of the input bindings, relative to the asset pack.
+    pub fn with_input_config(mut self, path: &str) -> Self {
+        self.input_config = path.to_string();
+        self
+    }
+
+    pub fn with_render(mut self, render: RenderPreset) -> Self {
+        self.render = render;
+        self
+    }
+
+    /// Adds the `AudioBundle`, playing the `Music` resource, and the `AudioSettingsSystem`,
+    /// applying the volumes of the `Dirty<GameSettings>` resource. Enabled by default.
+    #[cfg(feature = "audio")]
+    pub fn with_audio(mut self, audio: bool) -> Self {
+        self.audio = audio;
+        self
+    }
+
+    /// Plays the `Playlist` resource with the `PlaylistSystem` instead of the `Music` resource.
+    /// Only has an effect with the audio enabled.
+    #[cfg(feature = "audio")]
+    pub fn with_playlist(mut self, playlist: bool) -> Self {
+        self.playlist = playlist;
+        self
+    }
+
+    /// Adds the `SoundEffectsSystem`, playing the `SoundEffects` resource.
+    /// Only has an effect with the audio enabled. Enabled by default.
+    #[cfg(feature = "audio")]
+    pub fn with_sound_effects(mut self, sound_effects: bool) -> Self {
+        self.sound_effects = sound_effects;
+        self
+    }
+
+    /// Adds the `UiBundle` and draws the ui.
+    pub fn with_ui(mut self, ui: bool) -> Self {
+        self.ui = ui;
+        self
+    }
+
+    /// Draws the ui in the render pass when the `UiBundle` is added. Enabled by default.
+    pub fn with_draw_ui(mut self, draw_ui: bool) -> Self {
+        self.draw_ui = draw_ui;
+        self
+    }
+
+    /// Adds an `AnimationBundle<u32, Material>`.
+    pub fn with_animation(mut self, animation: bool) -> Self {
+        self.animation = animation;
+        self
+    }
+
+    /// The systems of this crate to add. Defaults to `ExtraBundle::new()`.
+    /// The `UiTimerSystem` is only added with the ui.
+    pub fn with_extra(mut self, extra: ExtraBundle) -> Self {
+        self.extra = extra;
+        self
+    }
+
+    /// Starts the amethyst logger when building. Enabled by default.
+    pub fn with_logger(mut self, logger: bool) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    fn config_path(&self, path: &str) -> String {
+        format!("{}/assets/{}/{}", self.base, self.pack, path)
+    }
 
-impl Component for DestroyInTime {
-    type Storage = VecStorage<Self>;
-}
+    pub fn build(self) -> Result<GameDataBuilder<'static, 'static>> {
+        self.build_with(GameDataBuilder::default(), &[])
+    }
 
-pub struct TimedDestroySystem;
+    fn build_with(
+        self,
+        builder: GameDataBuilder<'static, 'static>,
+        transform_deps: &[&str],
+    ) -> Result<GameDataBuilder<'static, 'static>> {
+        if self.logger {
+            amethyst::start_logger(Default::default());
+        }
 
-impl<'a> System<'a> for TimedDestroySystem {
-    type SystemData = (
-        Entities<'a>,
-        ReadStorage<'a, DestroyAtTime>,
-        WriteStorage<'a, DestroyInTime>,
-        Read<'a, Time>,
-    );
-    fn run(&mut self, (entities, dat, mut dit, time): Self::SystemData) {
-        for (e, d) in (&*entities, &dat).join() {
-            if time.absolute_time_seconds() > d.time {
-                entities.delete(e).expect("Failed to delete entity!");
+        let display_config_path = self.config_path(&self.display_config);
+        let key_bindings_path = self.config_path(&self.input_config);
+
+        let mut builder = builder
+            .with_bundle(TransformBundle::new().with_dep(transform_deps))?
+            .with_bundle(InputBundle::<A, B>::new().with_bindings_from_file(&key_bindings_path)?)?;
+        if self.ui {
+            builder = builder.with_bundle(UiBundle::<A, B>::new())?;
+        }
+        if self.animation {
+            builder = builder.with_bundle(AnimationBundle::<u32, Material>::new(
+                "animation_control_system",
+                "sampler_interpolation_system",
+            ))?;
+        }
+        #[cfg(feature = "audio")]
+        {
+            if self.audio && self.playlist {
+                builder = builder
+                    .with(Processor::<Source>::new(), "source_processor", &[])
+                    .with(PlaylistSystem, "playlist", &[]);
+            } else if self.audio {
+                builder = builder
+                    .with_bundle(AudioBundle::new(|music: &mut Music| music.music.next()))?;
+            }
+            if self.audio && self.sound_effects {
+                builder = builder.with(SoundEffectsSystem::default(), "sound_effects", &[]);
             }
+            if self.audio {
+                builder = builder.with(AudioSettingsSystem::default(), "audio_settings", &[]);
+            }
+        }
+        let mut extra = self.extra;
+        if !self.ui {
+            extra = extra.with_ui_timer(false);
         }
+        builder = builder.with_bundle(extra)?;
 
-        for (e, mut d) in (&*entities, &mut dit).join() {
-            if d.timer <= 0.0 {
-                entities.delete(e).expect("Failed to delete entity!");
+        let draw_ui = self.ui && self.draw_ui;
+        match self.render {
+            RenderPreset::Flat => {
+                builder.with_basic_renderer(display_config_path, DrawFlat::<PosTex>::new(), draw_ui)
             }
-            d.timer -= time.delta_seconds() as f64;
+            RenderPreset::Shaded => builder.with_basic_renderer(
+                display_config_path,
+                DrawShaded::<PosNormTex>::new(),
+                draw_ui,
+            ),
+            RenderPreset::Pbr => builder.with_basic_renderer(
+                display_config_path,
+                DrawPbm::<PosNormTangTex>::new(),
+                draw_ui,
+            ),
         }
     }
 }
 
-pub struct UiTimer {
-    pub start: f64,
-}
-
-impl Component for UiTimer {
-    type Storage = VecStorage<Self>;
+/// The flat 2d preset using `String` input types and the configs of the "base" asset pack.
+/// The ui isn't drawn, the `AudioBundle` plays the `Music` and only the `TimedDestroySystem` and
+/// `AudioSettingsSystem` of this crate are added.
+/// See `GameDataPreset` to change these options.
+pub fn amethyst_gamedata_base_2d(base: &str) -> Result<GameDataBuilder<'static, 'static>> {
+    let preset = GameDataPreset::<String, String>::base_2d(base)
+        .with_draw_ui(false)
+        .with_extra(
+            ExtraBundle::new()
+                .with_timers(false)
+                .with_ui_timer(false)
+                .with_frame_animation(false)
+                .with_settings(false),
+        );
+    #[cfg(feature = "audio")]
+    let preset = preset.with_sound_effects(false);
+    preset.build()
 }
 
-pub struct UiTimerSystem;
-
-impl<'a> System<'a> for UiTimerSystem {
-    type SystemData = (
-        ReadStorage<'a, UiTimer>,
-        WriteStorage<'a, UiText>,
-        Read<'a, Time>,
-    );
-    fn run(&mut self, (timers, mut texts, time): Self::SystemData) {
-        for (timer, mut text) in (&timers, &mut texts).join() {
-            let t = time.absolute_time_seconds() - timer.start;
-            text.text = t.to_string(); // Simply show seconds for now.
-        }
-    }
-}
+/*pub fn build_amethyst(game_data_builder: GameDataBuilder<'static,'static>, init_state: State<GameData<'static,'static>>) -> Result<Application<GameData<'static,'static>>>{
+    let resources_directory = format!("{}/assets/base", env!("CARGO_MANIFEST_DIR"));
+    let game_data = game_data_builder.build()?;
+    Application::build(resources_directory, init_state)?.build(game_data)
+}*/
 
 pub trait UiAutoText: Component {
     fn get_text(&self) -> String;
@@ -866,768 +2472,214 @@ fn run(&mut self, (follow_mouses,mut transforms, global_transforms, dimension,in
     }
 }
 
-#[derive(Deserialize)]
-pub struct LootTreeNode<R> {
-    pub chances: i32,
-    pub result: R,
-}
-
-#[derive(Deserialize)]
-pub struct LootTreeBuilder<R> {
-    pub nodes: Vec<LootTreeNode<R>>,
-}
-
-impl<R: Clone + 'static> LootTreeBuilder<R> {
-    pub fn new() -> Self {
-        LootTreeBuilder { nodes: vec![] }
-    }
-
-    pub fn build(self) -> LootTree<R> {
-        let mut f = LowerPartialFunction::new();
-        let mut accum = 0;
-        for n in self.nodes.into_iter() {
-            let tmp = n.chances;
-            f = f.with(accum, move |_| n.result.clone());
-            accum = accum + tmp;
-        }
-        LootTree {
-            partial_func: f.build(),
-            max: accum,
-        }
-    }
-}
-
-/// A loot tree based on the lower partial function construct.
-/// Each loot tree node has a chance associated with it.
-///
-/// Example:
-/// { chance: 5, result: "item1" }
-/// { chance: 2, result: "item2" }
-///
-/// Internally this becomes
-/// [0,infinite[ -> item1
-/// [5,infinite[ -> item2
-/// maximum = 7 exclusive (that means 6)
-///
-/// Chances will effectively be:
-/// [0,4] (5) -> item1
-/// [5,6] (2) -> item2
-pub struct LootTree<R> {
-    partial_func: LowerPartialFunction<i32, R>,
-    max: i32,
-}
-
-impl<R> LootTree<R> {
-    pub fn roll(&self) -> Option<R> {
-        let rng = thread_rng().gen_range(0, self.max);
-        self.partial_func.eval(rng)
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, new, Component)]
-pub struct FpsMovement {
-    /// The movement speed in units per second.
-    pub speed: f32,
-}
-
-/// The system that manages the fly movement.
-/// Generic parameters are the parameters for the InputHandler.
-#[derive(new)]
-pub struct FpsMovementSystemSimple<A, B> {
-    /// The name of the input axis to locally move in the x coordinates.
-    /// Left and right.
-    right_input_axis: Option<A>,
-    /// The name of the input axis to locally move in the z coordinates.
-    /// Forward and backward. Please note that -z is forward when defining your input configurations.
-    forward_input_axis: Option<A>,
-    _phantomdata: PhantomData<B>,
-}
-
-impl<'a, A, B> System<'a> for FpsMovementSystemSimple<A, B>
-where
-    A: Send + Sync + Hash + Eq + Clone + 'static,
-    B: Send + Sync + Hash + Eq + Clone + 'static,
-{
-    type SystemData = (
-        Read<'a, Time>,
-        WriteStorage<'a, Transform>,
-        Read<'a, InputHandler<A, B>>,
-        ReadStorage<'a, FpsMovement>,
-        WriteStorage<'a, ForceAccumulator<Vector3<f32>, Vector3<f32>>>,
-    );
-
-    fn run(&mut self, (time, transforms, input, tags, mut forces): Self::SystemData) {
-        let x = get_input_axis_simple(&self.right_input_axis, &input);
-        let z = get_input_axis_simple(&self.forward_input_axis, &input);
-
-        let dir = Vector3::new(x, 0.0, z);
-        if dir.magnitude() != 0.0 {
-            for (transform, tag, mut force) in (&transforms, &tags, &mut forces).join() {
-                let mut dir = transform.rotation * dir;
-                dir = dir.normalize();
-                force.add_force(dir * tag.speed * time.delta_seconds());
-            }
-        }
-    }
+/*pub struct NavigationButton{
+    pub target: fn() -> Trans,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Component)]
-pub struct RotationControl {
-    pub mouse_accum_x: f32,
-    pub mouse_accum_y: f32,
-}
+impl Component for NavigationButton{
+    type Storage = VecStorage<Self>;
+}*/
 
-/// The system that manages the view rotation.
-/// Controlled by the mouse.
-#[derive(Debug, new)]
-pub struct FPSRotationRhusicsSystem<A, B> {
-    sensitivity_x: f32,
-    sensitivity_y: f32,
-    _marker1: PhantomData<A>,
-    _marker2: PhantomData<B>,
-    #[new(default)]
-    event_reader: Option<ReaderId<Event>>,
-}
+/*
+  * = could do it in the engine directly
+  BHop controller
+  2D controllers
+  load asset by name ("images/player.png"), infer which one to load using asset override system (modding)
+  - {modname}/localisation/{player_lang}.txt
+  *http calls utils
+  item/inventory system
 
-impl<'a, A, B> System<'a> for FPSRotationRhusicsSystem<A, B>
-where
-    A: Send + Sync + Hash + Eq + Clone + 'static,
-    B: Send + Sync + Hash + Eq + Clone + 'static,
-{
-    type SystemData = (
-        Read<'a, EventChannel<Event>>,
-        WriteStorage<'a, Transform>,
-        WriteStorage<'a, BodyPose3<f32>>,
-        WriteStorage<'a, NextFrame<BodyPose3<f32>>>,
-        WriteStorage<'a, RotationControl>,
-        Read<'a, WindowFocus>,
-        Read<'a, HideCursor>,
-        ReadStorage<'a, FlyControlTag>,
-    );
+*/
 
-    fn run(
-        &mut self,
-        (
-            events,
-            mut transforms,
-            mut body_poses,
-            mut next_body_poses,
-            mut rotation_controls,
-            focus,
-            hide,
-            fly_controls,
-        ): Self::SystemData,
-    ) {
-        let focused = focus.is_focused;
-        for event in events.read(&mut self.event_reader.as_mut().unwrap()) {
-            if focused && hide.hide {
-                if let Event::DeviceEvent { ref event, .. } = *event {
-                    if let DeviceEvent::MouseMotion { delta: (x, y) } = *event {
-                        for (mut transform, mut rotation_control) in
-                            (&mut transforms, &mut rotation_controls).join()
-                        {
-                            rotation_control.mouse_accum_x -= x as f32 * self.sensitivity_x;
-                            rotation_control.mouse_accum_y += y as f32 * self.sensitivity_y;
-                            // Limit maximum vertical angle to prevent locking the quaternion and/or going upside down.
-                            rotation_control.mouse_accum_y =
-                                rotation_control.mouse_accum_y.max(-89.5).min(89.5);
-
-                            transform.rotation =
-                                Quaternion::from_angle_x(Deg(-rotation_control.mouse_accum_y));
-
-                            for (mut body_pose, mut next_body_pose, _) in
-                                (&mut body_poses, &mut next_body_poses, &fly_controls).join()
-                            {
-                                body_pose.set_rotation(Quaternion::from_angle_y(Deg(
-                                    rotation_control.mouse_accum_x,
-                                )));
-                                next_body_pose
-                                    .value
-                                    .set_rotation(Quaternion::from_angle_y(Deg(
-                                        rotation_control.mouse_accum_x
-                                    )));
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
+#[cfg(test)]
+mod test {
+    use *;
 
-    fn setup(&mut self, res: &mut Resources) {
-        Self::SystemData::setup(res);
-        self.event_reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    #[test]
+    fn indexed_rectangle_matches_triangle_list() {
+        let flat = gen_rectangle_vertices(2.0, 1.0);
+        let indexed = IndexedMesh::from_triangle_list(&flat);
+        assert_eq!(indexed.vertices.len(), 4);
+        assert_eq!(indexed.indices.len(), 6);
+        assert_eq!(indexed.to_triangle_list(), flat);
+        assert_eq!(
+            indexed,
+            gen_rectangle_indexed(2.0, 1.0, UvMapping::PlanarFit)
+        );
     }
-}
-
-#[derive(Debug, Clone, Default, Serialize, Deserialize, new, Component)]
-pub struct Grounded {
-    #[new(value = "false")]
-    pub ground: bool,
-    #[new(default)]
-    pub since: f64,
-    pub distance_check: f32,
-    /// Checks if the selected entity collides with the ground.
-    #[serde(skip)]
-    pub watch_entity: Option<Entity>,
-}
-
-/// T: ObjectType for collider checks
-#[derive(new)]
-pub struct GroundCheckerSystem<T> {
-    pub collider_types: Vec<T>,
-    #[new(default)]
-    contact_reader: Option<ReaderId<ContactEvent<Entity, Point3<f32>>>>,
-}
-
-impl<'a, T: Component + PartialEq> System<'a> for GroundCheckerSystem<T> {
-    type SystemData = (
-        Entities<'a>,
-        ReadStorage<'a, Transform>,
-        WriteStorage<'a, Grounded>,
-        ReadStorage<'a, T>,
-        Read<'a, DynamicBoundingVolumeTree3<f32>>,
-        Read<'a, Time>,
-        Read<'a, EventChannel<ContactEvent<Entity, Point3<f32>>>>,
-    );
 
-    fn setup(&mut self, mut res: &mut Resources) {
-        Self::SystemData::setup(&mut res);
-        /*self.contact_reader = Some(
-            res.fetch_mut::<EventChannel<ContactEvent<Entity, Point3<f32>>>>()
-                .register_reader(),
-        );*/
+    #[test]
+    fn indexed_circle_shares_center() {
+        let circle = generate_circle_indexed(1.0, 16, UvMapping::PlanarFit);
+        assert_eq!(circle.vertices.len(), 17);
+        assert_eq!(circle.triangle_count(), 16);
+        let deduped = IndexedMesh::from_triangle_list(&generate_circle_vertices(1.0, 16));
+        assert_eq!(deduped.vertices.len(), 17);
     }
 
-    fn run(
-        &mut self,
-        (entities, transforms, mut grounded, objecttypes, tree, time, contacts): Self::SystemData,
-    ) {
-        let down = -Vector3::unit_y();
-        for (entity, transform, mut grounded) in (&*entities, &transforms, &mut grounded).join() {
-            let mut ground = false;
-
-            let ray = Ray3::new(Point3::from_vec(transform.translation), down);
-
-            // For all in ray
-            for (v, p) in query_ray(&*tree, ray) {
-                // Not self and close enough
-                if v.value != entity
-                    && (transform.translation - Vector3::new(p.x, p.y, p.z)).magnitude()
-                        <= grounded.distance_check
-                {
-                    // If we can jump off that type of collider
-                    if let Some(obj_type) = objecttypes.get(v.value) {
-                        if self.collider_types.contains(obj_type) {
-                            ground = true;
-                        }
-                    }
-                    //info!("hit bounding volume of {:?} at point {:?}", v.value, p);
-                }
-            }
-
-            /*info!("run {:?}", entity);
-            // Check for secondary collider if any
-            for contact in contacts.read(&mut self.contact_reader.as_mut().unwrap()) {
-                info!("Contact {:?} -> {:?}",contact.bodies.0, contact.bodies.1);
-                // Here because we need to empty the contacts EventChannel
-                if let Some(secondary) = grounded.watch_entity {
-                    info!("Secondary");
-                    if contact.bodies.0 == entity || contact.bodies.1 == entity {
-                        // We hit our player... let's ignore that.
-                        continue;
-                    }
-                    info!("tmp1");
-                    if contact.bodies.0 != secondary && contact.bodies.1 != secondary {
-                        // This has nothing to do with the secondary collider. Skip!
-                        continue;
-                    }
-                    info!("type check");
-                    let type1 = objecttypes.get(contact.bodies.0);
-                    let type2 = objecttypes.get(contact.bodies.1);
-
-                    if type1.is_none() || type2.is_none() {
-                        continue;
-                    }
-                    info!("good to go");
-                    // If we can jump off that type of collider
-                    if self.collider_types.contains(type1.unwrap())
-                        || self.collider_types.contains(type2.unwrap())
-                    {
-                        ground = true;
-                    }
-                }
-            }*/
-
-            if ground && !grounded.ground {
-                // Just grounded
-                grounded.since = time.absolute_time_seconds();
-            }
-            grounded.ground = ground;
+    #[test]
+    fn circle_uvs_fit_texture() {
+        let circle = generate_circle_indexed(2.0, 8, UvMapping::PlanarFit);
+        assert_eq!(circle.vertices[0].tex_coord, [0.5, 0.5]);
+        assert_eq!(circle.vertices[1].tex_coord, [1.0, 0.5]);
+        for v in &circle.vertices {
+            assert!(v.tex_coord.iter().all(|c| *c >= 0.0 && *c <= 1.0));
         }
     }
-}
 
-#[derive(Default, Component, new)]
-pub struct Jump {
-    pub absolute: bool,
-    pub check_ground: bool,
-    pub jump_force: f32,
-    pub auto_jump: bool,
-    #[new(value = "0.3333")]
-    pub jump_cooldown: f64,
-    #[new(value = "0.1")]
-    pub input_cooldown: f64,
-    /// Multiplier. Time can go in the negatives.
-    #[new(default)]
-    pub jump_timing_boost: Option<PartialFunction<f64, f32>>,
-    #[new(default)]
-    pub last_jump: f64,
-    #[new(default)]
-    pub last_jump_offset: f64,
-}
-
-#[derive(Default)]
-pub struct JumpSystem {
-    /// The last time the system considered a valid jump input.
-    last_logical_press: f64,
-    /// Was the jump key pressed last frame?
-    input_hold: bool,
-    /// The last time we physically pressed the jump key.
-    last_physical_press: f64,
-}
-
-impl<'a> System<'a> for JumpSystem {
-    type SystemData = (
-        Entities<'a>,
-        WriteStorage<'a, Grounded>,
-        WriteStorage<'a, Jump>,
-        Read<'a, Time>,
-        Read<'a, InputHandler<String, String>>,
-        WriteStorage<'a, ForceAccumulator<Vector3<f32>, Vector3<f32>>>,
-        WriteStorage<'a, NextFrame<Velocity3<f32>>>,
-    );
-
-    fn run(
-        &mut self,
-        (entities, mut grounded, mut jumps, time, input, mut forces, mut velocities): Self::SystemData,
-){
-        if let Some(true) = input.action_is_down("jump") {
-            if !self.input_hold {
-                // We just started pressing the key. Registering time.
-                self.last_physical_press = time.absolute_time_seconds();
-                self.input_hold = true;
-            }
-
-            for (entity, mut jump, mut force, mut velocity) in
-                (&*entities, &mut jumps, &mut forces, &mut velocities).join()
-            {
-                // Holding the jump key on a non-auto jump controller.
-                if self.input_hold && !jump.auto_jump {
-                    continue;
-                }
-
-                // The last time we jumped wasn't long enough ago
-                if time.absolute_time_seconds() - self.last_logical_press < jump.input_cooldown {
-                    continue;
-                }
-                self.last_logical_press = time.absolute_time_seconds();
-
-                // If we need to check for it, verify that we are on the ground.
-                let mut grounded_since = time.absolute_time_seconds();
-                if jump.check_ground {
-                    if let Some(ground) = grounded.get(entity) {
-                        if !ground.ground {
-                            continue;
-                        }
-                        grounded_since = ground.since;
-                    } else {
-                        continue;
-                    }
-                }
-
-                if time.absolute_time_seconds() - jump.last_jump > jump.jump_cooldown {
-                    // Jump!
-                    jump.last_jump = time.absolute_time_seconds();
-                    // Offset for jump. Positive = time when we jumped AFTER we hit the ground.
-                    jump.last_jump_offset = grounded_since - self.last_physical_press;
-
-                    let multiplier = if let Some(ref curve) = jump.jump_timing_boost {
-                        curve.eval(jump.last_jump_offset).unwrap_or(1.0)
-                    } else {
-                        1.0
-                    };
-
-                    if !jump.absolute {
-                        force.add_force(Vector3::<f32>::unit_y() * jump.jump_force * multiplier);
-                    } else {
-                        let (x, z) = {
-                            let v = velocity.value.linear();
-                            (v.x, v.z)
-                        };
-                        velocity
-                            .value
-                            .set_linear(Vector3::new(x, jump.jump_force, z));
-                    }
-                }
-                if let Some(ref mut ground) = grounded.get_mut(entity) {
-                    ground.ground = false;
-                }
-            }
-        } else {
-            // The jump key was released.
-            self.input_hold = false;
-        }
+    #[test]
+    fn uv_mapping_modes() {
+        let tiled = gen_rectangle_indexed(4.0, 2.0, UvMapping::Tiled { repeat: 0.5 });
+        assert_eq!(tiled.vertices[2].tex_coord, [2.0, 1.0]);
+        let atlas = gen_rectangle_indexed(
+            4.0,
+            2.0,
+            UvMapping::Atlas {
+                min: [0.25, 0.5],
+                max: [0.5, 1.0],
+            },
+        );
+        assert_eq!(atlas.vertices[0].tex_coord, [0.25, 0.5]);
+        assert_eq!(atlas.vertices[2].tex_coord, [0.5, 1.0]);
+
+        let tiled = gen_rectangle_vertices_with_uv(4.0, 2.0, UvMapping::Tiled { repeat: 0.5 });
+        assert!(tiled.iter().any(|v| v.tex_coord == [2.0, 1.0]));
+        let circle = generate_circle_vertices_with_uv(1.0, 4, UvMapping::Tiled { repeat: 1.0 });
+        assert_eq!(circle.len(), 12);
+        assert!(circle.iter().any(|v| v.tex_coord == [2.0, 1.0]));
     }
-}
 
-/// The settings controlling how the entity controlled by the `BhopMovementSystem` will behave.
-/// This is a component that you should add on the entity.
-#[derive(Serialize, Deserialize, Debug, Clone, Component, new)]
-pub struct BhopMovement3D {
-    /// False = Forces, True = Velocity
-    pub absolute: bool,
-    /// Use world coordinates XYZ.
-    #[new(default)]
-    pub absolute_axis: bool,
-    /// Negates the velocity when pressing the key opposite to the current velocity.
-    /// Effectively a way to instantly stop, even at high velocities.
-    #[new(default)]
-    pub counter_impulse: bool,
-    /// Acceleration in unit/s² while on the ground.
-    pub accelerate_ground: f32,
-    /// Acceleration in unit/s² while in the air.
-    pub accelerate_air: f32,
-    /// The maximum ground velocity.
-    pub max_velocity_ground: f32,
-    /// The maximum air velocity.
-    pub max_velocity_air: f32,
-    /// Enables accelerating over maximumVelocity by airstrafing. Bunnyhop in a nutshell.
-    pub allow_projection_acceleration: bool,
-}
-
-/// The system that manages the first person movements (with added projection acceleration capabilities).
-/// Generic parameters are the parameters for the InputHandler.
-#[derive(new)]
-pub struct BhopMovementSystem<A, B> {
-    /// The name of the input axis to locally move in the x coordinates.
-    right_input_axis: Option<A>,
-    /// The name of the input axis to locally move in the z coordinates.
-    forward_input_axis: Option<A>,
-    phantom_data: PhantomData<B>,
-}
-
-impl<'a, A, B> System<'a> for BhopMovementSystem<A, B>
-where
-    A: Send + Sync + Hash + Eq + Clone + 'static,
-    B: Send + Sync + Hash + Eq + Clone + 'static,
-{
-    type SystemData = (
-        Read<'a, Time>,
-        Read<'a, InputHandler<A, B>>,
-        ReadStorage<'a, Transform>,
-        ReadStorage<'a, BhopMovement3D>,
-        ReadStorage<'a, Grounded>,
-        WriteStorage<'a, NextFrame<Velocity3<f32>>>,
-    );
-
-    fn run(
-        &mut self,
-        (time, input, transforms, movements, groundeds, mut velocities): Self::SystemData,
-    ) {
-        let x = get_input_axis_simple(&self.right_input_axis, &input);
-        let z = get_input_axis_simple(&self.forward_input_axis, &input);
-        let input = Vector2::new(x, z);
-
-        if input.magnitude() != 0.0 {
-            for (transform, movement, grounded, mut velocity) in
-                (&transforms, &movements, &groundeds, &mut velocities).join()
-            {
-                let (acceleration, max_velocity) = if grounded.ground {
-                    (movement.accelerate_ground, movement.max_velocity_ground)
-                } else {
-                    (movement.accelerate_air, movement.max_velocity_air)
-                };
-
-                // Global to local coords.
-                let mut relative = SquareMatrix::invert(Basis3::from(transform.rotation).as_ref())
-                    .unwrap()
-                    * velocity.value.linear();
-
-                let new_vel_rel = if movement.absolute {
-                    // Absolute = We immediately set the maximum velocity without checking the max speed.
-                    Vector3::new(input.x * acceleration, relative.y, input.y * acceleration)
-                } else {
-                    let mut wish_vel = relative;
-
-                    if movement.counter_impulse {
-                        wish_vel = counter_impulse(input, wish_vel);
-                    }
-
-                    wish_vel = accelerate_vector(
-                        time.delta_seconds(),
-                        input,
-                        wish_vel,
-                        acceleration,
-                        max_velocity,
-                    );
-                    if !movement.allow_projection_acceleration {
-                        wish_vel = limit_velocity(wish_vel, max_velocity);
-                    }
-
-                    wish_vel
-                };
-
-                // Global to local coords;
-                let new_vel = transform.rotation * new_vel_rel;
-
-                // Assign the new velocity to the player
-                velocity.value.set_linear(new_vel);
-            }
-        }
+    fn triangulated_area(shape: &Shape2D) -> f32 {
+        let points = shape.points();
+        shape
+            .triangulate()
+            .chunks(3)
+            .map(|t| {
+                cross2(
+                    points[t[0] as usize],
+                    points[t[1] as usize],
+                    points[t[2] as usize],
+                ) / 2.0
+            }).sum()
     }
-}
 
-/// The way friction is applied.
-#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
-pub enum FrictionMode {
-    /// The velocity is reduced by a fixed amount each second (deceleration).
-    Linear,
-    /// The velocity is reduced by a fraction of the current velocity.
-    /// A value of 0.2 means that approximatively 20% of the speed will be lost each second.
-    /// Since it is not calculated as an integration but as discrete values, the actual slowdown will vary slightly from case to case.
-    Percent,
-}
-
-/// Component you add to your entities to apply a ground friction.
-/// What the friction field does is dependent on the choosen `FrictionMode`.
-#[derive(Serialize, Deserialize, Clone, Debug, Component, new)]
-pub struct GroundFriction3D {
-    /// The amount of friction speed loss by second.
-    pub friction: f32,
-    /// The way friction is applied.
-    pub friction_mode: FrictionMode,
-    /// The time to wait after touching the ground before applying the friction.
-    pub ground_time_before_apply: f64,
-}
-
-/// Applies friction (slows the velocity down) according to the `GroundFriction3D` component of your entity.
-/// Your entity also needs to have a `Grounded` component (and the `GroundCheckerSystem` added to your dispatcher) to detect the ground.
-/// It also needs to have a NextFrame<Velocity3<f32>> component. This is added automatically by rhusics when creating a dynamic physical entity.
-pub struct GroundFrictionSystem;
-
-impl<'a> System<'a> for GroundFrictionSystem {
-    type SystemData = (
-        Read<'a, Time>,
-        ReadStorage<'a, Grounded>,
-        ReadStorage<'a, GroundFriction3D>,
-        WriteStorage<'a, NextFrame<Velocity3<f32>>>,
-    );
+    #[test]
+    fn triangulate_concave_with_hole() {
+        let l_shape = Shape2D::new(
+            vec![[0., 0.], [2., 0.], [2., 1.], [1., 1.], [1., 2.], [0., 2.]],
+            vec![],
+        );
+        assert_eq!(l_shape.triangulate().len(), 12);
+        assert!(value_near(triangulated_area(&l_shape), 3.0, 0.001));
 
-    fn run(&mut self, (time, groundeds, frictions, mut velocities): Self::SystemData) {
-        fn apply_friction_single(v: f32, friction: f32) -> f32 {
-            if v.abs() <= friction {
-                return 0.0;
-            }
-            v - friction
-        }
-        for (grounded, friction, mut velocity) in (&groundeds, &frictions, &mut velocities).join() {
-            if grounded.ground
-                && time.absolute_time_seconds() - grounded.since
-                    >= friction.ground_time_before_apply
-            {
-                let (x, y, z) = {
-                    let v = velocity.value.linear();
-                    (v.x, v.y, v.z)
-                };
-                match friction.friction_mode {
-                    FrictionMode::Linear => {
-                        let slowdown = friction.friction * time.delta_seconds();
-                        velocity.value.set_linear(Vector3::new(
-                            apply_friction_single(x, slowdown),
-                            y,
-                            apply_friction_single(z, slowdown),
-                        ));
-                    }
-                    FrictionMode::Percent => {
-                        let coef = friction.friction * time.delta_seconds();
-                        velocity.value.set_linear(Vector3::new(
-                            apply_friction_single(x, x * coef),
-                            y,
-                            apply_friction_single(z, z * coef),
-                        ));
-                    }
-                }
-            }
-        }
+        let framed = Shape2D::new(
+            rectangle_contour([0., 0.], 4., 4.),
+            vec![rectangle_contour([0., 0.], 2., 2.)],
+        );
+        assert_eq!(framed.triangulate().len(), 24);
+        assert!(value_near(triangulated_area(&framed), 12.0, 0.001));
     }
-}
 
-/// Accelerates the given `relative` vector by the given `acceleration` and `input`.
-/// The `maximum_velocity` is only taken into account for the projection of the acceleration vector on the `relative` vector.
-/// This allows going over the speed limit by performing what is called a "strafe".
-/// If your velocity is forward and have an input accelerating you to the right, the projection of
-/// the acceleration vector over your current velocity will be 0. This means that the acceleration vector will be applied fully,
-/// even if this makes the resulting vector's magnitude go over `max_velocity`.
-pub fn accelerate_vector(
-    delta_time: f32,
-    input: Vector2<f32>,
-    rel: Vector3<f32>,
-    acceleration: f32,
-    max_velocity: f32,
-) -> Vector3<f32> {
-    let mut o = rel;
-    let input3 = Vector3::new(input.x, 0.0, input.y);
-    let rel_flat = Vector3::new(rel.x, 0.0, rel.z);
-    if input3.magnitude() > 0.0 {
-        let proj = rel_flat.dot(input3.normalize());
-        let mut accel_velocity = acceleration * delta_time as f32;
-        if proj + accel_velocity > max_velocity {
-            accel_velocity = max_velocity - proj;
-        }
-        if accel_velocity > 0.0 {
-            let add_speed = input3 * accel_velocity;
-            o += add_speed;
-        }
+    #[test]
+    fn shape_builder_booleans() {
+        let merged = ShapeBuilder::new()
+            .union(rectangle_contour([0., 0.], 2., 2.))
+            .union(rectangle_contour([1., 1.], 2., 2.));
+        assert_eq!(merged.shapes().len(), 1);
+        assert!(value_near(merged.shapes()[0].area(), 7.0, 0.001));
+
+        let cut = ShapeBuilder::new()
+            .union(rectangle_contour([0., 0.], 4., 4.))
+            .difference(rectangle_contour([0., 0.], 2., 2.));
+        assert_eq!(cut.shapes().len(), 1);
+        assert_eq!(cut.shapes()[0].holes.len(), 1);
+        assert!(value_near(cut.shapes()[0].area(), 12.0, 0.001));
+
+        let split = ShapeBuilder::new()
+            .union(rectangle_contour([0., 0.], 4., 2.))
+            .difference(rectangle_contour([0., 0.], 1., 4.));
+        assert_eq!(split.shapes().len(), 2);
     }
-    o
-}
 
-/// Completely negates the velocity of a specific axis if an input is performed in the opposite direction.
-pub fn counter_impulse(input: Vector2<f32>, relative_velocity: Vector3<f32>) -> Vector3<f32> {
-    let mut o = relative_velocity;
-    if (input.x < 0.0 && relative_velocity.x > 0.001)
-        || (input.x > 0.0 && relative_velocity.x < -0.001)
-    {
-        o = Vector3::new(0.0, relative_velocity.y, relative_velocity.z);
-    }
-    if (input.y < 0.0 && relative_velocity.z < -0.001)
-        || (input.y > 0.0 && relative_velocity.z > 0.001)
-    {
-        o = Vector3::new(relative_velocity.x, relative_velocity.y, 0.0);
+    #[test]
+    fn shape_builder_extrude() {
+        let mesh = ShapeBuilder::new()
+            .union(rectangle_contour([0., 0.], 2., 2.))
+            .extrude(1.0);
+        // Two caps of 4 vertices and 4 side quads.
+        assert_eq!(mesh.vertices.len(), 24);
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_eq!(mesh.vertices[8].normal, [0.0, -1.0, 0.0]);
     }
-    o
-}
 
-/// Limits the total velocity so that its magnitude doesn't exceed `maximum_velocity`.
-/// If you are using the `accelerate_vector` function, calling this will ensure that air strafing
-/// doesn't allow you to go over the maximum velocity, while still keeping fluid controls.
-pub fn limit_velocity(vec: Vector3<f32>, maximum_velocity: f32) -> Vector3<f32> {
-    let v_flat = Vector2::new(vec.x, vec.z).magnitude();
-    if v_flat > maximum_velocity && maximum_velocity != 0.0 {
-        let ratio = maximum_velocity / v_flat;
-        return Vector3::new(vec.x * ratio, vec.y, vec.z * ratio);
+    #[test]
+    fn terrain_grid() {
+        let heightmap = Heightmap::from_fn(4, 4, |_, _| 0.5);
+        let settings = TerrainSettings::new([10., 20.], 4., [5, 10]);
+        let terrain = generate_terrain(&heightmap, &settings);
+        assert_eq!(terrain.vertices.len(), 6 * 11);
+        assert_eq!(terrain.triangle_count(), 5 * 10 * 2);
+        assert_eq!(terrain.vertices[0].position, [-5., 2., -10.]);
+        assert!(terrain.vertices.iter().all(|v| v.normal == [0., 1., 0.]));
     }
-    vec
-}
-
-/*pub struct NavigationButton{
-    pub target: fn() -> Trans,
-}
-
-impl Component for NavigationButton{
-    type Storage = VecStorage<Self>;
-}*/
 
-
-/// Discord Rich Presence wrapper around discord_rpc_client
-/// Currently errors are not exposed by the library, so I use the log crate
-/// to display errors and only return Result<T, ()> from the methods.
-/// 
-/// Make sure to properly create your app here: https://discordapp.com/developers/applications
-/// 
-/// Usage: 
-/// ```rs
-/// fn init_discord_rich_presence() -> Result<DiscordRichPresence,()> {
-///     DiscordRichPresence::new(498979571933380609, "Main Menu", Some("large_image"), Some("Hoppin World"), None, None);
-/// }
-/// ```
-pub struct DiscordRichPresence {
-    pub rpc: Arc<Mutex<DiscordClient>>,
-    state: String,
-    large_image: Option<String>,
-    large_image_text: Option<String>,
-    small_image: Option<String>,
-    small_image_text: Option<String>,
-}
-
-impl DiscordRichPresence {
-    pub fn new(app_id: u64,
-        state: String,
-        large_image: Option<String>,
-        large_image_text: Option<String>,
-        small_image: Option<String>,
-        small_image_text: Option<String>) -> std::result::Result<Self,()> {
-        let mut rpc = DiscordClient::new(app_id);
-        if let Err(e) = rpc {
-            error!("Failed to create discord rich presence client: {:?}", e);
-            return Err(());
-        }
-        rpc.as_mut().unwrap().start();
-        let mut drp = DiscordRichPresence {
-            rpc: Arc::new(Mutex::new(rpc.unwrap())),
-            state,
-            large_image,
-            large_image_text,
-            small_image,
-            small_image_text,
-        };
-        drp.update();
-        Ok(drp)
-    }
-    pub fn set_state(&mut self, state: String) {
-        self.state = state;
-        self.update();
+    #[test]
+    fn noise_heightmap_is_deterministic() {
+        let noise = NoiseSettings::new(NoiseType::Perlin, 42, 8.0);
+        let heightmap = Heightmap::from_noise(32, 32, &noise);
+        assert_eq!(heightmap, Heightmap::from_noise(32, 32, &noise));
+        assert!(heightmap.heights.iter().all(|h| *h >= 0.0 && *h <= 1.0));
     }
 
-    pub fn update(&mut self) {
-        if let Err(e) = self.rpc.lock().unwrap().set_activity(|a| 
-            a.state(self.state.clone())
-            .assets(|ass| {
-                let mut tmp = ass;
-                if let Some(ref t) = self.large_image {
-                    tmp = tmp.large_image(t.clone());
-                }
-                if let Some(ref t) = self.large_image_text {
-                    tmp = tmp.large_text(t.clone());
-                }
-                if let Some(ref t) = self.small_image {
-                    tmp = tmp.small_image(t.clone());
-                }
-                if let Some(ref t) = self.small_image_text {
-                    tmp = tmp.small_text(t.clone());
-                }
-                tmp
-            })
-        ){
-            error!("Failed to set discord rich presence state: {}", e);
-        }
+    #[test]
+    fn invalid_heightmaps() {
+        assert!(Heightmap::new(2, 2, vec![0.0; 3]).is_none());
+        let empty = Heightmap::new(0, 4, vec![]).unwrap();
+        assert_eq!(empty.get(0, 0), None);
+        assert_eq!(empty.sample(0.5, 0.5), 0.0);
+        let map = Heightmap::new(2, 1, vec![0.25, 0.75]).unwrap();
+        assert_eq!(map.get(5, 5), Some(0.75));
     }
-}
 
-impl Drop for DiscordRichPresence {
-    fn drop(&mut self) {
-        if let Err(e) = self.rpc.lock().unwrap().clear_activity() {
-            eprintln!("Failed to clear discord rich presence activity {:?}", e);
-        }
+    #[test]
+    fn color_quantization() {
+        assert_eq!(
+            ColorMaterialCache::quantize([1.0, 0.5, 0.0, 2.0]),
+            [255, 128, 0, 255]
+        );
+        assert_eq!(
+            ColorMaterialCache::quantize([0.5, 0.5, 0.5, 1.0]),
+            ColorMaterialCache::quantize([0.501, 0.499, 0.5, 1.0])
+        );
     }
-}
 
-/// Changes the discord rich presence state, if present in the world.
-pub fn set_discord_state(state: String, world: &mut World) {
-    if let Some(mut drp) = world.res.try_fetch_mut::<DiscordRichPresence>() {
-        drp.set_state(state);
+    #[test]
+    fn procedural_textures() {
+        let white = [1.0, 1.0, 1.0, 1.0];
+        let black = [0.0, 0.0, 0.0, 1.0];
+
+        let checker = checkerboard_texture([4, 4], 2, white, black);
+        assert_eq!(checker.pixels.len(), 4 * 4 * 4);
+        assert_eq!(checker.pixel(0, 0), [255, 255, 255, 255]);
+        assert_eq!(checker.pixel(2, 0), [0, 0, 0, 255]);
+        assert_eq!(checker.pixel(2, 2), [255, 255, 255, 255]);
+
+        let grid = grid_texture([8, 8], 4, 1, black, white);
+        assert_eq!(grid.pixel(4, 2), [255, 255, 255, 255]);
+        assert_eq!(grid.pixel(2, 2), [0, 0, 0, 255]);
+
+        let gradient = linear_gradient_texture([4, 1], [1.0, 0.0], black, white);
+        assert_eq!(gradient.pixel(0, 0), [32, 32, 32, 255]);
+        assert_eq!(gradient.pixel(3, 0), [223, 223, 223, 255]);
+
+        let radial = radial_gradient_texture([5, 5], [0.5, 0.5], 0.5, white, black);
+        assert_eq!(radial.pixel(2, 2), [255, 255, 255, 255]);
+        assert_eq!(radial.pixel(0, 0), [0, 0, 0, 255]);
+
+        let noise = NoiseSettings::new(NoiseType::Value, 7, 4.0);
+        assert_eq!(
+            noise_texture([16, 16], &noise, black, white),
+            noise_texture([16, 16], &noise, black, white)
+        );
     }
 }
-
-
-/*
-  * = could do it in the engine directly
-  BHop controller
-  2D controllers
-  load asset by name ("images/player.png"), infer which one to load using asset override system (modding)
-  - {modname}/localisation/{player_lang}.txt
-  *http calls utils
-  item/inventory system
-
-*/
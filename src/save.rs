@@ -0,0 +1,2475 @@
+use amethyst::core::cgmath::{Quaternion, Vector3};
+use amethyst::core::timing::Time;
+use amethyst::core::Transform;
+use amethyst::ecs::{
+    Builder, Component, Entity, Join, NullStorage, Read, Resources, System, SystemData, World,
+    Write,
+};
+#[cfg(feature = "dirs")]
+use dirs;
+use dirty::Dirty;
+#[cfg(feature = "gzip")]
+use flate2::read::GzDecoder;
+#[cfg(feature = "gzip")]
+use flate2::write::GzEncoder;
+#[cfg(feature = "gzip")]
+use flate2::Compression;
+use get_working_dir;
+use ron;
+use ron::ser::PrettyConfig;
+use ron::value::{Number, Value};
+use serde::de::value::{Error as ValueError, MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+use serde::ser::{self, Serializer};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "cbor")]
+use serde_cbor;
+#[cfg(feature = "json")]
+use serde_json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read as IORead, Write as IOWrite};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// If the tracked resource changes, this will be checked to make sure it is a proper time to save.
+/// Use `set_save_ready(false)` to hold the saves back, for example during a cutscene.
+/// The pending changes are saved once it is set back to true.
+pub trait ShouldSave {
+    fn save_ready(&self) -> bool;
+    fn set_save_ready(&mut self, ready: bool);
+}
+
+/// A save that couldn't be loaded from its main file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredSave {
+    /// Path of the main save file.
+    pub save_path: String,
+    /// The backup the data was restored from.
+    /// None if no backup was valid and the default value was used instead.
+    pub backup_path: Option<String>,
+}
+
+/// Lists the saves that were restored from a backup while loading.
+/// Every `AutoSaveSystem` adds to it during setup, so you can check it
+/// once the game is started and tell the player about it.
+///
+/// The `AutoSaveSystem`s only read this resource, so they can run in parallel.
+#[derive(Debug, Default)]
+pub struct SaveRecovery {
+    recovered: Mutex<Vec<RecoveredSave>>,
+}
+
+impl SaveRecovery {
+    /// Records a recovered save.
+    pub fn push(&self, recovered: RecoveredSave) {
+        self.recovered.lock().unwrap().push(recovered);
+    }
+
+    /// Whether any save was recovered.
+    pub fn happened(&self) -> bool {
+        !self.recovered.lock().unwrap().is_empty()
+    }
+
+    /// The recovery that happened for this save file, if any.
+    pub fn get(&self, save_path: &str) -> Option<RecoveredSave> {
+        self.recovered
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|r| r.save_path == save_path)
+            .cloned()
+    }
+
+    /// All the recovered saves, in the order they were loaded.
+    pub fn recovered(&self) -> Vec<RecoveredSave> {
+        self.recovered.lock().unwrap().clone()
+    }
+}
+
+/// A function upgrading saved data from one version to the next.
+pub type SaveMigration = Box<Fn(Value) -> Result<Value, String> + Send + Sync>;
+
+/// The migrations applied to saves made with older versions of the saved struct.
+/// The current version is the number of migrations. Saves made before versioning are version 0.
+///
+/// Migrations operate on the generic RON `Value` of the saved data, where structs are maps
+/// with the field names as string keys.
+///
+/// Example:
+/// ```rs
+/// SaveMigrations::new().with(0, |value| match value {
+///     Value::Map(mut map) => {
+///         map.insert(Value::String("name".to_string()), Value::String("Player".to_string()));
+///         Ok(Value::Map(map))
+///     }
+///     _ => Err("Expected a struct.".to_string()),
+/// })
+/// ```
+#[derive(Default)]
+pub struct SaveMigrations {
+    migrations: Vec<SaveMigration>,
+}
+
+impl SaveMigrations {
+    pub fn new() -> Self {
+        SaveMigrations::default()
+    }
+
+    /// Adds the migration from `version` to `version + 1`.
+    /// Migrations have to be added in order, starting from version 0.
+    pub fn with<F>(mut self, version: u32, migration: F) -> Self
+    where
+        F: Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        assert_eq!(
+            version as usize,
+            self.migrations.len(),
+            "Migrations have to be added in order, starting from version 0."
+        );
+        self.migrations.push(Box::new(migration));
+        self
+    }
+
+    /// The current version of the saved data.
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+
+    /// Upgrades data saved at `version` to the current version.
+    pub fn migrate(&self, mut value: Value, version: u32) -> Result<Value, String> {
+        if version > self.version() {
+            return Err(format!(
+                "The save version {} is newer than the current version {}.",
+                version,
+                self.version()
+            ));
+        }
+        for (from, migration) in self.migrations.iter().enumerate().skip(version as usize) {
+            value = migration(value)
+                .map_err(|e| format!("Migration from version {} failed: {}", from, e))?;
+        }
+        Ok(value)
+    }
+}
+
+/// How save files are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SaveFormat {
+    /// RON on a single line.
+    Ron,
+    /// Indented RON, easier to read and edit by hand.
+    RonPretty,
+    #[cfg(feature = "json")]
+    Json,
+    /// CBOR, a compact binary format.
+    #[cfg(feature = "cbor")]
+    Binary,
+}
+
+impl Default for SaveFormat {
+    fn default() -> Self {
+        SaveFormat::Ron
+    }
+}
+
+impl SaveFormat {
+    /// Picks the format from the extension of the file, ignoring a trailing `.gz`.
+    /// `.json` is Json, `.bin` and `.cbor` are Binary and anything else is Ron.
+    /// Without the `json` or `cbor` feature, those extensions are Ron too.
+    pub fn from_path(path: &str) -> Self {
+        let path = if path.ends_with(".gz") {
+            &path[..path.len() - 3]
+        } else {
+            path
+        };
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "json")]
+            Some("json") => SaveFormat::Json,
+            #[cfg(feature = "cbor")]
+            Some("bin") | Some("cbor") => SaveFormat::Binary,
+            _ => SaveFormat::Ron,
+        }
+    }
+
+    /// Guesses the format of existing data.
+    pub fn detect(data: &[u8]) -> Self {
+        match ::std::str::from_utf8(data) {
+            #[cfg(feature = "json")]
+            Ok(text)
+                if text.trim().starts_with('{')
+                    && serde_json::from_slice::<serde_json::Value>(data).is_ok() =>
+            {
+                SaveFormat::Json
+            }
+            Ok(_) => SaveFormat::Ron,
+            #[cfg(feature = "cbor")]
+            Err(_) => SaveFormat::Binary,
+            // Decoding it as RON reports that the data isn't valid.
+            #[cfg(not(feature = "cbor"))]
+            Err(_) => SaveFormat::Ron,
+        }
+    }
+
+    pub fn encode<S: Serialize>(&self, value: &S) -> Result<Vec<u8>, String> {
+        match *self {
+            SaveFormat::Ron => ron::ser::to_string(value)
+                .map(String::into_bytes)
+                .map_err(|e| e.to_string()),
+            SaveFormat::RonPretty => ron::ser::to_string_pretty(value, PrettyConfig::default())
+                .map(String::into_bytes)
+                .map_err(|e| e.to_string()),
+            #[cfg(feature = "json")]
+            SaveFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            #[cfg(feature = "cbor")]
+            SaveFormat::Binary => serde_cbor::to_vec(value).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn decode<D: DeserializeOwned>(&self, data: &[u8]) -> Result<D, String> {
+        match *self {
+            SaveFormat::Ron | SaveFormat::RonPretty => ::std::str::from_utf8(data)
+                .map_err(|e| e.to_string())
+                .and_then(|text| ron::de::from_str(text).map_err(|e| e.to_string())),
+            #[cfg(feature = "json")]
+            SaveFormat::Json => serde_json::from_slice(data).map_err(|e| e.to_string()),
+            #[cfg(feature = "cbor")]
+            SaveFormat::Binary => serde_cbor::from_slice(data).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Gzip compresses the data.
+#[cfg(feature = "gzip")]
+pub fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Decompresses gzip compressed data. Other data is returned as is.
+/// Without the `gzip` feature, compressed data is an error.
+pub fn decompress(data: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !data.starts_with(&GZIP_MAGIC) {
+        return Ok(data);
+    }
+    inflate(&data)
+}
+
+#[cfg(feature = "gzip")]
+fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn inflate(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "The data is gzip compressed but the gzip feature is disabled.",
+    ))
+}
+
+/// What is actually written to the save files.
+#[derive(Serialize, Deserialize)]
+struct SaveEnvelope<T> {
+    version: u32,
+    data: T,
+}
+
+/// Turns a migrated `Value` back into the saved struct.
+/// `Value` stores every number as a float, so whole numbers are given as integers.
+/// Enum variants are read from their name, or from a map of their name to their data.
+struct ValueDeserializer(Value);
+
+impl<'de> IntoDeserializer<'de, ValueError> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self.0 {
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Char(c) => visitor.visit_char(c),
+            Value::String(s) => visitor.visit_string(s),
+            Value::Number(n) => {
+                let n = n.get();
+                if n.fract() != 0.0 {
+                    visitor.visit_f64(n)
+                } else if n >= 0.0 && n <= ::std::u64::MAX as f64 {
+                    visitor.visit_u64(n as u64)
+                } else if n >= ::std::i64::MIN as f64 {
+                    visitor.visit_i64(n as i64)
+                } else {
+                    visitor.visit_f64(n)
+                }
+            }
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(v)) => visitor.visit_some(ValueDeserializer(*v)),
+            Value::Unit => visitor.visit_unit(),
+            Value::Seq(seq) => {
+                visitor.visit_seq(SeqDeserializer::new(seq.into_iter().map(ValueDeserializer)))
+            }
+            Value::Map(map) => visitor.visit_map(MapDeserializer::new(
+                map.into_iter()
+                    .map(|(k, v)| (ValueDeserializer(k), ValueDeserializer(v))),
+            )),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self.0 {
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(v)) => visitor.visit_some(ValueDeserializer(*v)),
+            v => visitor.visit_some(ValueDeserializer(v)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        match self.0 {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Map(map) => {
+                let mut entries = map.into_iter();
+                match (entries.next(), entries.next()) {
+                    (Some((Value::String(variant), value)), None) => {
+                        visitor.visit_enum(EnumDeserializer { variant, value })
+                    }
+                    _ => Err(de::Error::custom(
+                        "Expected a map with a single enum variant name.",
+                    )),
+                }
+            }
+            _ => Err(de::Error::custom(
+                "Expected an enum variant name, or a map of it to the variant data.",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// An enum variant holding data.
+struct EnumDeserializer {
+    variant: String,
+    value: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = ValueError;
+    type Variant = ValueDeserializer;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, ValueDeserializer), ValueError> {
+        let variant = seed.deserialize(IntoDeserializer::<ValueError>::into_deserializer(
+            self.variant,
+        ))?;
+        Ok((variant, ValueDeserializer(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueDeserializer {
+    type Error = ValueError;
+
+    fn unit_variant(self) -> Result<(), ValueError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: de::DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<S::Value, ValueError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.deserialize_map(visitor)
+    }
+}
+
+/// Turns a serializable value into a `Value`, which `ValueDeserializer` reads back.
+/// Unit enum variants are stored as their name, and the other variants as a map of
+/// their name to their data.
+/// The struct fields that are None are left out, since not every format can tell them
+/// apart from unit values when reading them back.
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ValueError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, ValueError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, ValueError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, ValueError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, ValueError> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, ValueError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, ValueError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, ValueError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, ValueError> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, ValueError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, ValueError> {
+        if !v.is_finite() {
+            return Err(ser::Error::custom(format!("Can't save the number {}.", v)));
+        }
+        Ok(Value::Number(Number::new(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, ValueError> {
+        Ok(Value::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ValueError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ValueError> {
+        Ok(Value::Seq(
+            v.iter()
+                .map(|b| Value::Number(Number::new(f64::from(*b))))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Value, ValueError> {
+        Ok(Value::Option(None))
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, ValueError> {
+        Ok(Value::Option(Some(Box::new(value.serialize(self)?))))
+    }
+
+    fn serialize_unit(self) -> Result<Value, ValueError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ValueError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, ValueError> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, ValueError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, ValueError> {
+        Ok(tag_variant(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, ValueError> {
+        Ok(SeqSerializer {
+            variant: None,
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, ValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ValueError> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            values: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, ValueError> {
+        Ok(MapSerializer::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer, ValueError> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer, ValueError> {
+        Ok(MapSerializer {
+            variant: Some(variant),
+            ..MapSerializer::default()
+        })
+    }
+}
+
+/// Stores the data of an enum variant in a map of the variant name to the data.
+fn tag_variant(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(variant) => Value::Map(
+            vec![(Value::String(variant.to_string()), value)]
+                .into_iter()
+                .collect(),
+        ),
+        None => value,
+    }
+}
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    values: Vec<Value>,
+}
+
+impl SeqSerializer {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.values.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ValueError> {
+        Ok(tag_variant(self.variant, Value::Seq(self.values)))
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+#[derive(Default)]
+struct MapSerializer {
+    variant: Option<&'static str>,
+    entries: Vec<(Value, Value)>,
+    key: Option<Value>,
+}
+
+impl MapSerializer {
+    fn push_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        match value.serialize(ValueSerializer)? {
+            Value::Option(None) => {}
+            value => self.entries.push((Value::String(key.to_string()), value)),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ValueError> {
+        let map = Value::Map(self.entries.into_iter().collect());
+        Ok(tag_variant(self.variant, map))
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), ValueError> {
+        self.key = Some(key.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("Map value serialized before its key."))?;
+        self.entries.push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        self.push_field(key, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        self.push_field(key, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+/// Writes a `Value` as the data it holds, whole numbers as integers.
+struct SerializeValue<'a>(&'a Value);
+
+impl<'a> Serialize for SerializeValue<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self.0 {
+            Value::Bool(b) => serializer.serialize_bool(b),
+            Value::Char(c) => serializer.serialize_char(c),
+            Value::String(ref s) => serializer.serialize_str(s),
+            Value::Number(ref n) => {
+                let n = n.get();
+                if n.fract() != 0.0 {
+                    serializer.serialize_f64(n)
+                } else if n >= 0.0 && n <= ::std::u64::MAX as f64 {
+                    serializer.serialize_u64(n as u64)
+                } else if n >= ::std::i64::MIN as f64 {
+                    serializer.serialize_i64(n as i64)
+                } else {
+                    serializer.serialize_f64(n)
+                }
+            }
+            Value::Option(None) => serializer.serialize_none(),
+            Value::Option(Some(ref v)) => serializer.serialize_some(&SerializeValue(&**v)),
+            Value::Unit => serializer.serialize_unit(),
+            Value::Seq(ref seq) => serializer.collect_seq(seq.iter().map(SerializeValue)),
+            Value::Map(ref map) => serializer.collect_map(
+                map.iter()
+                    .map(|(k, v)| (SerializeValue(k), SerializeValue(v))),
+            ),
+        }
+    }
+}
+
+/// System used to automatically save a Resource T to a file.
+/// On load, it will attempt to load it from the file and if it fails, it will use T::default().
+///
+/// Saves are written to a temporary file which then replaces the save file, so a crash
+/// while saving never leaves a half written file behind.
+/// The previous saves are kept as `{save_path}.bak1` (the newest) to `{save_path}.bakN`.
+/// If the save file can't be loaded, the newest valid backup is used and
+/// the `SaveRecovery` resource records it.
+///
+/// The data is saved along with its version. Saves made with older versions are upgraded using
+/// the `SaveMigrations` given to `with_migrations`. If a migration fails, the original file is
+/// kept as `{save_path}.v{version}`.
+///
+/// The format is picked from the extension of the save path, see `SaveFormat::from_path`.
+/// With the `gzip` feature, paths ending with `.gz` are compressed.
+/// When loading, the format and compression are detected, so changing them keeps
+/// the existing saves.
+///
+/// Use `in_slot` instead of `new` to save in the active slot of the `SaveManager`.
+///
+/// Changes are only saved while `ShouldSave::save_ready` is true.
+/// Use `with_min_interval` and `with_debounce` to avoid writing to the disk every frame
+/// for resources that change often.
+/// The resource is serialized once it stopped changing for a frame, or when the save is due,
+/// so a resource changing every frame isn't encoded every frame.
+/// Changes that are still pending when the system is dropped, usually when the application
+/// exits, are written right away.
+/// If writing fails, the changes are kept and written again a second later.
+pub struct AutoSaveSystem<T> {
+    /// Absolute path.
+    save_path: String,
+    backups: usize,
+    /// False when the save file on disk couldn't be loaded, so it isn't rotated into the backups.
+    save_valid: bool,
+    min_interval: f64,
+    debounce: f64,
+    last_save: Option<f64>,
+    last_change: f64,
+    /// When writing the save last failed.
+    failed_at: Option<f64>,
+    /// True when the resource changed since it was last serialized.
+    changed: bool,
+    /// The serialized changes that weren't written yet.
+    pending: Option<Vec<u8>>,
+    ready: bool,
+    migrations: SaveMigrations,
+    format: SaveFormat,
+    #[cfg(feature = "gzip")]
+    compressed: bool,
+    /// The file name in the save slots and the `SaveManager::generation` of the slot in use.
+    slot: Option<(String, u64)>,
+    _phantom_data: PhantomData<T>,
+}
+
+impl<T> AutoSaveSystem<T> {
+    /// Save path is an absolute path.
+    pub fn new(save_path: String) -> Self {
+        AutoSaveSystem {
+            format: SaveFormat::from_path(&save_path),
+            #[cfg(feature = "gzip")]
+            compressed: save_path.ends_with(".gz"),
+            save_path,
+            backups: 3,
+            save_valid: true,
+            min_interval: 0.0,
+            debounce: 0.0,
+            last_save: None,
+            last_change: 0.0,
+            failed_at: None,
+            changed: false,
+            pending: None,
+            ready: false,
+            migrations: SaveMigrations::new(),
+            slot: None,
+            _phantom_data: PhantomData,
+        }
+    }
+
+    /// Saves to the file with this name in the active slot of the `SaveManager` resource.
+    /// The data is loaded again when another slot is selected,
+    /// and nothing is saved while no slot is selected.
+    pub fn in_slot(file_name: &str) -> Self {
+        let mut system =
+            AutoSaveSystem::new(String::new()).with_format(SaveFormat::from_path(file_name));
+        #[cfg(feature = "gzip")]
+        {
+            system.compressed = file_name.ends_with(".gz");
+        }
+        system.slot = Some((file_name.to_string(), 0));
+        system
+    }
+
+    /// Minimum time in seconds between two saves. Defaults to 0.
+    pub fn with_min_interval(mut self, seconds: f64) -> Self {
+        self.min_interval = seconds;
+        self
+    }
+
+    /// Time in seconds without changes to wait for before saving. Defaults to 0.
+    pub fn with_debounce(mut self, seconds: f64) -> Self {
+        self.debounce = seconds;
+        self
+    }
+
+    /// Number of previous saves to keep. Defaults to 3.
+    pub fn with_backups(mut self, backups: usize) -> Self {
+        self.backups = backups;
+        self
+    }
+
+    /// Migrations applied when loading saves made with older versions of T.
+    pub fn with_migrations(mut self, migrations: SaveMigrations) -> Self {
+        self.migrations = migrations;
+        self
+    }
+
+    /// Overrides the format picked from the save path.
+    pub fn with_format(mut self, format: SaveFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Whether to compress the saves. Defaults to true for paths ending with `.gz`.
+    #[cfg(feature = "gzip")]
+    pub fn with_compression(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    fn backup_path(&self, index: usize) -> String {
+        format!("{}.bak{}", self.save_path, index)
+    }
+
+    /// Writes the data to a temporary file, rotates the backups and replaces the save file.
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let tmp = format!("{}.tmp", self.save_path);
+        {
+            let mut file = File::create(&tmp)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+        }
+        if self.save_valid && Path::new(&self.save_path).exists() {
+            self.rotate_backups()?;
+        }
+        replace_file(&tmp, &self.save_path)?;
+        self.save_valid = true;
+        Ok(())
+    }
+
+    /// Writes the pending changes, if any. Returns true if something was written.
+    fn flush(&mut self) -> bool {
+        if self.save_path.is_empty() {
+            return false;
+        }
+        if let Some(data) = self.pending.take() {
+            if let Err(e) = self.write(&data) {
+                error!(
+                    "Failed to write serialized save data to the file \"{}\". Error: {:?}",
+                    self.save_path, e
+                );
+                // Kept to try again later.
+                self.pending = Some(data);
+                return false;
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Whether another slot than the one in use is selected.
+    fn slot_outdated(&self, manager: &SaveManager) -> bool {
+        match self.slot {
+            Some((_, generation)) => generation != manager.generation(),
+            None => false,
+        }
+    }
+
+    /// Follows the active slot of the save manager. Returns true if the slot changed.
+    fn follow_slot(&mut self, manager: &SaveManager) -> bool {
+        if !self.slot_outdated(manager) {
+            return false;
+        }
+        let file = match self.slot {
+            Some((ref file, _)) => file.clone(),
+            None => return false,
+        };
+        if self.ready {
+            self.flush();
+        }
+        self.save_path = manager.active_file(&file).unwrap_or_default();
+        self.slot = Some((file, manager.generation()));
+        self.save_valid = true;
+        self.changed = false;
+        self.pending = None;
+        self.last_save = None;
+        self.failed_at = None;
+        true
+    }
+
+    fn rotate_backups(&self) -> io::Result<()> {
+        if self.backups == 0 {
+            return Ok(());
+        }
+        for i in (1..self.backups).rev() {
+            let from = self.backup_path(i);
+            if Path::new(&from).exists() {
+                replace_file(&from, &self.backup_path(i + 1))?;
+            }
+        }
+        fs::copy(&self.save_path, self.backup_path(1))?;
+        Ok(())
+    }
+}
+
+impl<T> AutoSaveSystem<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn read(&self, path: &str) -> Option<T> {
+        let read = File::open(path).and_then(|mut f| {
+            let mut data = Vec::new();
+            f.read_to_end(&mut data)?;
+            decompress(data)
+        });
+        let data = match read {
+            Ok(data) => data,
+            Err(e) => {
+                error!(
+                    "Failed to read content of save file: {}. Error: {:?}",
+                    path, e
+                );
+                return None;
+            }
+        };
+        let format = SaveFormat::detect(&data);
+        let current = self.migrations.version();
+        if let Ok(envelope) = format.decode::<SaveEnvelope<T>>(&data) {
+            if envelope.version == current {
+                return Some(envelope.data);
+            }
+        }
+        let (version, value) = match format.decode::<SaveEnvelope<Value>>(&data) {
+            Ok(envelope) => (envelope.version, envelope.data),
+            // Saves made before versioning.
+            Err(_) => {
+                if current == 0 {
+                    if let Ok(o) = format.decode::<T>(&data) {
+                        return Some(o);
+                    }
+                }
+                match format.decode::<Value>(&data) {
+                    Ok(value) => (0, value),
+                    Err(e) => {
+                        error!(
+                            "Failed to deserialize save file: {}.\nThe file might be corrupted. Error: {}",
+                            path, e
+                        );
+                        return None;
+                    }
+                }
+            }
+        };
+        if version >= current {
+            // Not a migration problem: the data itself is broken, or from a newer version.
+            if version > current {
+                error!(
+                    "Failed to load save file: {}. It was made with version {}, but only versions up to {} are supported.",
+                    path, version, current
+                );
+            } else {
+                error!(
+                    "Failed to deserialize save file: {}.\nThe file might be corrupted.",
+                    path
+                );
+            }
+            return None;
+        }
+        let migrated = self
+            .migrations
+            .migrate(value, version)
+            .and_then(|value| T::deserialize(ValueDeserializer(value)).map_err(|e| e.to_string()));
+        match migrated {
+            Ok(o) => {
+                info!(
+                    "Migrated save file {} from version {} to version {}.",
+                    path, version, current
+                );
+                Some(o)
+            }
+            Err(e) => {
+                let original = format!("{}.v{}", path, version);
+                error!(
+                    "Failed to migrate save file {} from version {}: {}\nThe original file is kept as {}.",
+                    path, version, e, original
+                );
+                if let Err(e) = fs::copy(path, &original) {
+                    error!("Failed to copy save file to {}: {:?}", original, e);
+                }
+                None
+            }
+        }
+    }
+
+    /// Loads the save file, or the newest valid backup if it fails.
+    /// Returns the loaded data and the backup it came from, if any.
+    fn load(&mut self) -> (Option<T>, Option<RecoveredSave>) {
+        if self.save_path.is_empty() {
+            return (None, None);
+        }
+        let backups = (1..self.backups + 1)
+            .map(|i| self.backup_path(i))
+            .filter(|p| Path::new(p).exists())
+            .collect::<Vec<_>>();
+        let exists = Path::new(&self.save_path).exists();
+        if !exists && backups.is_empty() {
+            warn!(
+                "Failed to load save file: {}. It will be created during the next save.",
+                self.save_path
+            );
+            return (None, None);
+        }
+        if exists {
+            if let Some(o) = self.read(&self.save_path) {
+                return (Some(o), None);
+            }
+        }
+        self.save_valid = false;
+        for backup in backups {
+            if let Some(o) = self.read(&backup) {
+                warn!(
+                    "Restored save file {} from backup {}.",
+                    self.save_path, backup
+                );
+                let recovered = RecoveredSave {
+                    save_path: self.save_path.clone(),
+                    backup_path: Some(backup),
+                };
+                return (Some(o), Some(recovered));
+            }
+        }
+        error!(
+            "No valid backup of save file {} was found. Using the default value.",
+            self.save_path
+        );
+        if exists {
+            // Keep the broken file around instead of overwriting it on the next save.
+            let corrupted = format!("{}.corrupted", self.save_path);
+            if let Err(e) = fs::copy(&self.save_path, &corrupted) {
+                error!(
+                    "Failed to copy corrupted save file to {}: {:?}",
+                    corrupted, e
+                );
+            }
+        }
+        let recovered = RecoveredSave {
+            save_path: self.save_path.clone(),
+            backup_path: None,
+        };
+        (None, Some(recovered))
+    }
+
+    /// Serializes the changes that weren't serialized yet.
+    /// If it fails, the previous pending changes are kept.
+    fn prepare(&mut self, value: &T) {
+        if !self.changed {
+            return;
+        }
+        self.changed = false;
+        match self.serialize(value) {
+            Ok(data) => self.pending = Some(data),
+            Err(e) => error!(
+                "Failed to serialize the save data for the file \"{}\". Error: {}",
+                self.save_path, e
+            ),
+        }
+    }
+
+    fn serialize(&self, value: &T) -> Result<Vec<u8>, String> {
+        let envelope = SaveEnvelope {
+            version: self.migrations.version(),
+            data: value,
+        };
+        let data = self.format.encode(&envelope)?;
+        #[cfg(feature = "gzip")]
+        {
+            if self.compressed {
+                return compress(&data).map_err(|e| e.to_string());
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Seconds to wait before writing a save again after it failed.
+const SAVE_RETRY_DELAY: f64 = 1.0;
+
+/// Moves a file over another one. `fs::rename` doesn't replace existing files on every platform.
+fn replace_file(from: &str, to: &str) -> io::Result<()> {
+    if let Err(e) = fs::rename(from, to) {
+        if Path::new(to).exists() {
+            fs::remove_file(to)?;
+            return fs::rename(from, to);
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+impl<'a, T> System<'a> for AutoSaveSystem<T>
+where
+    T: Serialize + DeserializeOwned + Default + ShouldSave + Send + Sync + 'static,
+{
+    type SystemData = (
+        Write<'a, Dirty<T>>,
+        Read<'a, Time>,
+        Option<Read<'a, SaveManager>>,
+        Read<'a, SaveRecovery>,
+    );
+    fn setup(&mut self, res: &mut Resources) {
+        if let Some(manager) = res.try_fetch::<SaveManager>() {
+            self.follow_slot(&manager);
+        }
+        let (loaded, recovered) = self.load();
+        if let Some(o) = loaded {
+            res.insert(Dirty::new(o));
+        }
+        res.entry::<SaveRecovery>()
+            .or_insert_with(SaveRecovery::default);
+        if let Some(recovered) = recovered {
+            res.fetch::<SaveRecovery>().push(recovered);
+        }
+        Self::SystemData::setup(res);
+    }
+    fn run(&mut self, (mut d, time, manager, recovery): Self::SystemData) {
+        let now = time.absolute_real_time_seconds();
+        if manager.as_ref().map_or(false, |m| self.slot_outdated(m)) {
+            // The changes are written to the previous slot before following the new one.
+            self.prepare(d.read());
+        }
+        if manager.as_ref().map_or(false, |m| self.follow_slot(m)) {
+            let (loaded, recovered) = self.load();
+            *d.write() = loaded.unwrap_or_default();
+            d.clear();
+            if let Some(recovered) = recovered {
+                recovery.push(recovered);
+            }
+        }
+        let changing = d.dirty();
+        if changing {
+            d.clear();
+            self.changed = true;
+            self.last_change = now;
+        }
+        self.ready = d.read().save_ready();
+        let due = (self.changed || self.pending.is_some())
+            && self.ready
+            && now - self.last_change >= self.debounce
+            && self
+                .last_save
+                .map_or(true, |t| now - t >= self.min_interval)
+            && self.failed_at.map_or(true, |t| now - t >= SAVE_RETRY_DELAY);
+        if !changing || due {
+            self.prepare(d.read());
+        }
+        if due {
+            if self.flush() {
+                self.last_save = Some(now);
+                self.failed_at = None;
+                if self.slot.is_some() {
+                    if let Some(ref manager) = manager {
+                        manager.touch();
+                    }
+                }
+            } else if self.pending.is_some() {
+                self.failed_at = Some(now);
+            }
+        }
+    }
+}
+
+impl<T> Drop for AutoSaveSystem<T> {
+    fn drop(&mut self) {
+        if self.ready {
+            self.flush();
+        }
+    }
+}
+
+/// Information about a save slot, shown to the player when picking a slot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotMetadata {
+    pub display_name: String,
+    /// When the slot was last saved, in seconds since the unix epoch.
+    pub timestamp: u64,
+    /// Time played in this slot, in seconds.
+    pub playtime: f64,
+    /// Path of a screenshot of the game.
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+}
+
+/// A save slot of a user profile. Each slot is a directory holding the save files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveSlot {
+    pub profile: String,
+    pub id: String,
+    pub path: PathBuf,
+    pub metadata: SlotMetadata,
+}
+
+const SLOT_METADATA_FILE: &str = "slot.ron";
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Profile and slot names are used as directory names.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(|c: char| c == '/' || c == '\\')
+}
+
+/// Resource managing the save slots of the user profiles, stored as
+/// `{root}/{profile}/{slot}/`.
+/// `AutoSaveSystem::in_slot` saves into the active slot,
+/// and the metadata of the active slot is updated every time it saves.
+pub struct SaveManager {
+    root: PathBuf,
+    /// The active slot, with its metadata as it was when selected.
+    active: Option<SaveSlot>,
+    selected_at: Instant,
+    generation: u64,
+    /// Held while writing the metadata, which can happen from several systems at once.
+    metadata_lock: Mutex<()>,
+}
+
+impl SaveManager {
+    /// Stores the saves in the platform data directory, under `{game_name}/saves`.
+    /// Without the `dirs` feature, the working directory is used instead.
+    pub fn new(game_name: &str) -> Self {
+        SaveManager::from_dir(data_dir().join(game_name).join("saves"))
+    }
+
+    /// Stores the saves in the given directory.
+    pub fn from_dir<P: Into<PathBuf>>(root: P) -> Self {
+        SaveManager {
+            root: root.into(),
+            active: None,
+            selected_at: Instant::now(),
+            generation: 0,
+            metadata_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Changes every time another slot is selected.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The names of the profiles having at least one slot.
+    pub fn profiles(&self) -> Vec<String> {
+        let mut profiles = list_dirs(&self.root)
+            .into_iter()
+            .filter(|p| !self.slots(p).is_empty())
+            .collect::<Vec<_>>();
+        profiles.sort();
+        profiles
+    }
+
+    /// The slots of the profile, the most recently saved first.
+    pub fn slots(&self, profile: &str) -> Vec<SaveSlot> {
+        if !valid_name(profile) {
+            return vec![];
+        }
+        let mut slots = list_dirs(&self.root.join(profile))
+            .into_iter()
+            .filter_map(|id| self.slot(profile, &id))
+            .collect::<Vec<_>>();
+        slots.sort_by(|a, b| b.metadata.timestamp.cmp(&a.metadata.timestamp));
+        slots
+    }
+
+    pub fn slot(&self, profile: &str, id: &str) -> Option<SaveSlot> {
+        if !valid_name(profile) || !valid_name(id) {
+            return None;
+        }
+        let path = self.root.join(profile).join(id);
+        let metadata = fs::read(path.join(SLOT_METADATA_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|data| SaveFormat::Ron.decode::<SlotMetadata>(&data));
+        match metadata {
+            Ok(metadata) => Some(SaveSlot {
+                profile: profile.to_string(),
+                id: id.to_string(),
+                path,
+                metadata,
+            }),
+            Err(e) => {
+                if path.exists() {
+                    error!("Failed to read the metadata of save slot {:?}: {}", path, e);
+                }
+                None
+            }
+        }
+    }
+
+    /// Creates an empty slot. Fails if the slot already exists.
+    pub fn create_slot(&mut self, profile: &str, id: &str, display_name: &str) -> Option<SaveSlot> {
+        if !valid_name(profile) || !valid_name(id) {
+            error!("Invalid save slot name: {}/{}", profile, id);
+            return None;
+        }
+        let path = self.root.join(profile).join(id);
+        if path.exists() {
+            error!("Save slot {}/{} already exists.", profile, id);
+            return None;
+        }
+        if let Err(e) = fs::create_dir_all(&path) {
+            error!("Failed to create save slot directory {:?}: {:?}", path, e);
+            return None;
+        }
+        let slot = SaveSlot {
+            profile: profile.to_string(),
+            id: id.to_string(),
+            path,
+            metadata: SlotMetadata {
+                display_name: display_name.to_string(),
+                timestamp: unix_timestamp(),
+                playtime: 0.0,
+                thumbnail: None,
+            },
+        };
+        if write_metadata(&slot) {
+            Some(slot)
+        } else {
+            None
+        }
+    }
+
+    /// Copies all the files of a slot into a new slot of the same profile.
+    pub fn copy_slot(&mut self, profile: &str, from: &str, to: &str) -> Option<SaveSlot> {
+        let source = self.slot(profile, from)?;
+        let mut slot = self.create_slot(profile, to, &source.metadata.display_name)?;
+        for entry in fs::read_dir(&source.path).ok()?.filter_map(|e| e.ok()) {
+            if entry.path().is_file() {
+                if let Err(e) = fs::copy(entry.path(), slot.path.join(entry.file_name())) {
+                    error!("Failed to copy save file {:?}: {:?}", entry.path(), e);
+                    return None;
+                }
+            }
+        }
+        slot.metadata = source.metadata;
+        slot.metadata.timestamp = unix_timestamp();
+        if write_metadata(&slot) {
+            Some(slot)
+        } else {
+            None
+        }
+    }
+
+    /// Deletes the slot and all its files. The slot is deselected if it was active.
+    pub fn delete_slot(&mut self, profile: &str, id: &str) -> bool {
+        let slot = match self.slot(profile, id) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        if self.active.as_ref().map_or(false, |a| a.path == slot.path) {
+            self.deselect();
+        }
+        if let Err(e) = fs::remove_dir_all(&slot.path) {
+            error!("Failed to delete save slot {:?}: {:?}", slot.path, e);
+            return false;
+        }
+        true
+    }
+
+    /// Makes this slot the one the `AutoSaveSystem`s save to.
+    pub fn select_slot(&mut self, profile: &str, id: &str) -> bool {
+        match self.slot(profile, id) {
+            Some(slot) => {
+                self.active = Some(slot);
+                self.selected_at = Instant::now();
+                self.generation += 1;
+                true
+            }
+            None => {
+                error!("Failed to select save slot {}/{}.", profile, id);
+                false
+            }
+        }
+    }
+
+    pub fn deselect(&mut self) {
+        self.touch();
+        self.active = None;
+        self.generation += 1;
+    }
+
+    /// The active slot, with its metadata as it was last saved.
+    pub fn active_slot(&self) -> Option<SaveSlot> {
+        self.active
+            .as_ref()
+            .and_then(|s| self.slot(&s.profile, &s.id))
+    }
+
+    /// Path of a file in the active slot.
+    pub fn active_file(&self, file_name: &str) -> Option<String> {
+        self.active
+            .as_ref()
+            .and_then(|s| s.path.join(file_name).to_str().map(|p| p.to_string()))
+    }
+
+    /// Sets the thumbnail of the active slot and touches it.
+    pub fn set_thumbnail(&mut self, thumbnail: Option<String>) {
+        if let Some(ref mut slot) = self.active {
+            slot.metadata.thumbnail = thumbnail;
+        }
+        self.touch();
+    }
+
+    /// Updates the timestamp and playtime of the active slot.
+    /// The playtime counts the real time since the slot was selected.
+    pub fn touch(&self) {
+        if let Some(ref active) = self.active {
+            let elapsed = self.selected_at.elapsed();
+            let mut slot = active.clone();
+            slot.metadata.timestamp = unix_timestamp();
+            slot.metadata.playtime +=
+                elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0;
+            let _lock = self.metadata_lock.lock().unwrap();
+            write_metadata(&slot);
+        }
+    }
+}
+
+#[cfg(feature = "dirs")]
+fn data_dir() -> PathBuf {
+    dirs::data_dir().unwrap_or_else(|| PathBuf::from(get_working_dir()))
+}
+
+#[cfg(not(feature = "dirs"))]
+fn data_dir() -> PathBuf {
+    PathBuf::from(get_working_dir())
+}
+
+fn list_dirs(path: &Path) -> Vec<String> {
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().is_dir())
+                .filter_map(|e| e.file_name().to_str().map(|n| n.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn write_metadata(slot: &SaveSlot) -> bool {
+    let path = slot.path.join(SLOT_METADATA_FILE);
+    let tmp = slot.path.join(format!("{}.tmp", SLOT_METADATA_FILE));
+    let written = SaveFormat::RonPretty
+        .encode(&slot.metadata)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        .and_then(|data| fs::write(&tmp, data))
+        .and_then(|_| replace_file(tmp.to_str().unwrap(), path.to_str().unwrap()));
+    if let Err(e) = written {
+        error!(
+            "Failed to write the metadata of save slot {:?}: {:?}",
+            slot.path, e
+        );
+        return false;
+    }
+    true
+}
+
+/// Marks the entities saved in world snapshots.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Persist;
+
+impl Component for Persist {
+    type Storage = NullStorage<Self>;
+}
+
+/// Maps the persisted entities to their id in a snapshot.
+/// Used to save and restore references between entities.
+#[derive(Debug, Default)]
+pub struct EntityIds {
+    ids: HashMap<Entity, u32>,
+    entities: HashMap<u32, Entity>,
+}
+
+impl EntityIds {
+    fn insert(&mut self, entity: Entity, id: u32) {
+        self.ids.insert(entity, id);
+        self.entities.insert(id, entity);
+    }
+
+    /// The id of the entity in the snapshot. None if the entity isn't persisted.
+    pub fn id(&self, entity: Entity) -> Option<u32> {
+        self.ids.get(&entity).cloned()
+    }
+
+    /// The entity created for this id when restoring the snapshot.
+    pub fn entity(&self, id: u32) -> Option<Entity> {
+        self.entities.get(&id).cloned()
+    }
+}
+
+/// A component saved in world snapshots through another type,
+/// for components holding entity references or data that can't be serialized.
+pub trait Persistent: Component {
+    type Data: Serialize + DeserializeOwned;
+    /// The name of the component in the snapshots.
+    fn name() -> &'static str;
+    fn save(&self, ids: &EntityIds) -> Self::Data;
+    fn load(data: Self::Data, ids: &EntityIds) -> Self;
+}
+
+/// The data of a `Transform`. The rotation is a quaternion stored as [w, x, y, z].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransformData {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl Persistent for Transform {
+    type Data = TransformData;
+
+    fn name() -> &'static str {
+        "transform"
+    }
+
+    fn save(&self, _ids: &EntityIds) -> TransformData {
+        let r = self.rotation;
+        TransformData {
+            translation: self.translation.into(),
+            rotation: [r.s, r.v.x, r.v.y, r.v.z],
+            scale: self.scale.into(),
+        }
+    }
+
+    fn load(data: TransformData, _ids: &EntityIds) -> Self {
+        let r = data.rotation;
+        let mut transform = Transform::default();
+        transform.translation = Vector3::from(data.translation);
+        transform.rotation = Quaternion::new(r[0], r[1], r[2], r[3]);
+        transform.scale = Vector3::from(data.scale);
+        transform
+    }
+}
+
+/// A persisted entity and its components, by component name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+    pub id: u32,
+    #[serde(serialize_with = "serialize_components")]
+    pub components: BTreeMap<String, Value>,
+}
+
+fn serialize_components<S: Serializer>(
+    components: &BTreeMap<String, Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(components.iter().map(|(k, v)| (k, SerializeValue(v))))
+}
+
+/// The persisted entities of a world.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub entities: Vec<EntitySnapshot>,
+}
+
+/// Saves and restores one component type.
+trait ComponentSnapshot: Send + Sync {
+    fn name(&self) -> &str;
+    fn register(&self, world: &mut World);
+    /// Serializes the component of a persisted entity, if it has one.
+    fn save(&self, world: &World, entity: Entity, ids: &EntityIds) -> Option<Value>;
+    fn load(
+        &self,
+        world: &World,
+        entity: Entity,
+        value: Value,
+        ids: &EntityIds,
+    ) -> Result<(), String>;
+}
+
+struct PersistentSnapshot<C>(PhantomData<fn() -> C>);
+
+impl<C: Persistent> ComponentSnapshot for PersistentSnapshot<C> {
+    fn name(&self) -> &str {
+        C::name()
+    }
+
+    fn register(&self, world: &mut World) {
+        world.register::<C>();
+    }
+
+    fn save(&self, world: &World, entity: Entity, ids: &EntityIds) -> Option<Value> {
+        let data = world.read_storage::<C>().get(entity)?.save(ids);
+        data.serialize(ValueSerializer)
+            .map_err(|e| error!("Failed to serialize component {}: {}", C::name(), e))
+            .ok()
+    }
+
+    fn load(
+        &self,
+        world: &World,
+        entity: Entity,
+        value: Value,
+        ids: &EntityIds,
+    ) -> Result<(), String> {
+        let data = C::Data::deserialize(ValueDeserializer(value)).map_err(|e| e.to_string())?;
+        world
+            .write_storage::<C>()
+            .insert(entity, C::load(data, ids))
+            .map(|_| ())
+            .map_err(|e| format!("{:?}", e))
+    }
+}
+
+/// Saves components that are serializable as they are.
+struct SerdeSnapshot<C> {
+    name: String,
+    _phantom: PhantomData<fn() -> C>,
+}
+
+impl<C> ComponentSnapshot for SerdeSnapshot<C>
+where
+    C: Component + Serialize + DeserializeOwned,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn register(&self, world: &mut World) {
+        world.register::<C>();
+    }
+
+    fn save(&self, world: &World, entity: Entity, _ids: &EntityIds) -> Option<Value> {
+        let storage = world.read_storage::<C>();
+        storage
+            .get(entity)?
+            .serialize(ValueSerializer)
+            .map_err(|e| error!("Failed to serialize component {}: {}", self.name, e))
+            .ok()
+    }
+
+    fn load(
+        &self,
+        world: &World,
+        entity: Entity,
+        value: Value,
+        _ids: &EntityIds,
+    ) -> Result<(), String> {
+        let component = C::deserialize(ValueDeserializer(value)).map_err(|e| e.to_string())?;
+        world
+            .write_storage::<C>()
+            .insert(entity, component)
+            .map(|_| ())
+            .map_err(|e| format!("{:?}", e))
+    }
+}
+
+/// The components saved in world snapshots, and how to save and restore them.
+/// Only the entities with the `Persist` component are saved.
+///
+/// Snapshot files are written like the `AutoSaveSystem` saves: atomically,
+/// in the format picked from the file extension and, with the `gzip` feature,
+/// compressed if it ends with `.gz`.
+///
+/// Example:
+/// ```rs
+/// let registry = SnapshotRegistry::new().with::<Health>("health");
+/// registry.save(&world, "/path/to/world.ron");
+/// // Later, in a fresh world.
+/// registry.load(&mut world, "/path/to/world.ron");
+/// ```
+pub struct SnapshotRegistry {
+    components: Vec<Box<ComponentSnapshot>>,
+}
+
+impl Default for SnapshotRegistry {
+    fn default() -> Self {
+        SnapshotRegistry::new()
+    }
+}
+
+impl SnapshotRegistry {
+    /// A registry saving the `Transform` of the entities.
+    pub fn new() -> Self {
+        SnapshotRegistry { components: vec![] }.with_persistent::<Transform>()
+    }
+
+    /// Saves a serializable component under this name.
+    pub fn with<C>(mut self, name: &str) -> Self
+    where
+        C: Component + Serialize + DeserializeOwned,
+    {
+        self.components.push(Box::new(SerdeSnapshot::<C> {
+            name: name.to_string(),
+            _phantom: PhantomData,
+        }));
+        self
+    }
+
+    /// Saves a component through its `Persistent` implementation.
+    pub fn with_persistent<C: Persistent>(mut self) -> Self {
+        self.components
+            .push(Box::new(PersistentSnapshot::<C>(PhantomData)));
+        self
+    }
+
+    /// Takes a snapshot of the entities with the `Persist` component.
+    pub fn snapshot(&self, world: &World) -> WorldSnapshot {
+        let mut ids = EntityIds::default();
+        let entities = (&*world.entities(), &world.read_storage::<Persist>())
+            .join()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        for (id, entity) in entities.iter().enumerate() {
+            ids.insert(*entity, id as u32);
+        }
+        let entities = entities
+            .iter()
+            .enumerate()
+            .map(|(id, entity)| EntitySnapshot {
+                id: id as u32,
+                components: self
+                    .components
+                    .iter()
+                    .filter_map(|c| {
+                        c.save(world, *entity, &ids)
+                            .map(|v| (c.name().to_string(), v))
+                    })
+                    .collect(),
+            })
+            .collect();
+        WorldSnapshot { entities }
+    }
+
+    /// Creates the entities of the snapshot, with the `Persist` component.
+    /// Entity references are remapped to the new entities.
+    /// Returns the created entities, in the order of the snapshot.
+    pub fn restore(&self, world: &mut World, snapshot: &WorldSnapshot) -> Vec<Entity> {
+        world.register::<Persist>();
+        for component in &self.components {
+            component.register(world);
+        }
+        let mut ids = EntityIds::default();
+        let created = snapshot
+            .entities
+            .iter()
+            .map(|e| {
+                let entity = world.create_entity().with(Persist).build();
+                ids.insert(entity, e.id);
+                entity
+            })
+            .collect::<Vec<_>>();
+        for (entity_snapshot, entity) in snapshot.entities.iter().zip(created.iter()) {
+            for (name, value) in &entity_snapshot.components {
+                match self.components.iter().find(|c| c.name() == name) {
+                    Some(component) => {
+                        if let Err(e) = component.load(world, *entity, value.clone(), &ids) {
+                            error!(
+                                "Failed to restore component {} of entity {}: {}",
+                                name, entity_snapshot.id, e
+                            );
+                        }
+                    }
+                    None => warn!("Unknown component {} in world snapshot.", name),
+                }
+            }
+        }
+        created
+    }
+
+    /// Writes a snapshot of the world to a file.
+    pub fn save(&self, world: &World, path: &str) -> bool {
+        let envelope = SaveEnvelope {
+            version: 0,
+            data: self.snapshot(world),
+        };
+        let written = SaveFormat::from_path(path)
+            .encode(&envelope)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .and_then(|data| {
+                #[cfg(feature = "gzip")]
+                {
+                    if path.ends_with(".gz") {
+                        return compress(&data);
+                    }
+                }
+                Ok(data)
+            })
+            .and_then(|data| {
+                let tmp = format!("{}.tmp", path);
+                fs::write(&tmp, data).and_then(|_| replace_file(&tmp, path))
+            });
+        if let Err(e) = written {
+            error!("Failed to write world snapshot {}: {:?}", path, e);
+            return false;
+        }
+        true
+    }
+
+    /// Restores the snapshot saved in a file. Returns the created entities.
+    pub fn load(&self, world: &mut World, path: &str) -> Option<Vec<Entity>> {
+        let snapshot = fs::read(path)
+            .and_then(decompress)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                SaveFormat::detect(&data).decode::<SaveEnvelope<WorldSnapshot>>(&data)
+            });
+        match snapshot {
+            Ok(envelope) => Some(self.restore(world, &envelope.data)),
+            Err(e) => {
+                error!("Failed to load world snapshot {}: {}", path, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amethyst::ecs::Resources;
+    use dirty::Dirty;
+    use ron::value::Value;
+    use std::fs;
+    use *;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Progress {
+        level: u32,
+        #[serde(skip)]
+        ready: bool,
+    }
+
+    impl ShouldSave for Progress {
+        fn save_ready(&self) -> bool {
+            self.ready
+        }
+        fn set_save_ready(&mut self, ready: bool) {
+            self.ready = ready;
+        }
+    }
+
+    fn save_file(name: &str, file: &str) -> String {
+        let dir = ::std::env::temp_dir().join(format!("amethyst_extra_save_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(file).to_str().unwrap().to_string()
+    }
+
+    fn save_path(name: &str) -> String {
+        save_file(name, "progress.ron")
+    }
+
+    fn load(path: &str) -> Resources {
+        let mut res = Resources::new();
+        System::setup(
+            &mut AutoSaveSystem::<Progress>::new(path.to_string()),
+            &mut res,
+        );
+        res
+    }
+
+    fn step(system: &mut AutoSaveSystem<Progress>, res: &Resources, seconds: f32) {
+        res.fetch_mut::<Time>().set_delta_seconds(seconds);
+        system.run_now(res);
+    }
+
+    /// Changes the progress and runs the system once, like a game would.
+    fn save(system: &mut AutoSaveSystem<Progress>, res: &Resources, level: u32) {
+        *res.fetch_mut::<Dirty<Progress>>().write() = Progress { level, ready: true };
+        step(system, res, 0.1);
+    }
+
+    fn saved_level(path: &str) -> Option<u32> {
+        if !::std::path::Path::new(path).exists() {
+            return None;
+        }
+        let level = load(path).fetch::<Dirty<Progress>>().read().level;
+        Some(level)
+    }
+
+    #[test]
+    fn save_rotates_backups() {
+        let path = save_path("rotate");
+        let mut system = AutoSaveSystem::<Progress>::new(path.clone()).with_backups(2);
+        let mut res = Resources::new();
+        System::setup(&mut system, &mut res);
+        for level in 1..5 {
+            save(&mut system, &res, level);
+        }
+        assert!(!::std::path::Path::new(&format!("{}.tmp", path)).exists());
+        assert!(!::std::path::Path::new(&format!("{}.bak3", path)).exists());
+        assert_eq!(saved_level(&path), Some(4));
+        assert_eq!(saved_level(&format!("{}.bak1", path)), Some(3));
+        assert_eq!(saved_level(&format!("{}.bak2", path)), Some(2));
+
+        let res = load(&path);
+        assert_eq!(res.fetch::<Dirty<Progress>>().read().level, 4);
+        assert!(!res.fetch::<SaveRecovery>().happened());
+    }
+
+    #[test]
+    fn corrupted_save_falls_back_to_backup() {
+        let path = save_path("recover");
+        let mut system = AutoSaveSystem::<Progress>::new(path.clone());
+        let mut res = Resources::new();
+        System::setup(&mut system, &mut res);
+        save(&mut system, &res, 1);
+        save(&mut system, &res, 2);
+        fs::write(&path, "(level: ").unwrap();
+
+        let res = load(&path);
+        assert_eq!(res.fetch::<Dirty<Progress>>().read().level, 1);
+        let recovery = res.fetch::<SaveRecovery>();
+        assert!(recovery.happened());
+        assert_eq!(
+            recovery.get(&path).unwrap().backup_path,
+            Some(format!("{}.bak1", path))
+        );
+    }
+
+    #[test]
+    fn corrupted_save_without_backup_is_kept() {
+        let path = save_path("corrupted");
+        fs::write(&path, "not a save").unwrap();
+
+        let res = load(&path);
+        assert_eq!(*res.fetch::<Dirty<Progress>>().read(), Progress::default());
+        assert_eq!(
+            res.fetch::<SaveRecovery>().get(&path).unwrap().backup_path,
+            None
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}.corrupted", path)).unwrap(),
+            "not a save"
+        );
+    }
+
+    #[test]
+    fn saves_wait_for_ready_and_debounce() {
+        let path = save_path("debounce");
+        let mut system = AutoSaveSystem::<Progress>::new(path.clone()).with_debounce(1.0);
+        let mut res = Resources::new();
+        System::setup(&mut system, &mut res);
+
+        res.fetch_mut::<Dirty<Progress>>().write().level = 1;
+        step(&mut system, &res, 0.5);
+        step(&mut system, &res, 2.0);
+        assert_eq!(saved_level(&path), None);
+
+        res.fetch_mut::<Dirty<Progress>>().write().ready = true;
+        step(&mut system, &res, 0.5);
+        step(&mut system, &res, 0.6);
+        assert_eq!(saved_level(&path), None);
+        step(&mut system, &res, 0.5);
+        assert_eq!(saved_level(&path), Some(1));
+    }
+
+    #[test]
+    fn debounce_waits_in_real_time() {
+        let path = save_path("debounce_real_time");
+        let mut system = AutoSaveSystem::<Progress>::new(path.clone()).with_debounce(1.0);
+        let mut res = Resources::new();
+        System::setup(&mut system, &mut res);
+        // The game is paused, but the debounce window still elapses.
+        res.fetch_mut::<Time>().set_time_scale(0.0);
+
+        save(&mut system, &res, 1);
+        step(&mut system, &res, 0.4);
+        step(&mut system, &res, 0.4);
+        assert_eq!(saved_level(&path), None);
+        step(&mut system, &res, 0.4);
+        assert_eq!(saved_level(&path), Some(1));
+        let time = res.fetch::<Time>();
+        assert_eq!(time.absolute_time_seconds(), 0.0);
+        assert!(time.absolute_real_time_seconds() > 1.0);
+    }
+
+    #[test]
+    fn saves_respect_min_interval_and_flush_on_drop() {
+        let path = save_path("interval");
+        let mut system = AutoSaveSystem::<Progress>::new(path.clone()).with_min_interval(5.0);
+        let mut res = Resources::new();
+        System::setup(&mut system, &mut res);
+
+        res.fetch_mut::<Dirty<Progress>>().write().ready = true;
+        res.fetch_mut::<Dirty<Progress>>().write().level = 1;
+        step(&mut system, &res, 0.1);
+        assert_eq!(saved_level(&path), Some(1));
+
+        res.fetch_mut::<Dirty<Progress>>().write().level = 2;
+        step(&mut system, &res, 1.0);
+        assert_eq!(saved_level(&path), Some(1));
+        step(&mut system, &res, 4.5);
+        assert_eq!(saved_level(&path), Some(2));
+
+        res.fetch_mut::<Dirty<Progress>>().write().level = 3;
+        step(&mut system, &res, 0.1);
+        // Serialized once it stopped changing.
+        step(&mut system, &res, 0.1);
+        assert_eq!(saved_level(&path), Some(2));
+        drop(system);
+        assert_eq!(saved_level(&path), Some(3));
+    }
+
+    #[test]
+    fn failed_writes_are_retried() {
+        let path = save_file("retry", "missing/progress.ron");
+        let mut system = AutoSaveSystem::<Progress>::new(path.clone());
+        let mut res = Resources::new();
+        System::setup(&mut system, &mut res);
+
+        save(&mut system, &res, 1);
+        assert_eq!(saved_level(&path), None);
+        fs::create_dir_all(::std::path::Path::new(&path).parent().unwrap()).unwrap();
+        step(&mut system, &res, 0.5);
+        assert_eq!(saved_level(&path), None);
+        step(&mut system, &res, 0.6);
+        assert_eq!(saved_level(&path), Some(1));
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Profile {
+        stage: u32,
+        name: String,
+    }
+
+    impl ShouldSave for Profile {
+        fn save_ready(&self) -> bool {
+            true
+        }
+        fn set_save_ready(&mut self, _ready: bool) {}
+    }
+
+    fn key(name: &str) -> Value {
+        Value::String(name.to_string())
+    }
+
+    fn profile_migrations() -> SaveMigrations {
+        SaveMigrations::new()
+            .with(0, |value| match value {
+                Value::Map(mut map) => {
+                    map.insert(key("name"), key("Player"));
+                    Ok(Value::Map(map))
+                }
+                _ => Err("Expected a struct.".to_string()),
+            })
+            .with(1, |value| match value {
+                Value::Map(mut map) => {
+                    let level = map
+                        .remove(&key("level"))
+                        .ok_or_else(|| "Missing level.".to_string())?;
+                    map.insert(key("stage"), level);
+                    Ok(Value::Map(map))
+                }
+                _ => Err("Expected a struct.".to_string()),
+            })
+    }
+
+    fn load_fixture(name: &str, migrations: SaveMigrations) -> (String, Resources) {
+        let path = save_path(name);
+        fs::copy(
+            format!("{}/test/saves/{}.ron", env!("CARGO_MANIFEST_DIR"), name),
+            &path,
+        )
+        .unwrap();
+        let mut res = Resources::new();
+        System::setup(
+            &mut AutoSaveSystem::<Profile>::new(path.clone()).with_migrations(migrations),
+            &mut res,
+        );
+        (path, res)
+    }
+
+    #[test]
+    fn old_saves_are_migrated() {
+        let (_, res) = load_fixture("profile_v0", profile_migrations());
+        assert_eq!(
+            *res.fetch::<Dirty<Profile>>().read(),
+            Profile {
+                stage: 3,
+                name: "Player".to_string(),
+            }
+        );
+
+        let (path, res) = load_fixture("profile_v1", profile_migrations());
+        let profile = Profile {
+            stage: 5,
+            name: "Jojo".to_string(),
+        };
+        assert_eq!(*res.fetch::<Dirty<Profile>>().read(), profile);
+        assert!(!res.fetch::<SaveRecovery>().happened());
+
+        let mut system =
+            AutoSaveSystem::<Profile>::new(path.clone()).with_migrations(profile_migrations());
+        system.pending = Some(system.serialize(&profile).unwrap());
+        system.flush();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("(version:2,"));
+        assert_eq!(system.read(&path), Some(profile));
+    }
+
+    #[test]
+    fn failed_migration_keeps_original() {
+        let migrations = SaveMigrations::new()
+            .with(0, Ok)
+            .with(1, |_| Err("Unsupported save.".to_string()));
+        let (path, res) = load_fixture("profile_v1", migrations);
+        assert_eq!(*res.fetch::<Dirty<Profile>>().read(), Profile::default());
+        assert_eq!(
+            res.fetch::<SaveRecovery>().get(&path).unwrap().backup_path,
+            None
+        );
+        let original = fs::read_to_string(format!(
+            "{}/test/saves/profile_v1.ron",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert_eq!(
+            fs::read_to_string(format!("{}.v1", path)).unwrap(),
+            original
+        );
+    }
+
+    #[test]
+    fn broken_current_version_is_recovered() {
+        let path = save_path("broken_current");
+        fs::write(
+            &path,
+            "(version: 2, data: (stage: \"three\", name: \"Jojo\"))",
+        )
+        .unwrap();
+        let mut res = Resources::new();
+        System::setup(
+            &mut AutoSaveSystem::<Profile>::new(path.clone()).with_migrations(profile_migrations()),
+            &mut res,
+        );
+        assert_eq!(*res.fetch::<Dirty<Profile>>().read(), Profile::default());
+        assert!(res.fetch::<SaveRecovery>().happened());
+        assert!(::std::path::Path::new(&format!("{}.corrupted", path)).exists());
+        assert!(!::std::path::Path::new(&format!("{}.v2", path)).exists());
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(SaveFormat::from_path("save.ron"), SaveFormat::Ron);
+        assert_eq!(SaveFormat::from_path("save.ron.gz"), SaveFormat::Ron);
+        #[cfg(feature = "json")]
+        assert_eq!(SaveFormat::from_path("save.json.gz"), SaveFormat::Json);
+        #[cfg(feature = "cbor")]
+        assert_eq!(SaveFormat::from_path("save.bin"), SaveFormat::Binary);
+        assert_eq!(SaveFormat::from_path("save"), SaveFormat::Ron);
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "cbor", feature = "gzip"))]
+    fn formats_round_trip() {
+        for file in &[
+            "progress.ron",
+            "progress.json",
+            "progress.bin",
+            "progress.ron.gz",
+        ] {
+            let path = save_file("formats", file);
+            let mut system = AutoSaveSystem::<Progress>::new(path.clone());
+            let mut res = Resources::new();
+            System::setup(&mut system, &mut res);
+            save(&mut system, &res, 12);
+            let data = fs::read(&path).unwrap();
+            assert_eq!(data.starts_with(&[0x1f, 0x8b]), file.ends_with(".gz"));
+            assert_eq!(
+                SaveFormat::detect(&decompress(data).unwrap()),
+                SaveFormat::from_path(file)
+            );
+            assert_eq!(saved_level(&path), Some(12));
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "cbor", feature = "gzip"))]
+    fn format_change_keeps_old_saves() {
+        let path = save_file("format_change", "progress.sav");
+        fs::write(&path, "(level: 4)").unwrap();
+        let mut system = AutoSaveSystem::<Progress>::new(path.clone())
+            .with_format(SaveFormat::Binary)
+            .with_compression(true);
+        let mut res = Resources::new();
+        System::setup(&mut system, &mut res);
+        assert_eq!(res.fetch::<Dirty<Progress>>().read().level, 4);
+
+        save(&mut system, &res, 5);
+        let data = decompress(fs::read(&path).unwrap()).unwrap();
+        assert_eq!(SaveFormat::detect(&data), SaveFormat::Binary);
+        assert_eq!(saved_level(&path), Some(5));
+    }
+
+    fn slot_manager(name: &str) -> SaveManager {
+        let root = ::std::env::temp_dir().join(format!("amethyst_extra_slots_{}", name));
+        let _ = fs::remove_dir_all(&root);
+        SaveManager::from_dir(root)
+    }
+
+    #[test]
+    fn slots_create_copy_delete() {
+        let mut manager = slot_manager("manage");
+        assert!(manager.create_slot("alice", "1", "Forest").is_some());
+        assert!(manager.create_slot("alice", "1", "Again").is_none());
+        assert!(manager.create_slot("alice", "../2", "Escape").is_none());
+        assert!(manager.create_slot("bob", "1", "Cave").is_some());
+        assert_eq!(manager.profiles(), vec!["alice", "bob"]);
+
+        fs::write(manager.root().join("alice/1/progress.ron"), "(level: 3)").unwrap();
+        let copy = manager.copy_slot("alice", "1", "2").unwrap();
+        assert_eq!(copy.metadata.display_name, "Forest");
+        assert!(copy.path.join("progress.ron").exists());
+        assert_eq!(manager.slots("alice").len(), 2);
+
+        assert!(manager.select_slot("alice", "1"));
+        assert!(manager.delete_slot("alice", "1"));
+        assert!(manager.active_slot().is_none());
+        let slots = manager.slots("alice");
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].id, "2");
+    }
+
+    #[test]
+    fn touch_counts_playtime_once() {
+        let mut manager = slot_manager("touch");
+        manager.create_slot("alice", "1", "First");
+        assert!(manager.select_slot("alice", "1"));
+        ::std::thread::sleep(::std::time::Duration::from_millis(50));
+        let manager = &manager;
+        manager.touch();
+        manager.touch();
+        let playtime = manager.active_slot().unwrap().metadata.playtime;
+        assert!(playtime >= 0.05 && playtime < 0.1);
+    }
+
+    #[test]
+    fn auto_save_follows_active_slot() {
+        let mut manager = slot_manager("follow");
+        manager.create_slot("alice", "1", "First");
+        manager.create_slot("alice", "2", "Second");
+        let mut res = Resources::new();
+        res.insert(manager);
+        let mut system = AutoSaveSystem::<Progress>::in_slot("progress.ron");
+        System::setup(&mut system, &mut res);
+
+        res.fetch_mut::<Dirty<Progress>>().write().ready = true;
+        step(&mut system, &res, 0.1);
+        assert!(res
+            .fetch::<SaveManager>()
+            .active_file("progress.ron")
+            .is_none());
+
+        res.fetch_mut::<SaveManager>().select_slot("alice", "1");
+        step(&mut system, &res, 0.1);
+        {
+            let mut progress = res.fetch_mut::<Dirty<Progress>>();
+            progress.write().ready = true;
+            progress.write().level = 7;
+        }
+        step(&mut system, &res, 0.1);
+        let first = res
+            .fetch::<SaveManager>()
+            .active_file("progress.ron")
+            .unwrap();
+        assert_eq!(saved_level(&first), Some(7));
+
+        res.fetch_mut::<SaveManager>().select_slot("alice", "2");
+        step(&mut system, &res, 0.1);
+        assert_eq!(*res.fetch::<Dirty<Progress>>().read(), Progress::default());
+
+        res.fetch_mut::<SaveManager>().select_slot("alice", "1");
+        step(&mut system, &res, 0.1);
+        assert_eq!(res.fetch::<Dirty<Progress>>().read().level, 7);
+        let slot = res.fetch::<SaveManager>().slot("alice", "1").unwrap();
+        assert!(slot.metadata.playtime >= 0.0);
+        assert_eq!(slot.metadata.display_name, "First");
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Team {
+        Red,
+        Blue(u32),
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Component)]
+    struct Member {
+        team: Team,
+        name: Option<String>,
+        score: u32,
+    }
+
+    #[test]
+    fn world_snapshot_round_trip() {
+        let mut world = World::new();
+        world.register::<Persist>();
+        world.register::<Transform>();
+        world.register::<Member>();
+        let mut transform = Transform::default();
+        transform.translation = Vector3::new(1.0, 2.5, -3.0);
+        let member = Member {
+            team: Team::Blue(2),
+            name: None,
+            score: 10,
+        };
+        world
+            .create_entity()
+            .with(Persist)
+            .with(transform.clone())
+            .with(member.clone())
+            .build();
+        world
+            .create_entity()
+            .with(Member {
+                team: Team::Red,
+                name: Some("Temporary".to_string()),
+                score: 3,
+            })
+            .build();
+
+        let registry = SnapshotRegistry::new().with::<Member>("member");
+        let mut files = vec!["world.ron"];
+        #[cfg(all(feature = "json", feature = "gzip"))]
+        {
+            files.push("world.json.gz");
+        }
+        #[cfg(feature = "cbor")]
+        {
+            files.push("world.bin");
+        }
+        for file in files {
+            let path = save_file("snapshot", file);
+            assert!(registry.save(&world, &path));
+            let mut loaded = World::new();
+            let entities = registry.load(&mut loaded, &path).unwrap();
+            assert_eq!(entities.len(), 1);
+            assert_eq!(
+                loaded.read_storage::<Transform>().get(entities[0]),
+                Some(&transform)
+            );
+            assert_eq!(
+                loaded.read_storage::<Member>().get(entities[0]),
+                Some(&member)
+            );
+            assert!(loaded.read_storage::<Persist>().get(entities[0]).is_some());
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use amethyst::core::cgmath::Deg;
+use amethyst::ecs::{Join, ReadExpect, System, Write, WriteStorage};
+use amethyst::input::{Bindings, InputHandler};
+use amethyst::renderer::{Camera, Projection, ScreenDimensions};
+use dirty::Dirty;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ShouldSave;
+
+/// The settings found in most games, usually saved with an `AutoSaveSystem<GameSettings>`
+/// and stored as a `Dirty<GameSettings>` resource.
+///
+/// They are applied by the `GameSettingsSystem` (key bindings and field of view),
+/// the `AudioSettingsSystem` (volumes) and the `FPSRotationRhusicsSystem` (mouse sensitivity).
+///
+/// Use `descriptions` to build a settings menu, and `get` and `set` to edit the values
+/// from it. Values outside of the described ranges are clamped by `validate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameSettings {
+    /// Degrees of rotation per pixel of mouse movement.
+    pub mouse_sensitivity: f32,
+    pub invert_y: bool,
+    /// Multiplies the music and the effects volumes.
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    /// Vertical field of view of perspective cameras, in degrees.
+    pub fov: f32,
+    /// Replaces the bindings of the `InputHandler<String, String>` when set.
+    key_bindings: Option<Bindings<String, String>>,
+    /// Identifies the key bindings, so the `GameSettingsSystem` only applies them when
+    /// they change. Every new or loaded `GameSettings` gets a new one.
+    #[serde(skip, default = "next_bindings_id")]
+    bindings_id: usize,
+}
+
+static BINDINGS_IDS: AtomicUsize = AtomicUsize::new(0);
+
+fn next_bindings_id() -> usize {
+    BINDINGS_IDS.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            mouse_sensitivity: 0.2,
+            invert_y: false,
+            master_volume: 1.0,
+            music_volume: 1.0,
+            effects_volume: 1.0,
+            fov: 90.0,
+            key_bindings: None,
+            bindings_id: next_bindings_id(),
+        }
+    }
+}
+
+impl ShouldSave for GameSettings {
+    fn save_ready(&self) -> bool {
+        true
+    }
+    fn set_save_ready(&mut self, _ready: bool) {}
+}
+
+/// How a setting is edited in a settings menu.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingKind {
+    /// A number between `min` and `max`, changed by increments of `step`.
+    Slider { min: f32, max: f32, step: f32 },
+    /// A boolean.
+    Toggle,
+    /// The key bindings. Edited through `GameSettings::set_key_bindings`.
+    KeyBindings,
+}
+
+/// Describes a setting of `GameSettings`, to generate a settings menu.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingDescription {
+    /// The key used with `GameSettings::get` and `GameSettings::set`.
+    pub key: &'static str,
+    /// A name that can be shown to the player.
+    pub label: &'static str,
+    /// Used to group settings in tabs or sections.
+    pub category: &'static str,
+    pub kind: SettingKind,
+}
+
+impl SettingDescription {
+    fn new(
+        key: &'static str,
+        label: &'static str,
+        category: &'static str,
+        kind: SettingKind,
+    ) -> Self {
+        SettingDescription {
+            key,
+            label,
+            category,
+            kind,
+        }
+    }
+}
+
+/// The value of a setting, as edited by a settings menu.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingValue {
+    Number(f32),
+    Bool(bool),
+}
+
+impl GameSettings {
+    /// The description of every setting, in the order they should be shown.
+    pub fn descriptions() -> Vec<SettingDescription> {
+        let slider = |min, max, step| SettingKind::Slider { min, max, step };
+        vec![
+            SettingDescription::new(
+                "mouse_sensitivity",
+                "Mouse Sensitivity",
+                "Controls",
+                slider(0.01, 2.0, 0.01),
+            ),
+            SettingDescription::new("invert_y", "Invert Y Axis", "Controls", SettingKind::Toggle),
+            SettingDescription::new(
+                "key_bindings",
+                "Key Bindings",
+                "Controls",
+                SettingKind::KeyBindings,
+            ),
+            SettingDescription::new(
+                "master_volume",
+                "Master Volume",
+                "Audio",
+                slider(0.0, 1.0, 0.05),
+            ),
+            SettingDescription::new(
+                "music_volume",
+                "Music Volume",
+                "Audio",
+                slider(0.0, 1.0, 0.05),
+            ),
+            SettingDescription::new(
+                "effects_volume",
+                "Effects Volume",
+                "Audio",
+                slider(0.0, 1.0, 0.05),
+            ),
+            SettingDescription::new("fov", "Field of View", "Video", slider(60.0, 120.0, 1.0)),
+        ]
+    }
+
+    /// The description of a single setting.
+    pub fn description(key: &str) -> Option<SettingDescription> {
+        GameSettings::descriptions()
+            .into_iter()
+            .find(|d| d.key == key)
+    }
+
+    /// The value of a slider or toggle setting.
+    pub fn get(&self, key: &str) -> Option<SettingValue> {
+        match key {
+            "mouse_sensitivity" => Some(SettingValue::Number(self.mouse_sensitivity)),
+            "invert_y" => Some(SettingValue::Bool(self.invert_y)),
+            "master_volume" => Some(SettingValue::Number(self.master_volume)),
+            "music_volume" => Some(SettingValue::Number(self.music_volume)),
+            "effects_volume" => Some(SettingValue::Number(self.effects_volume)),
+            "fov" => Some(SettingValue::Number(self.fov)),
+            _ => None,
+        }
+    }
+
+    /// Changes a slider or toggle setting. Numbers are clamped to the range of the setting.
+    /// Returns false if the key is unknown or the value has the wrong type.
+    pub fn set(&mut self, key: &str, value: SettingValue) -> bool {
+        let valid = match (key, value) {
+            ("invert_y", SettingValue::Bool(b)) => {
+                self.invert_y = b;
+                true
+            }
+            (_, SettingValue::Number(n)) => match GameSettings::range(key) {
+                Some((min, max)) => self.set_number(key, clamp(n, min, max)),
+                None => false,
+            },
+            _ => false,
+        };
+        if !valid {
+            warn!("Invalid value {:?} for the setting {}", value, key);
+        }
+        valid
+    }
+
+    /// Clamps every number to the range of its setting.
+    /// Returns false if some values were invalid.
+    pub fn validate(&mut self) -> bool {
+        let mut valid = true;
+        for description in GameSettings::descriptions() {
+            if let SettingKind::Slider { min, max, .. } = description.kind {
+                if let Some(SettingValue::Number(n)) = self.get(description.key) {
+                    let clamped = clamp(n, min, max);
+                    if clamped != n {
+                        warn!(
+                            "The setting {} is out of range: {}. Using {} instead.",
+                            description.key, n, clamped
+                        );
+                        valid = false;
+                        self.set_number(description.key, clamped);
+                    }
+                }
+            }
+        }
+        valid
+    }
+
+    /// True if every number is within the range of its setting.
+    pub fn is_valid(&self) -> bool {
+        GameSettings::descriptions().into_iter().all(|description| {
+            match (description.kind, self.get(description.key)) {
+                (SettingKind::Slider { min, max, .. }, Some(SettingValue::Number(n))) => {
+                    clamp(n, min, max) == n
+                }
+                _ => true,
+            }
+        })
+    }
+
+    fn range(key: &str) -> Option<(f32, f32)> {
+        match GameSettings::description(key).map(|d| d.kind) {
+            Some(SettingKind::Slider { min, max, .. }) => Some((min, max)),
+            _ => None,
+        }
+    }
+
+    fn set_number(&mut self, key: &str, value: f32) -> bool {
+        match key {
+            "mouse_sensitivity" => self.mouse_sensitivity = value,
+            "master_volume" => self.master_volume = value,
+            "music_volume" => self.music_volume = value,
+            "effects_volume" => self.effects_volume = value,
+            "fov" => self.fov = value,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The key bindings replacing the ones of the `InputHandler<String, String>`, if any.
+    pub fn key_bindings(&self) -> Option<&Bindings<String, String>> {
+        self.key_bindings.as_ref()
+    }
+
+    /// Changes the key bindings. None keeps the bindings currently in use.
+    pub fn set_key_bindings(&mut self, bindings: Option<Bindings<String, String>>) {
+        self.key_bindings = bindings;
+        self.bindings_id = next_bindings_id();
+    }
+
+    /// The music volume multiplied by the master volume.
+    pub fn effective_music_volume(&self) -> f32 {
+        self.master_volume * self.music_volume
+    }
+
+    /// The effects volume multiplied by the master volume.
+    pub fn effective_effects_volume(&self) -> f32 {
+        self.master_volume * self.effects_volume
+    }
+
+    /// The mouse sensitivity for each axis, with the y axis inverted if requested.
+    pub fn sensitivity(&self) -> (f32, f32) {
+        let y = if self.invert_y {
+            -self.mouse_sensitivity
+        } else {
+            self.mouse_sensitivity
+        };
+        (self.mouse_sensitivity, y)
+    }
+
+    /// A perspective projection using the field of view of the settings.
+    /// Use it for the cameras you create, the `GameSettingsSystem` only updates
+    /// the cameras existing when the field of view changes.
+    pub fn projection(&self, aspect_ratio: f32) -> Projection {
+        Projection::perspective(aspect_ratio, Deg(self.fov))
+    }
+}
+
+/// NaN fails both comparisons and is replaced by the minimum.
+fn clamp(value: f32, min: f32, max: f32) -> f32 {
+    if value >= min && value <= max {
+        value
+    } else if value > max {
+        max
+    } else {
+        min
+    }
+}
+
+/// Applies the `Dirty<GameSettings>` resource when it changes:
+/// invalid values are clamped, the key bindings replace the ones of
+/// the `InputHandler<String, String>` and the field of view is set on
+/// every perspective `Camera`.
+///
+/// The volumes are applied by the `AudioSettingsSystem` and the mouse sensitivity
+/// by the `FPSRotationRhusicsSystem`.
+#[derive(Default)]
+pub struct GameSettingsSystem {
+    fov: Option<f32>,
+    /// The `GameSettings::bindings_id` of the key bindings last applied.
+    bindings: Option<usize>,
+}
+
+impl<'a> System<'a> for GameSettingsSystem {
+    type SystemData = (
+        Option<Write<'a, Dirty<GameSettings>>>,
+        Option<Write<'a, InputHandler<String, String>>>,
+        Option<ReadExpect<'a, ScreenDimensions>>,
+        WriteStorage<'a, Camera>,
+    );
+
+    fn run(&mut self, (settings, input, dimensions, mut cameras): Self::SystemData) {
+        let mut settings = match settings {
+            Some(s) => s,
+            None => return,
+        };
+        // The dirty flag is cleared by the AutoSaveSystem, which can run first,
+        // so the settings are compared to the ones last applied instead.
+        if !settings.read().is_valid() {
+            settings.write().validate();
+        }
+        let settings = settings.read();
+
+        if let Some(mut input) = input {
+            if self.bindings != Some(settings.bindings_id) {
+                if let Some(ref bindings) = settings.key_bindings {
+                    input.bindings = bindings.clone();
+                }
+                self.bindings = Some(settings.bindings_id);
+            }
+        }
+
+        if self.fov != Some(settings.fov) {
+            if let Some(dimensions) = dimensions {
+                for camera in (&mut cameras).join() {
+                    // Orthographic projections have a 1 there, perspective ones a 0.
+                    if camera.proj.w.w == 0.0 {
+                        *camera = Camera::from(settings.projection(dimensions.aspect_ratio()));
+                    }
+                }
+                self.fov = Some(settings.fov);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use dirty::Dirty;
+    use *;
+
+    #[test]
+    fn settings_are_clamped() {
+        let mut settings = GameSettings::default();
+        assert!(settings.validate());
+        assert!(settings.set("music_volume", SettingValue::Number(1.5)));
+        assert_eq!(settings.music_volume, 1.0);
+        assert!(settings.set("fov", SettingValue::Number(10.0)));
+        assert_eq!(settings.fov, 60.0);
+
+        settings.mouse_sensitivity = ::std::f32::NAN;
+        assert!(!settings.validate());
+        assert_eq!(settings.mouse_sensitivity, 0.01);
+    }
+
+    #[test]
+    fn descriptions_match_values() {
+        let mut settings = GameSettings::default();
+        for description in GameSettings::descriptions() {
+            match description.kind {
+                SettingKind::Slider { min, max, step } => {
+                    assert!(min < max && step > 0.0, "{}", description.key);
+                    let value = settings.get(description.key);
+                    assert!(value.is_some(), "{}", description.key);
+                    assert!(settings.set(description.key, SettingValue::Number(max)));
+                    assert_eq!(
+                        settings.get(description.key),
+                        Some(SettingValue::Number(max))
+                    );
+                }
+                SettingKind::Toggle => {
+                    assert!(settings.set(description.key, SettingValue::Bool(true)));
+                    assert_eq!(
+                        settings.get(description.key),
+                        Some(SettingValue::Bool(true))
+                    );
+                }
+                SettingKind::KeyBindings => assert_eq!(settings.get(description.key), None),
+            }
+        }
+        assert!(!settings.set("fov", SettingValue::Bool(true)));
+        assert!(!settings.set("unknown", SettingValue::Number(1.0)));
+        assert_eq!(settings.sensitivity(), (2.0, -2.0));
+    }
+
+    #[test]
+    fn settings_apply_after_dirty_is_cleared() {
+        let mut world = World::new();
+        world.register::<Camera>();
+        world.add_resource(ScreenDimensions::new(800, 400, 1.0));
+        world.add_resource(Dirty::new(GameSettings::default()));
+        let camera = world
+            .create_entity()
+            .with(Camera::from(GameSettings::default().projection(2.0)))
+            .build();
+        let mut system = GameSettingsSystem::default();
+        system.run_now(&world.res);
+        {
+            let mut settings = world.write_resource::<Dirty<GameSettings>>();
+            settings.write().fov = 100.0;
+            // Saved by the AutoSaveSystem before this system ran.
+            settings.clear();
+        }
+        system.run_now(&world.res);
+
+        let mut settings = GameSettings::default();
+        settings.fov = 100.0;
+        assert_eq!(
+            world.read_storage::<Camera>().get(camera).unwrap().proj,
+            Camera::from(settings.projection(2.0)).proj
+        );
+    }
+
+    #[test]
+    fn key_bindings_apply_when_changed() {
+        let mut world = World::new();
+        world.register::<Camera>();
+        world.add_resource(InputHandler::<String, String>::new());
+        world.add_resource(Dirty::new(GameSettings::default()));
+        let mut system = GameSettingsSystem::default();
+        system.run_now(&world.res);
+        let applied = system.bindings;
+        assert!(applied.is_some());
+        system.run_now(&world.res);
+        assert_eq!(system.bindings, applied);
+
+        world
+            .write_resource::<Dirty<GameSettings>>()
+            .write()
+            .set_key_bindings(Some(Bindings::default()));
+        system.run_now(&world.res);
+        assert_ne!(system.bindings, applied);
+        assert_eq!(
+            system.bindings,
+            Some(
+                world
+                    .read_resource::<Dirty<GameSettings>>()
+                    .read()
+                    .bindings_id
+            )
+        );
+    }
+
+    #[test]
+    fn settings_load_with_defaults() {
+        let settings: GameSettings = ron::de::from_str("(fov: 100.0, invert_y: true)").unwrap();
+        assert_eq!(settings.fov, 100.0);
+        assert!(settings.invert_y);
+        assert_eq!(settings.music_volume, 1.0);
+        assert!(settings.key_bindings().is_none());
+    }
+}
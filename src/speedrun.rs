@@ -0,0 +1,535 @@
+use amethyst::core::cgmath::Point3;
+use amethyst::core::timing::Time;
+use amethyst::ecs::{
+    Component, DenseVecStorage, Entity, Join, NullStorage, Read, ReadStorage, Resources, System,
+    SystemData, Write, WriteStorage,
+};
+use amethyst::shrev::{EventChannel, ReaderId};
+use amethyst_rhusics::rhusics_core::ContactEvent;
+use dirty::Dirty;
+use std::collections::BTreeMap;
+
+use {Clock, ShouldSave, TimerFormat, UiAutoText};
+
+/// A trigger volume of a speedrun course.
+/// Add it to rhusics colliders generating contacts with the collider of the `Runner`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RunTrigger {
+    /// The run starts when the runner leaves this volume.
+    Start,
+    /// Checkpoints have to be reached in order, starting from 0.
+    Checkpoint(usize),
+    /// Ends the run, once every checkpoint was reached.
+    Finish,
+}
+
+impl Component for RunTrigger {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// The entity whose contacts with the `RunTrigger` volumes drive the `RunTimer`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Runner;
+
+impl Component for Runner {
+    type Storage = NullStorage<Self>;
+}
+
+/// The records of a course.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CourseRecords {
+    /// The splits of the fastest run, the last one being its finish time.
+    /// Empty until a run is finished.
+    pub personal_best: Vec<f64>,
+    /// The fastest time of each segment between two splits, across every run.
+    pub best_segments: Vec<f64>,
+}
+
+impl CourseRecords {
+    pub fn best_time(&self) -> Option<f64> {
+        self.personal_best.last().cloned()
+    }
+
+    /// The time of a run made of every best segment.
+    pub fn sum_of_best(&self) -> Option<f64> {
+        if self.best_segments.is_empty() {
+            None
+        } else {
+            Some(self.best_segments.iter().sum())
+        }
+    }
+}
+
+/// The records of every course, by name.
+/// Save them with an `AutoSaveSystem<RunRecords>`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunRecords {
+    pub courses: BTreeMap<String, CourseRecords>,
+}
+
+impl RunRecords {
+    pub fn course(&self, name: &str) -> Option<&CourseRecords> {
+        self.courses.get(name)
+    }
+}
+
+impl ShouldSave for RunRecords {
+    fn save_ready(&self) -> bool {
+        true
+    }
+    fn set_save_ready(&mut self, _ready: bool) {}
+}
+
+/// A split of the current run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunSplit {
+    /// Seconds since the start of the run.
+    pub time: f64,
+    /// Difference with the same split of the personal best. Negative when ahead.
+    pub delta: Option<f64>,
+    /// Difference between the segment ended by this split and the best one.
+    /// Negative for a new best segment.
+    pub segment_delta: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    /// Waiting for the runner to enter the start volume.
+    Waiting,
+    /// In the start volume. The run starts when the runner leaves it.
+    InStart,
+    Running,
+    Finished,
+}
+
+/// The timer of the current speedrun, driven by the `RunTriggerSystem`.
+///
+/// Each checkpoint and the finish record a split, compared with the personal best
+/// and the best segments of the course in the `Dirty<RunRecords>` resource.
+/// The records are updated as the run goes.
+pub struct RunTimer {
+    /// The name of the course in the `RunRecords`.
+    pub course: String,
+    pub format: TimerFormat,
+    pub clock: Clock,
+    /// The number of checkpoints to reach before the finish.
+    /// Updated by the `RunTriggerSystem` from the `RunTrigger` components.
+    pub checkpoints: usize,
+    state: RunState,
+    elapsed: f64,
+    splits: Vec<RunSplit>,
+}
+
+impl Default for RunTimer {
+    fn default() -> Self {
+        RunTimer::new("")
+    }
+}
+
+impl RunTimer {
+    pub fn new(course: &str) -> Self {
+        RunTimer {
+            course: course.to_string(),
+            format: TimerFormat::default(),
+            clock: Clock::Game,
+            checkpoints: 0,
+            state: RunState::Waiting,
+            elapsed: 0.0,
+            splits: vec![],
+        }
+    }
+
+    pub fn with_format(mut self, format: TimerFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn state(&self) -> RunState {
+        self.state
+    }
+
+    /// Seconds since the start of the run.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn splits(&self) -> &[RunSplit] {
+        &self.splits
+    }
+
+    pub fn last_split(&self) -> Option<&RunSplit> {
+        self.splits.last()
+    }
+
+    /// Cancels the run and waits for the runner to enter the start volume again.
+    pub fn reset(&mut self) {
+        self.state = RunState::Waiting;
+        self.elapsed = 0.0;
+        self.splits.clear();
+    }
+
+    /// Starts a new run from zero.
+    pub fn start(&mut self) {
+        self.reset();
+        self.state = RunState::Running;
+    }
+
+    /// Records the split of a checkpoint. Returns false if the run isn't going or
+    /// the checkpoint isn't the next one.
+    pub fn checkpoint(&mut self, index: usize, records: &mut RunRecords) -> bool {
+        if self.state != RunState::Running || index != self.splits.len() {
+            return false;
+        }
+        self.split(records, false);
+        true
+    }
+
+    /// Records the finish time and updates the personal best.
+    /// Returns false if the run isn't going or some checkpoints were missed.
+    pub fn finish(&mut self, records: &mut RunRecords) -> bool {
+        if self.state != RunState::Running || self.splits.len() != self.checkpoints {
+            return false;
+        }
+        self.split(records, true);
+        self.state = RunState::Finished;
+        true
+    }
+
+    fn split(&mut self, records: &mut RunRecords, finish: bool) {
+        let index = self.splits.len();
+        let segment = self.elapsed - self.splits.last().map(|s| s.time).unwrap_or(0.0);
+        let course = records
+            .courses
+            .entry(self.course.clone())
+            .or_insert_with(CourseRecords::default);
+
+        let delta = {
+            let pb = &course.personal_best;
+            // The last split of the personal best is its finish.
+            let target = if finish {
+                pb.last()
+            } else if index + 1 < pb.len() {
+                pb.get(index)
+            } else {
+                None
+            };
+            target.map(|t| self.elapsed - t)
+        };
+        let segment_delta = course.best_segments.get(index).map(|t| segment - t);
+        self.splits.push(RunSplit {
+            time: self.elapsed,
+            delta,
+            segment_delta,
+        });
+
+        if segment_delta
+            .map(|d| d < 0.0)
+            .unwrap_or(index == course.best_segments.len())
+        {
+            if index < course.best_segments.len() {
+                course.best_segments[index] = segment;
+            } else {
+                course.best_segments.push(segment);
+            }
+        }
+        if finish && delta.map(|d| d < 0.0).unwrap_or(true) {
+            course.personal_best = self.splits.iter().map(|s| s.time).collect();
+        }
+    }
+
+    /// The time of the run.
+    pub fn time_text(&self) -> String {
+        self.format.format(self.elapsed)
+    }
+
+    /// The delta of the last split with the personal best, signed. Empty without one.
+    pub fn delta_text(&self) -> String {
+        self.signed(self.last_split().and_then(|s| s.delta))
+    }
+
+    /// The delta of the last segment with the best one, signed. Empty without one.
+    pub fn segment_delta_text(&self) -> String {
+        self.signed(self.last_split().and_then(|s| s.segment_delta))
+    }
+
+    fn signed(&self, delta: Option<f64>) -> String {
+        match delta {
+            Some(d) if d >= 0.0 => format!("+{}", self.format.format(d)),
+            Some(d) => self.format.format(d),
+            None => String::new(),
+        }
+    }
+}
+
+/// What a `RunDisplay` shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RunInfo {
+    Time,
+    /// The delta of the last split with the personal best.
+    Delta,
+    /// The delta of the last segment with the best one.
+    SegmentDelta,
+    /// The finish time of the personal best.
+    PersonalBest,
+    /// The sum of the best segments.
+    SumOfBest,
+}
+
+/// Add a `UiAutoTextSystem<RunDisplay>`, for example with `ExtraBundle::with_auto_text`,
+/// to show it in the `UiText` of the entity.
+/// Add a `UiAutoTextSystem<RunDisplay>` to show it in the `UiText` of the entity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunDisplay {
+    pub info: RunInfo,
+    text: String,
+}
+
+impl RunDisplay {
+    pub fn new(info: RunInfo) -> Self {
+        RunDisplay {
+            info,
+            text: String::new(),
+        }
+    }
+}
+
+impl Component for RunDisplay {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl UiAutoText for RunDisplay {
+    fn get_text(&self) -> String {
+        self.text.clone()
+    }
+}
+
+/// Drives the `RunTimer` with the contacts between the `Runner` and the `RunTrigger` volumes,
+/// and updates the `RunDisplay` components.
+#[derive(Default)]
+pub struct RunTriggerSystem {
+    contact_reader: Option<ReaderId<ContactEvent<Entity, Point3<f32>>>>,
+}
+
+impl<'a> System<'a> for RunTriggerSystem {
+    type SystemData = (
+        Read<'a, EventChannel<ContactEvent<Entity, Point3<f32>>>>,
+        ReadStorage<'a, Runner>,
+        ReadStorage<'a, RunTrigger>,
+        WriteStorage<'a, RunDisplay>,
+        Write<'a, RunTimer>,
+        Write<'a, Dirty<RunRecords>>,
+        Read<'a, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (contacts, runners, triggers, mut displays, mut timer, mut records, time): Self::SystemData,
+    ) {
+        let mut touched = vec![];
+        for contact in contacts.read(self.contact_reader.as_mut().unwrap()) {
+            let (a, b) = contact.bodies;
+            let trigger = if runners.get(a).is_some() {
+                triggers.get(b)
+            } else if runners.get(b).is_some() {
+                triggers.get(a)
+            } else {
+                None
+            };
+            if let Some(trigger) = trigger {
+                if !touched.contains(trigger) {
+                    touched.push(*trigger);
+                }
+            }
+        }
+
+        timer.checkpoints = triggers
+            .join()
+            .filter_map(|t| match *t {
+                RunTrigger::Checkpoint(i) => Some(i + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+
+        if timer.state == RunState::Running {
+            let delta = timer.clock.delta_seconds(&time);
+            timer.elapsed += delta;
+        }
+        if touched.contains(&RunTrigger::Start) {
+            timer.reset();
+            timer.state = RunState::InStart;
+        } else if timer.state == RunState::InStart {
+            timer.start();
+        }
+
+        touched.sort_by_key(|t| match *t {
+            RunTrigger::Start => 0,
+            RunTrigger::Checkpoint(i) => i + 1,
+            RunTrigger::Finish => ::std::usize::MAX,
+        });
+        for trigger in touched {
+            match trigger {
+                RunTrigger::Checkpoint(i) if timer.state == RunState::Running => {
+                    timer.checkpoint(i, records.write());
+                }
+                RunTrigger::Finish if timer.state == RunState::Running => {
+                    timer.finish(records.write());
+                }
+                _ => {}
+            }
+        }
+
+        let course = records.read().course(&timer.course);
+        for display in (&mut displays).join() {
+            display.text = match display.info {
+                RunInfo::Time => timer.time_text(),
+                RunInfo::Delta => timer.delta_text(),
+                RunInfo::SegmentDelta => timer.segment_delta_text(),
+                RunInfo::PersonalBest => course
+                    .and_then(|c| c.best_time())
+                    .map(|t| timer.format.format(t))
+                    .unwrap_or_default(),
+                RunInfo::SumOfBest => course
+                    .and_then(|c| c.sum_of_best())
+                    .map(|t| timer.format.format(t))
+                    .unwrap_or_default(),
+            };
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.contact_reader = Some(
+            res.fetch_mut::<EventChannel<ContactEvent<Entity, Point3<f32>>>>()
+                .register_reader(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amethyst::core::cgmath::{Point3, Vector3};
+    use amethyst::shrev::EventChannel;
+    use amethyst_rhusics::collision::Contact;
+    use amethyst_rhusics::rhusics_core::{CollisionStrategy, ContactEvent};
+    use dirty::Dirty;
+    use *;
+
+    fn step(
+        world: &mut World,
+        system: &mut RunTriggerSystem,
+        seconds: f32,
+        touched: &[(Entity, Entity)],
+    ) {
+        for &bodies in touched {
+            world
+                .write_resource::<EventChannel<ContactEvent<Entity, Point3<f32>>>>()
+                .single_write(ContactEvent::new(
+                    bodies,
+                    Contact::new(CollisionStrategy::CollisionOnly, Vector3::unit_y(), 0.0),
+                ));
+        }
+        world.write_resource::<Time>().set_delta_seconds(seconds);
+        system.run_now(&world.res);
+    }
+
+    #[test]
+    fn runs_are_compared_with_records() {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        world
+            .add_resource(RunTimer::new("canyon").with_format(TimerFormat::parse("s.mm").unwrap()));
+        let mut records = RunRecords::default();
+        records.courses.insert(
+            "canyon".to_string(),
+            CourseRecords {
+                personal_best: vec![2.0, 5.0],
+                best_segments: vec![1.5, 3.0],
+            },
+        );
+        world.add_resource(Dirty::new(records));
+        let mut system = RunTriggerSystem::default();
+        System::setup(&mut system, &mut world.res);
+
+        let runner = world.create_entity().with(Runner).build();
+        let start = world.create_entity().with(RunTrigger::Start).build();
+        let checkpoint = world
+            .create_entity()
+            .with(RunTrigger::Checkpoint(0))
+            .build();
+        let finish = world.create_entity().with(RunTrigger::Finish).build();
+        let delta = world
+            .create_entity()
+            .with(RunDisplay::new(RunInfo::Delta))
+            .build();
+
+        step(&mut world, &mut system, 0.5, &[(runner, start)]);
+        step(&mut world, &mut system, 0.5, &[(start, runner)]);
+        assert_eq!(world.read_resource::<RunTimer>().state(), RunState::InStart);
+        // Leaving the start volume.
+        step(&mut world, &mut system, 0.5, &[]);
+        assert_eq!(world.read_resource::<RunTimer>().state(), RunState::Running);
+        // The checkpoint wasn't reached yet.
+        step(&mut world, &mut system, 1.0, &[(runner, finish)]);
+        assert_eq!(world.read_resource::<RunTimer>().state(), RunState::Running);
+        step(&mut world, &mut system, 0.75, &[(runner, checkpoint)]);
+        {
+            let timer = world.read_resource::<RunTimer>();
+            assert_eq!(
+                timer.splits(),
+                &[RunSplit {
+                    time: 1.75,
+                    delta: Some(-0.25),
+                    segment_delta: Some(0.25),
+                }]
+            );
+            assert_eq!(
+                world
+                    .read_storage::<RunDisplay>()
+                    .get(delta)
+                    .unwrap()
+                    .get_text(),
+                "-0.25"
+            );
+        }
+
+        step(&mut world, &mut system, 2.5, &[(runner, finish)]);
+        step(&mut world, &mut system, 1.0, &[]);
+        let timer = world.read_resource::<RunTimer>();
+        assert_eq!(timer.state(), RunState::Finished);
+        assert_eq!(timer.elapsed(), 4.25);
+        assert_eq!(timer.segment_delta_text(), "-0.50");
+        let records = world.read_resource::<Dirty<RunRecords>>();
+        assert!(records.dirty());
+        assert_eq!(
+            records.read().course("canyon"),
+            Some(&CourseRecords {
+                personal_best: vec![1.75, 4.25],
+                best_segments: vec![1.5, 2.5],
+            })
+        );
+    }
+
+    #[test]
+    fn first_run_sets_records() {
+        let mut records = RunRecords::default();
+        let mut timer = RunTimer::new("first");
+        timer.checkpoints = 1;
+        assert!(!timer.checkpoint(0, &mut records));
+        timer.start();
+        timer.elapsed = 3.0;
+        assert!(!timer.finish(&mut records));
+        assert!(timer.checkpoint(0, &mut records));
+        assert!(!timer.checkpoint(0, &mut records));
+        timer.elapsed = 7.0;
+        assert!(timer.finish(&mut records));
+        assert_eq!(timer.last_split().unwrap().delta, None);
+        assert_eq!(timer.delta_text(), "");
+
+        let course = records.course("first").unwrap();
+        assert_eq!(course.best_time(), Some(7.0));
+        assert_eq!(course.sum_of_best(), Some(7.0));
+    }
+}
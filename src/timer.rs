@@ -0,0 +1,869 @@
+use amethyst::core::timing::Time;
+use amethyst::ecs::{
+    Component, DenseVecStorage, Entities, Entity, Join, LazyUpdate, Read, System, VecStorage,
+    World, Write, WriteStorage,
+};
+use amethyst::shrev::EventChannel;
+use amethyst::ui::UiText;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The time used to count down a timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Clock {
+    /// Scaled by `Time::time_scale`, so it stops while the game is paused.
+    Game,
+    /// Real time, even while the game is paused.
+    Real,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::Game
+    }
+}
+
+impl Clock {
+    /// Seconds since the last frame.
+    pub fn delta_seconds(&self, time: &Time) -> f64 {
+        match *self {
+            Clock::Game => time.delta_seconds() as f64,
+            Clock::Real => time.delta_real_seconds() as f64,
+        }
+    }
+
+    /// Seconds since the start of the game.
+    pub fn absolute_seconds(&self, time: &Time) -> f64 {
+        match *self {
+            Clock::Game => time.absolute_time_seconds(),
+            Clock::Real => time.absolute_real_time_seconds(),
+        }
+    }
+}
+
+/// Limits how often something can happen, for example an attack or a jump.
+/// Only stores when it was last triggered, so it doesn't need to be updated every frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cooldown {
+    /// Seconds to wait after each trigger.
+    pub duration: f64,
+    pub clock: Clock,
+    /// When it was last triggered, in seconds of the clock.
+    #[serde(skip)]
+    last: Option<f64>,
+}
+
+impl Cooldown {
+    pub fn new(duration: f64) -> Self {
+        Cooldown {
+            duration,
+            clock: Clock::Game,
+            last: None,
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// When it was last triggered, in seconds of the clock.
+    pub fn last(&self) -> Option<f64> {
+        self.last
+    }
+
+    /// Seconds left before it is ready.
+    pub fn remaining(&self, time: &Time) -> f64 {
+        match self.last {
+            Some(last) => (last + self.duration - self.clock.absolute_seconds(time)).max(0.0),
+            None => 0.0,
+        }
+    }
+
+    pub fn is_ready(&self, time: &Time) -> bool {
+        self.remaining(time) <= 0.0
+    }
+
+    /// Starts the cooldown if it is ready. Returns false if it wasn't.
+    pub fn trigger(&mut self, time: &Time) -> bool {
+        if self.is_ready(time) {
+            self.start(time);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Starts the cooldown, even if it wasn't ready.
+    pub fn start(&mut self, time: &Time) {
+        self.last = Some(self.clock.absolute_seconds(time));
+    }
+
+    /// Makes it ready right away.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// Fires once or repeatedly after `duration` seconds of its clock.
+/// Updated by the `TimerSystem` when it is part of a `Timers`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timer {
+    /// Identifies the timer in its `Timers` and in the `TimerFired` events.
+    pub tag: String,
+    pub duration: f64,
+    pub repeat: bool,
+    pub clock: Clock,
+    /// The name of the callback of the `TimerCallbacks` run each time the timer fires.
+    pub callback: Option<String>,
+    elapsed: f64,
+    paused: bool,
+    finished: bool,
+}
+
+impl Timer {
+    /// Fires once, after `duration` seconds.
+    pub fn once(tag: &str, duration: f64) -> Self {
+        Timer {
+            tag: tag.to_string(),
+            duration,
+            repeat: false,
+            clock: Clock::Game,
+            callback: None,
+            elapsed: 0.0,
+            paused: false,
+            finished: false,
+        }
+    }
+
+    /// Fires every `duration` seconds.
+    pub fn repeating(tag: &str, duration: f64) -> Self {
+        Timer {
+            repeat: true,
+            ..Timer::once(tag, duration)
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_callback(mut self, name: &str) -> Self {
+        self.callback = Some(name.to_string());
+        self
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Restarts the timer from zero. Finished timers start again.
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.finished = false;
+    }
+
+    /// Seconds since the timer started or last fired.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Seconds left before the timer fires.
+    pub fn remaining(&self) -> f64 {
+        (self.duration - self.elapsed).max(0.0)
+    }
+
+    /// True once a timer firing once has fired.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Advances the timer. Returns the number of times it fired.
+    pub fn tick(&mut self, delta: f64) -> u32 {
+        if self.paused || self.finished {
+            return 0;
+        }
+        self.elapsed += delta;
+        if self.elapsed < self.duration {
+            return 0;
+        }
+        if !self.repeat {
+            self.elapsed = self.duration;
+            self.finished = true;
+            return 1;
+        }
+        if self.duration <= 0.0 {
+            self.elapsed = 0.0;
+            return 1;
+        }
+        let fired = (self.elapsed / self.duration).floor();
+        self.elapsed -= fired * self.duration;
+        fired as u32
+    }
+}
+
+/// A set of timers, identified by their tags.
+/// Add it to an entity for timers attached to it, or as a resource for global timers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timers {
+    timers: Vec<Timer>,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Timers::default()
+    }
+
+    pub fn with(mut self, timer: Timer) -> Self {
+        self.add(timer);
+        self
+    }
+
+    /// Adds a timer, replacing the one with the same tag.
+    pub fn add(&mut self, timer: Timer) {
+        self.remove(&timer.tag);
+        self.timers.push(timer);
+    }
+
+    pub fn remove(&mut self, tag: &str) -> Option<Timer> {
+        let index = self.timers.iter().position(|t| t.tag == tag)?;
+        Some(self.timers.remove(index))
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&Timer> {
+        self.timers.iter().find(|t| t.tag == tag)
+    }
+
+    pub fn get_mut(&mut self, tag: &str) -> Option<&mut Timer> {
+        self.timers.iter_mut().find(|t| t.tag == tag)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Timer> {
+        self.timers.iter()
+    }
+
+    pub fn pause_all(&mut self) {
+        for timer in &mut self.timers {
+            timer.pause();
+        }
+    }
+
+    pub fn resume_all(&mut self) {
+        for timer in &mut self.timers {
+            timer.resume();
+        }
+    }
+
+    /// Advances the timers, and calls `fired` each time one of them fires.
+    fn tick<F: FnMut(&Timer)>(&mut self, time: &Time, mut fired: F) {
+        for timer in &mut self.timers {
+            let delta = timer.clock.delta_seconds(time);
+            for _ in 0..timer.tick(delta) {
+                fired(timer);
+            }
+        }
+    }
+}
+
+impl Component for Timers {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Written to the `EventChannel<TimerFired>` each time a timer fires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimerFired {
+    /// The entity the `Timers` is attached to. None for the global `Timers` resource.
+    pub entity: Option<Entity>,
+    pub tag: String,
+}
+
+/// A callback run when a timer fires.
+pub type TimerCallback = Arc<Fn(&mut World, Option<Entity>) + Send + Sync>;
+
+/// The callbacks used by `Timer::with_callback`, by name.
+#[derive(Default)]
+pub struct TimerCallbacks {
+    callbacks: HashMap<String, TimerCallback>,
+}
+
+impl TimerCallbacks {
+    pub fn add<F>(&mut self, name: &str, callback: F)
+    where
+        F: Fn(&mut World, Option<Entity>) + Send + Sync + 'static,
+    {
+        self.callbacks.insert(name.to_string(), Arc::new(callback));
+    }
+
+    pub fn get(&self, name: &str) -> Option<TimerCallback> {
+        self.callbacks.get(name).cloned()
+    }
+}
+
+/// Updates the `Timers` resource and components.
+/// Fired timers write a `TimerFired` event and run their callback.
+/// Callbacks run when the world is maintained.
+#[derive(Default)]
+pub struct TimerSystem;
+
+impl<'a> System<'a> for TimerSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Timers>,
+        Write<'a, Timers>,
+        Read<'a, Time>,
+        Read<'a, LazyUpdate>,
+        Read<'a, TimerCallbacks>,
+        Write<'a, EventChannel<TimerFired>>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut timers, mut global, time, lazy, callbacks, mut events): Self::SystemData,
+    ) {
+        let mut fired = |entity: Option<Entity>, timer: &Timer| {
+            events.single_write(TimerFired {
+                entity,
+                tag: timer.tag.clone(),
+            });
+            if let Some(ref name) = timer.callback {
+                match callbacks.get(name) {
+                    Some(callback) => lazy.exec_mut(move |world| callback(world, entity)),
+                    None => warn!("Unknown timer callback: {}", name),
+                }
+            }
+        };
+
+        global.tick(&time, |timer| fired(None, timer));
+        for (entity, timers) in (&*entities, &mut timers).join() {
+            timers.tick(&time, |timer| fired(Some(entity), timer));
+        }
+    }
+}
+
+/// A unit of time shown by a `TimerFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeUnit {
+    Hours,
+    Minutes,
+    Seconds,
+}
+
+impl TimeUnit {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            'h' => Some(TimeUnit::Hours),
+            'm' => Some(TimeUnit::Minutes),
+            's' => Some(TimeUnit::Seconds),
+            _ => None,
+        }
+    }
+
+    fn seconds(&self) -> u64 {
+        match *self {
+            TimeUnit::Hours => 3600,
+            TimeUnit::Minutes => 60,
+            TimeUnit::Seconds => 1,
+        }
+    }
+}
+
+/// How a duration is shown, parsed from a spec such as "mm:ss.mmm" or "h:mm:ss".
+///
+/// The fields are hours (`h`), minutes (`m`) and seconds (`s`), separated by colons,
+/// from the largest to the smallest, without gaps. The number of letters is the minimum
+/// number of digits. The first field isn't wrapped, so "mm:ss" shows 75 minutes as "75:00".
+/// The number of `m` after the dot is the number of decimals of the seconds, which are truncated
+/// like on a stopwatch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimerFormat {
+    fields: Vec<(TimeUnit, usize)>,
+    decimals: usize,
+}
+
+impl TimerFormat {
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut parts = spec.splitn(2, '.');
+        let mut fields = vec![];
+        for field in parts.next().unwrap_or("").split(':') {
+            let unit = field.chars().next().and_then(TimeUnit::from_char);
+            match unit {
+                Some(unit) if field.chars().all(|c| TimeUnit::from_char(c) == Some(unit)) => {
+                    fields.push((unit, field.len()))
+                }
+                _ => {
+                    error!("Invalid field \"{}\" in the timer format {}", field, spec);
+                    return None;
+                }
+            }
+        }
+        let units = fields.iter().map(|&(u, _)| u).collect::<Vec<_>>();
+        let valid = [
+            vec![TimeUnit::Hours, TimeUnit::Minutes, TimeUnit::Seconds],
+            vec![TimeUnit::Minutes, TimeUnit::Seconds],
+            vec![TimeUnit::Seconds],
+        ];
+        if !valid.contains(&units) {
+            error!(
+                "The timer format {} must end with seconds and have no gaps between its fields.",
+                spec
+            );
+            return None;
+        }
+        let decimals = match parts.next() {
+            Some(decimals) if !decimals.is_empty() && decimals.chars().all(|c| c == 'm') => {
+                decimals.len()
+            }
+            Some(decimals) => {
+                error!(
+                    "Invalid decimals \"{}\" in the timer format {}",
+                    decimals, spec
+                );
+                return None;
+            }
+            None => 0,
+        };
+        if decimals > 9 {
+            error!("The timer format {} has more than 9 decimals.", spec);
+            return None;
+        }
+        Some(TimerFormat { fields, decimals })
+    }
+
+    /// Formats a duration in seconds. Negative durations are prefixed with a minus sign.
+    pub fn format(&self, seconds: f64) -> String {
+        let sign = if seconds < 0.0 { "-" } else { "" };
+        let scale = 10u64.pow(self.decimals as u32);
+        // Avoids showing 12.344 for 12.345 because of the float representation.
+        let total = (seconds.abs() * scale as f64 + 1e-6).floor() as u64;
+        let (mut whole, fraction) = (total / scale, total % scale);
+
+        let mut text = sign.to_string();
+        for (i, &(unit, width)) in self.fields.iter().enumerate() {
+            let value = whole / unit.seconds();
+            whole %= unit.seconds();
+            if i > 0 {
+                text.push(':');
+            }
+            text.push_str(&format!("{:0width$}", value, width = width));
+        }
+        if self.decimals > 0 {
+            text.push_str(&format!(".{:0width$}", fraction, width = self.decimals));
+        }
+        text
+    }
+}
+
+impl Default for TimerFormat {
+    fn default() -> Self {
+        TimerFormat::parse("mm:ss.mmm").unwrap()
+    }
+}
+
+/// A stopwatch or a countdown shown in the `UiText` of its entity by the `UiTimerSystem`.
+///
+/// Countdowns write a `TimerFired` event with the tag of the timer when they reach zero.
+#[derive(Debug, Clone)]
+pub struct UiTimer {
+    pub format: TimerFormat,
+    /// Counts the time. Stopwatches never fire, their duration is infinite.
+    timer: Timer,
+    /// When a stopwatch started, in absolute seconds of its clock. Applied on the next update.
+    start: Option<f64>,
+    splits: Vec<f64>,
+}
+
+impl UiTimer {
+    /// A stopwatch counting up from zero.
+    pub fn new() -> Self {
+        UiTimer {
+            format: TimerFormat::default(),
+            timer: Timer::once("", ::std::f64::INFINITY),
+            start: None,
+            splits: vec![],
+        }
+    }
+
+    /// A stopwatch counting up from `start`, in absolute seconds of its clock.
+    /// Shows the same time as the `UiTimer { start }` of earlier versions.
+    pub fn started_at(start: f64) -> Self {
+        UiTimer {
+            start: Some(start),
+            ..UiTimer::new()
+        }
+    }
+
+    /// Counts down from `seconds` to zero.
+    pub fn countdown(tag: &str, seconds: f64) -> Self {
+        UiTimer {
+            timer: Timer::once(tag, seconds),
+            ..UiTimer::new()
+        }
+    }
+
+    pub fn with_format(mut self, format: TimerFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.timer.clock = clock;
+        self
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.timer.clock
+    }
+
+    /// Sent along with the `TimerFired` event of countdowns.
+    pub fn tag(&self) -> &str {
+        &self.timer.tag
+    }
+
+    pub fn pause(&mut self) {
+        self.timer.pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.timer.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.timer.is_paused()
+    }
+
+    /// Goes back to zero, or to the start of the countdown, and forgets the splits.
+    pub fn reset(&mut self) {
+        self.timer.reset();
+        self.start = None;
+        self.splits.clear();
+    }
+
+    /// Seconds counted since the start, pauses excluded.
+    pub fn elapsed(&self) -> f64 {
+        self.timer.elapsed()
+    }
+
+    /// Seconds left of a countdown.
+    pub fn remaining(&self) -> Option<f64> {
+        if self.timer.duration.is_finite() {
+            Some(self.timer.remaining())
+        } else {
+            None
+        }
+    }
+
+    /// True once a countdown reached zero.
+    pub fn is_expired(&self) -> bool {
+        self.timer.is_finished()
+    }
+
+    /// Records the current elapsed time and returns the time of the lap it ends.
+    pub fn split(&mut self) -> f64 {
+        let elapsed = self.elapsed();
+        let lap = elapsed - self.splits.last().cloned().unwrap_or(0.0);
+        self.splits.push(elapsed);
+        lap
+    }
+
+    /// The elapsed time of each split.
+    pub fn splits(&self) -> &[f64] {
+        &self.splits
+    }
+
+    /// The duration of each lap ended by a split.
+    pub fn laps(&self) -> Vec<f64> {
+        let mut previous = 0.0;
+        self.splits
+            .iter()
+            .map(|s| {
+                let lap = s - previous;
+                previous = *s;
+                lap
+            })
+            .collect()
+    }
+
+    /// The time shown: the remaining time of countdowns, the elapsed time of stopwatches.
+    pub fn text(&self) -> String {
+        self.format
+            .format(self.remaining().unwrap_or_else(|| self.elapsed()))
+    }
+
+    /// Advances the timer. Returns true if a countdown just expired.
+    fn update(&mut self, time: &Time) -> bool {
+        let clock = self.timer.clock;
+        let start = self.start;
+        let delta = match start {
+            // The time since the start, this frame included.
+            Some(start) if !self.timer.is_paused() => {
+                self.start = None;
+                (clock.absolute_seconds(time) - start).max(0.0)
+            }
+            _ => clock.delta_seconds(time),
+        };
+        self.timer.tick(delta) > 0
+    }
+}
+
+impl Default for UiTimer {
+    fn default() -> Self {
+        UiTimer::new()
+    }
+}
+
+impl Component for UiTimer {
+    type Storage = VecStorage<Self>;
+}
+
+/// Updates the `UiTimer`s and shows them in the `UiText` of their entity.
+pub struct UiTimerSystem;
+
+impl<'a> System<'a> for UiTimerSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, UiTimer>,
+        WriteStorage<'a, UiText>,
+        Read<'a, Time>,
+        Write<'a, EventChannel<TimerFired>>,
+    );
+    fn run(&mut self, (entities, mut timers, mut texts, time, mut events): Self::SystemData) {
+        for (entity, timer) in (&*entities, &mut timers).join() {
+            if timer.update(&time) {
+                events.single_write(TimerFired {
+                    entity: Some(entity),
+                    tag: timer.tag().to_string(),
+                });
+            }
+            if let Some(text) = texts.get_mut(entity) {
+                text.text = timer.text();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amethyst::shrev::EventChannel;
+    use *;
+
+    #[test]
+    fn timers_fire_once_or_repeat() {
+        let mut once = Timer::once("once", 1.0);
+        assert_eq!(once.tick(0.5), 0);
+        assert_eq!(once.tick(0.7), 1);
+        assert!(once.is_finished());
+        assert_eq!(once.tick(5.0), 0);
+        once.reset();
+        assert_eq!(once.tick(1.0), 1);
+
+        let mut repeating = Timer::repeating("repeat", 0.5);
+        assert_eq!(repeating.tick(1.2), 2);
+        assert!((repeating.elapsed() - 0.2).abs() < 1e-9);
+        repeating.pause();
+        assert_eq!(repeating.tick(1.0), 0);
+        repeating.resume();
+        assert_eq!(repeating.tick(0.4), 1);
+        assert!(!repeating.is_finished());
+    }
+
+    #[test]
+    fn cooldown_waits_between_triggers() {
+        let mut time = Time::default();
+        let mut cooldown = Cooldown::new(1.0);
+        assert!(cooldown.trigger(&time));
+        assert!(!cooldown.trigger(&time));
+        time.set_delta_seconds(0.5);
+        assert_eq!(cooldown.remaining(&time), 0.5);
+        assert!(!cooldown.is_ready(&time));
+        time.set_delta_seconds(0.5);
+        assert!(cooldown.trigger(&time));
+        cooldown.reset();
+        assert!(cooldown.is_ready(&time));
+
+        // Real time cooldowns run while the game is paused.
+        let mut real = Cooldown::new(1.0).with_clock(Clock::Real);
+        let mut game = Cooldown::new(1.0);
+        real.start(&time);
+        game.start(&time);
+        time.set_time_scale(0.0);
+        time.set_delta_seconds(1.0);
+        assert!(real.is_ready(&time));
+        assert!(!game.is_ready(&time));
+    }
+
+    #[test]
+    fn timer_system_fires_events_and_callbacks() {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        let mut system = TimerSystem;
+        System::setup(&mut system, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<TimerFired>>()
+            .register_reader();
+        world
+            .write_resource::<TimerCallbacks>()
+            .add("spawn", |world, _| {
+                world.create_entity().build();
+            });
+        world
+            .write_resource::<Timers>()
+            .add(Timer::repeating("wave", 1.0).with_callback("spawn"));
+        let entity = world
+            .create_entity()
+            .with(Timers::new().with(Timer::once("fuse", 1.5)))
+            .build();
+
+        for _ in 0..4 {
+            world.write_resource::<Time>().set_delta_seconds(0.5);
+            system.run_now(&world.res);
+            world.maintain();
+        }
+
+        let fired = world
+            .read_resource::<EventChannel<TimerFired>>()
+            .read(&mut reader)
+            .map(|e| (e.entity, e.tag.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fired,
+            vec![
+                (None, "wave".to_string()),
+                (Some(entity), "fuse".to_string()),
+                (None, "wave".to_string()),
+            ]
+        );
+        // The timed entity and the two spawned ones.
+        assert_eq!((&*world.entities()).join().count(), 3);
+        assert!(world
+            .read_storage::<Timers>()
+            .get(entity)
+            .unwrap()
+            .get("fuse")
+            .unwrap()
+            .is_finished());
+    }
+
+    #[test]
+    fn timer_formats() {
+        let format = TimerFormat::parse("mm:ss.mmm").unwrap();
+        assert_eq!(format.format(12.345), "00:12.345");
+        assert_eq!(format.format(4521.5), "75:21.500");
+        assert_eq!(format.format(-1.25), "-00:01.250");
+        let format = TimerFormat::parse("h:mm:ss").unwrap();
+        assert_eq!(format.format(3723.9), "1:02:03");
+        assert_eq!(TimerFormat::parse("s.mm").unwrap().format(75.456), "75.45");
+
+        assert!(TimerFormat::parse("h:ss").is_none());
+        assert!(TimerFormat::parse("ss:mm").is_none());
+        assert!(TimerFormat::parse("m:xs").is_none());
+        assert!(TimerFormat::parse("ss.sss").is_none());
+        assert!(TimerFormat::parse("ss.mx").is_none());
+        assert!(TimerFormat::parse("ss.").is_none());
+    }
+
+    #[test]
+    fn ui_timer_countdown_and_splits() {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        let mut system = UiTimerSystem;
+        System::setup(&mut system, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<TimerFired>>()
+            .register_reader();
+        let countdown = world
+            .create_entity()
+            .with(UiTimer::countdown("bomb", 1.0).with_format(TimerFormat::parse("s.m").unwrap()))
+            .build();
+        let stopwatch = world.create_entity().with(UiTimer::new()).build();
+
+        let mut step = |world: &mut World, seconds: f32| {
+            world.write_resource::<Time>().set_delta_seconds(seconds);
+            system.run_now(&world.res);
+        };
+        step(&mut world, 0.25);
+        world
+            .write_storage::<UiTimer>()
+            .get_mut(stopwatch)
+            .unwrap()
+            .split();
+        world
+            .write_storage::<UiTimer>()
+            .get_mut(countdown)
+            .unwrap()
+            .pause();
+        step(&mut world, 0.5);
+        assert_eq!(
+            world
+                .read_storage::<UiTimer>()
+                .get(countdown)
+                .unwrap()
+                .text(),
+            "0.7"
+        );
+        world
+            .write_storage::<UiTimer>()
+            .get_mut(countdown)
+            .unwrap()
+            .resume();
+        step(&mut world, 0.5);
+        step(&mut world, 0.5);
+
+        let fired = world
+            .read_resource::<EventChannel<TimerFired>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fired,
+            vec![TimerFired {
+                entity: Some(countdown),
+                tag: "bomb".to_string(),
+            }]
+        );
+        let timers = world.read_storage::<UiTimer>();
+        assert!(timers.get(countdown).unwrap().is_expired());
+        assert_eq!(timers.get(countdown).unwrap().text(), "0.0");
+
+        let mut stopwatch = timers.get(stopwatch).unwrap().clone();
+        assert_eq!(stopwatch.text(), "00:01.750");
+        assert_eq!(stopwatch.split(), 1.5);
+        assert_eq!(stopwatch.laps(), vec![0.25, 1.5]);
+        stopwatch.reset();
+        assert_eq!(stopwatch.elapsed(), 0.0);
+        assert!(stopwatch.splits().is_empty());
+    }
+
+    #[test]
+    fn ui_timer_started_at() {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        let mut system = UiTimerSystem;
+        System::setup(&mut system, &mut world.res);
+        let timer = world
+            .create_entity()
+            .with(UiTimer::started_at(0.25))
+            .build();
+
+        world.write_resource::<Time>().set_delta_seconds(1.0);
+        system.run_now(&world.res);
+        world.write_resource::<Time>().set_delta_seconds(0.5);
+        system.run_now(&world.res);
+        assert_eq!(
+            world
+                .read_storage::<UiTimer>()
+                .get(timer)
+                .unwrap()
+                .elapsed(),
+            1.25
+        );
+    }
+}